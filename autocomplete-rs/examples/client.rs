@@ -1,6 +1,6 @@
 use autocomplete_proto::{
     autocomplete_service_client::AutocompleteServiceClient,
-    CompleteRequest, InitRequest, StringScore,
+    CompleteRequest, CompletionMode, InitMode, InitRequest, ResultOrder, StringScore,
 };
 
 pub mod autocomplete_proto {
@@ -14,10 +14,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize with some test data
     let init_request = InitRequest {
         strings: vec![
-            StringScore { text: "hello".to_string(), score: 1.0 },
-            StringScore { text: "help".to_string(), score: 0.8 },
-            StringScore { text: "hell".to_string(), score: 0.6 },
+            StringScore { text: "hello".to_string(), score: 1.0, payload: None, tags: vec![] },
+            StringScore { text: "help".to_string(), score: 0.8, payload: None, tags: vec![] },
+            StringScore { text: "hell".to_string(), score: 0.6, payload: None, tags: vec![] },
         ],
+        mode: InitMode::Replace as i32,
     };
 
     let response = client.init(init_request).await?;
@@ -27,6 +28,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let request = CompleteRequest {
         prefix: "hel".to_string(),
         max_results: 10,
+        mode: CompletionMode::Prefix as i32,
+        order: ResultOrder::Score as i32,
+        max_edits: None,
+        filter: None,
     };
 
     let response = client.complete(request).await?;
@@ -1,45 +1,863 @@
-use crate::types::ScoreType;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use crate::constants::COMPACTION_THRESHOLD;
+use crate::limits::check_score;
+use crate::types::{IdType, ScoreType, ValueRange};
 use crate::trie::Trie;
-use crate::dictionary::Dictionary;
+use crate::dictionary::{CompactedDictionary, Dictionary, DictionarySnapshot, Payload};
+use crate::term_index::{conjunctive_matches_terms, prefix_matches_terms};
+use crate::topk_index::{IndexType, TopKIndex};
+use crate::infix_index::{infix_matches, substring_matches, InfixIndex};
+use crate::normalizer::{Normalizer, NormalizerChain};
+use crate::tag_index::{TagFilter, TagIndex};
 
+/// How a query is matched against the completions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompletionMode {
+    /// Completions starting with the query string
+    #[default]
+    Prefix,
+    /// Completions containing every complete term of the query and a term
+    /// prefixed by its last token, in any position
+    Conjunctive,
+    /// Completions starting with a string within `max_edits` edits of the
+    /// query, counting insertions, deletions, substitutions and
+    /// transpositions of adjacent characters
+    Fuzzy { max_edits: u32 },
+    /// Completions with a word starting with the query string
+    Infix,
+    /// Completions containing the query string at any position
+    Substring,
+}
+
+/// Order of the completions returned by a query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResultOrder {
+    /// By descending score, ties broken by lexicographic order of the
+    /// normalized text, then by id. The score of a fuzzy completion is
+    /// first multiplied by `FUZZY_EDIT_DISCOUNT` for each edit.
+    #[default]
+    Score,
+    /// The completions selected under `Score`, in lexicographic order of
+    /// their text
+    Lexicographic,
+}
+
+/// How `Init` treats the completions already indexed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InitMode {
+    /// Add the completions, replacing the score, payload and tags of those
+    /// already indexed
+    #[default]
+    Append,
+    /// Index only the given completions
+    Replace,
+}
+
+/// Structures built by an `Autocomplete`, beyond the term-level index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexConfig {
+    /// Index answering conjunctive queries, and prefix queries without the
+    /// character trie
+    pub index_type: IndexType,
+    /// Keep a character trie of the completions, answering prefix queries
+    /// by character prefix, and fuzzy queries, which are rejected otherwise.
+    /// Without it, prefix queries match term by term.
+    pub trie: bool,
+    /// Keep suffix arrays of the completions for infix and substring
+    /// queries, which are rejected otherwise
+    pub infix: bool,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            index_type: IndexType::default(),
+            trie: true,
+            infix: false,
+        }
+    }
+}
+
+/// A completion to index, with the data attached to it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompletionEntry {
+    pub text: String,
+    pub score: ScoreType,
+    /// Returned with the completion
+    pub payload: Payload,
+    /// Matched by the `TagFilter` of a query
+    pub tags: Vec<String>,
+}
+
+/// Completions indexed for top-k queries.
+///
+/// Changes are applied at once to the dictionary, tags and character trie, and
+/// recorded as pending for the term-level and infix indexes, whose entries
+/// for the changed completions are then ignored. Queries merge the results
+/// of these indexes with a scan of the pending completions, normalized and
+/// split into terms when they changed, until a compaction rebuilds the
+/// indexes. A compaction is built from a snapshot,
+/// so a shared autocomplete only needs to be locked to take the snapshot
+/// and to swap the new indexes in.
 #[derive(Clone)]
 pub struct Autocomplete {
-    trie: Trie,
+    config: IndexConfig,
+    /// Character trie for prefix and fuzzy queries, if enabled
+    trie: Option<Trie>,
     dictionary: Dictionary,
+    scores: Vec<ScoreType>,
+    index: Arc<dyn TopKIndex>,
+    /// Suffix arrays for infix and substring queries, if enabled
+    infix: Option<InfixIndex>,
+    tags: TagIndex,
+    normalizer: NormalizerChain,
+    /// Number of changes to completions so far
+    generation: u64,
+    /// Generation of the snapshot `index` and `infix` were built from
+    indexed_generation: u64,
+    /// Generation of the snapshot of the last compaction started
+    compaction_generation: u64,
+    /// Completions changed since `indexed_generation`, by id
+    pending: HashMap<IdType, PendingChange>,
+    /// Error of the last compaction if it failed, until a completion is
+    /// removed or the indexes are rebuilt
+    compaction_error: Option<String>,
+    /// Changes made since the replacement being built began
+    journal: Option<Journal>,
+}
+
+/// Changes to an autocomplete since a `Replacement` of it began, replayed
+/// on the replacement when it is swapped in
+#[derive(Clone)]
+struct Journal {
+    /// Token of the replacement, dropped with it if it is abandoned
+    token: Weak<()>,
+    /// Generation of the autocomplete when the replacement began
+    generation: u64,
+    changes: Vec<Change>,
+}
+
+/// Change recorded in a `Journal`
+#[derive(Clone)]
+enum Change {
+    Insert(String, ScoreType),
+    Upsert(CompletionEntry),
+    UpdateScore(String, ScoreType),
+    Remove(String),
+}
+
+/// Empty autocomplete to fill and swap in for the one it was begun from,
+/// see `Autocomplete::begin_replace`
+pub struct Replacement {
+    pub fresh: Autocomplete,
+    token: Arc<()>,
+}
+
+/// Last change of a completion not reflected in the indexes yet
+#[derive(Clone)]
+struct PendingChange {
+    generation: u64,
+    /// Normalized text of the completion, `None` if it was removed
+    text: Option<PendingText>,
+}
+
+/// Normalized text of a pending completion with its terms, matched by
+/// queries until a compaction indexes it
+#[derive(Clone)]
+struct PendingText {
+    normalized: String,
+    terms: Vec<String>,
+}
+
+impl PendingText {
+    fn new(normalized: String) -> Self {
+        let terms = normalized.split_whitespace().map(str::to_string).collect();
+        Self { normalized, terms }
+    }
+
+    fn bytes(&self) -> usize {
+        self.normalized.len() + self.terms.iter().map(String::len).sum::<usize>()
+    }
+}
+
+/// Snapshot of an `Autocomplete` from which its indexes are rebuilt without
+/// borrowing it, see `Autocomplete::compaction`
+pub struct Compaction {
+    generation: u64,
+    config: IndexConfig,
+    normalizer: NormalizerChain,
+    dictionary: DictionarySnapshot,
+    scores: Vec<ScoreType>,
+}
+
+impl Compaction {
+    /// Get the generation of the snapshot
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Build the indexes of the completions of the snapshot. Fails if they
+    /// cannot hold the completions.
+    pub fn run(self) -> Result<CompactedIndexes, String> {
+        let normalized: Vec<(IdType, String, ScoreType)> = self
+            .dictionary
+            .iter()
+            .map(|(id, text)| (id, self.normalizer.normalize(&text), self.scores[id as usize]))
+            .collect();
+        let completions = || {
+            normalized.iter().map(|(id, text, score)| (*id, text.as_str(), *score))
+        };
+        let infix = self
+            .config
+            .infix
+            .then(|| InfixIndex::build(completions()))
+            .transpose()?;
+        Ok(CompactedIndexes {
+            generation: self.generation,
+            dictionary: self.dictionary.compact(),
+            index: self.config.index_type.build(completions())?,
+            infix,
+        })
+    }
+}
+
+/// Indexes built by a `Compaction`, to be swapped into the autocomplete with
+/// `Autocomplete::finish_compaction`
+pub struct CompactedIndexes {
+    generation: u64,
+    dictionary: CompactedDictionary,
+    index: Arc<dyn TopKIndex>,
+    infix: Option<InfixIndex>,
 }
 
 impl Autocomplete {
     pub fn new() -> Self {
+        Self::with_config(IndexConfig::default())
+    }
+
+    /// Create an empty autocomplete answering term-level queries with an
+    /// index of the given type
+    pub fn with_index_type(index_type: IndexType) -> Self {
+        Self::with_config(IndexConfig {
+            index_type,
+            ..IndexConfig::default()
+        })
+    }
+
+    /// Create an empty autocomplete building the structures of `config`
+    pub fn with_config(config: IndexConfig) -> Self {
         Self {
-            trie: Trie::new(),
+            config,
+            trie: config.trie.then(Trie::new),
             dictionary: Dictionary::new(),
+            scores: Vec::new(),
+            index: config.index_type.build([]).expect("an empty index always builds"),
+            infix: config.infix.then(InfixIndex::new),
+            tags: TagIndex::new(),
+            normalizer: NormalizerChain::new(),
+            generation: 0,
+            indexed_generation: 0,
+            compaction_generation: 0,
+            pending: HashMap::new(),
+            compaction_error: None,
+            journal: None,
         }
     }
 
+    /// Normalize completions and queries with `normalizer` before matching
+    /// them. Completions are still returned in their original form.
+    pub fn with_normalizer(mut self, normalizer: NormalizerChain) -> Self {
+        self.normalizer = normalizer;
+        self
+    }
+
+    /// Get the normalizer applied to completions and queries
+    pub fn normalizer(&self) -> &NormalizerChain {
+        &self.normalizer
+    }
+
+    /// Create an empty autocomplete with the same configuration and
+    /// normalizer. Its generations continue from those of this one.
+    pub fn empty_like(&self) -> Self {
+        let mut empty = Self::with_config(self.config).with_normalizer(self.normalizer.clone());
+        empty.generation = self.generation;
+        empty.indexed_generation = self.generation;
+        empty.compaction_generation = self.generation;
+        empty
+    }
+
+    /// Index `strings` under their normalized form, then rebuild the
+    /// indexes. Strings with the same normalized form match the same queries
+    /// but are distinct completions, ranked by score, then by id. Payloads
+    /// and tags of strings already indexed are kept. Fails, indexing none of
+    /// them, if one is blank once normalized or has a non-finite score.
     pub fn init(&mut self, strings: &[(String, ScoreType)]) -> Result<(), String> {
-        for (string, score) in strings {
+        self.insert_all(strings.iter().map(|(string, score)| (string, *score, None)))?;
+        self.compact()
+    }
+
+    /// Index `entries` like `init`, replacing the payload and tags of each
+    /// of them
+    pub fn init_entries(&mut self, entries: &[CompletionEntry]) -> Result<(), String> {
+        self.upsert(entries)?;
+        self.compact()
+    }
+
+    fn insert_all<'a, I>(&mut self, entries: I) -> Result<(), String>
+    where
+        I: Iterator<Item = (&'a String, ScoreType, Option<&'a CompletionEntry>)> + Clone,
+    {
+        self.check_compaction()?;
+        for (string, score, _) in entries.clone() {
+            self.check_text(string)?;
+            check_score(score)?;
+        }
+        for (string, score, entry) in entries {
             let id = self.dictionary.insert(string.clone());
-            self.trie.insert(string, id, *score);
+            if let Some(entry) = entry {
+                self.dictionary.set_payload(id, entry.payload.clone());
+                self.tags.set_tags(id, &entry.tags);
+            }
+            let normalized = self.normalizer.normalize(string);
+            if let Some(trie) = &mut self.trie {
+                trie.insert(&normalized, id, score);
+            }
+            if id as usize == self.scores.len() {
+                self.scores.push(score);
+            } else {
+                self.scores[id as usize] = score;
+            }
+            self.mark_changed(id, Some(normalized));
+            self.record(|| match entry {
+                Some(entry) => Change::Upsert(entry.clone()),
+                None => Change::Insert(string.clone(), score),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that `text` has a term once normalized, as completions without
+    /// terms would match every query or none depending on the mode
+    fn check_text(&self, text: &str) -> Result<(), String> {
+        if self.normalizer.normalize(text).split_whitespace().next().is_none() {
+            return Err(format!("completion {:?} is blank once normalized", text));
+        }
+        Ok(())
+    }
+
+    /// Begin replacing this autocomplete with the `empty_like` one of the
+    /// replacement. Changes made to this one until `replace` are recorded,
+    /// and a replacement begun earlier is superseded.
+    pub fn begin_replace(&mut self) -> Replacement {
+        let token = Arc::new(());
+        self.journal = Some(Journal {
+            token: Arc::downgrade(&token),
+            generation: self.generation,
+            changes: Vec::new(),
+        });
+        Replacement { fresh: self.empty_like(), token }
+    }
+
+    /// Swap in `replacement`, after replaying on it the changes made to this
+    /// autocomplete since it began. Changes to completions it lacks are
+    /// dropped. Fails, keeping this one, if a later replacement began or a
+    /// change cannot be replayed.
+    pub fn replace(&mut self, replacement: Replacement) -> Result<(), String> {
+        let token = Arc::downgrade(&replacement.token);
+        let journal = match self.journal.take() {
+            Some(journal) if journal.token.ptr_eq(&token) => journal,
+            journal => {
+                self.journal = journal;
+                return Err("replacement superseded by a later one".to_string());
+            }
+        };
+        let mut fresh = replacement.fresh;
+        fresh.advance_generations(self.generation - journal.generation);
+        for change in journal.changes {
+            fresh.replay(change)?;
         }
+        *self = fresh;
         Ok(())
     }
 
-    pub fn complete(&self, prefix: &str) -> Vec<(String, ScoreType)> {
-        let completions = self.trie.complete(prefix);
-        completions
+    /// Shift the generations by `by`, so that those of a replacement follow
+    /// those of the changes it replays
+    fn advance_generations(&mut self, by: u64) {
+        self.generation += by;
+        self.indexed_generation += by;
+        self.compaction_generation += by;
+        for change in self.pending.values_mut() {
+            change.generation += by;
+        }
+    }
+
+    fn replay(&mut self, change: Change) -> Result<(), String> {
+        match change {
+            Change::Insert(text, score) => self.insert_all(std::iter::once((&text, score, None))),
+            Change::Upsert(entry) => self.upsert(std::slice::from_ref(&entry)),
+            Change::UpdateScore(text, score) if self.contains(&text) => {
+                self.update_score(&text, score)
+            }
+            Change::Remove(text) if self.contains(&text) => self.remove(&text),
+            Change::UpdateScore(..) | Change::Remove(_) => Ok(()),
+        }
+    }
+
+    /// Record `change` for the replacement being built, unless it was
+    /// abandoned
+    fn record(&mut self, change: impl FnOnce() -> Change) {
+        match &mut self.journal {
+            Some(journal) if journal.token.strong_count() > 0 => journal.changes.push(change()),
+            _ => self.journal = None,
+        }
+    }
+
+    /// Add a completion that is not indexed yet
+    pub fn add(&mut self, entry: CompletionEntry) -> Result<(), String> {
+        if self.contains(&entry.text) {
+            return Err(format!("completion {:?} already exists", entry.text));
+        }
+        self.upsert(std::slice::from_ref(&entry))
+    }
+
+    /// Change the score of the completion `text`. Fails if `score` is not
+    /// finite.
+    pub fn update_score(&mut self, text: &str, score: ScoreType) -> Result<(), String> {
+        self.check_compaction()?;
+        check_score(score)?;
+        let id = self
+            .dictionary
+            .get_id(text)
+            .ok_or_else(|| format!("completion {:?} not found", text))?;
+        self.scores[id as usize] = score;
+        let normalized = self.normalizer.normalize(text);
+        if let Some(trie) = &mut self.trie {
+            trie.insert(&normalized, id, score);
+        }
+        self.mark_changed(id, Some(normalized));
+        self.record(|| Change::UpdateScore(text.to_string(), score));
+        Ok(())
+    }
+
+    /// Remove the completion `text`. Clears the error of a failed
+    /// compaction, so that the next one is tried again.
+    pub fn remove(&mut self, text: &str) -> Result<(), String> {
+        let id = self
+            .dictionary
+            .remove(text)
+            .ok_or_else(|| format!("completion {:?} not found", text))?;
+        self.tags.set_tags::<&str>(id, &[]);
+        if let Some(trie) = &mut self.trie {
+            trie.remove_id(&self.normalizer.normalize(text), id);
+        }
+        self.mark_changed(id, None);
+        self.record(|| Change::Remove(text.to_string()));
+        self.compaction_error = None;
+        Ok(())
+    }
+
+    /// Add the completions `entries`, or replace the score, payload and tags
+    /// of those already indexed. Fails, changing none of them, if one is
+    /// blank once normalized or has a non-finite score.
+    pub fn upsert(&mut self, entries: &[CompletionEntry]) -> Result<(), String> {
+        self.insert_all(entries.iter().map(|entry| (&entry.text, entry.score, Some(entry))))
+    }
+
+    /// Check if the completion `text` is indexed
+    pub fn contains(&self, text: &str) -> bool {
+        self.dictionary.get_id(text).is_some()
+    }
+
+    /// Record a change to the completion `id`, pending until a compaction,
+    /// with its `normalized` text unless it was removed
+    fn mark_changed(&mut self, id: IdType, normalized: Option<String>) {
+        self.generation += 1;
+        let change = PendingChange {
+            generation: self.generation,
+            text: normalized.map(PendingText::new),
+        };
+        self.pending.insert(id, change);
+    }
+
+    /// Take a snapshot of the completions to rebuild the indexes from.
+    /// Only the completions changed since the last compaction and the
+    /// scores are copied.
+    pub fn compaction(&mut self) -> Compaction {
+        self.compaction_generation = self.generation;
+        Compaction {
+            generation: self.generation,
+            config: self.config,
+            normalizer: self.normalizer.clone(),
+            dictionary: self.dictionary.snapshot(),
+            scores: self.scores.clone(),
+        }
+    }
+
+    /// Swap in the indexes built by a compaction. Changes made since its
+    /// snapshot stay pending. Returns false, leaving the indexes untouched,
+    /// if they are no newer than the current ones.
+    pub fn finish_compaction(&mut self, indexes: CompactedIndexes) -> bool {
+        if indexes.generation <= self.indexed_generation {
+            return false;
+        }
+        self.dictionary.finish_compaction(indexes.dictionary);
+        self.index = indexes.index;
+        self.infix = indexes.infix;
+        self.indexed_generation = indexes.generation;
+        self.pending.retain(|_, change| change.generation > indexes.generation);
+        self.compaction_error = None;
+        true
+    }
+
+    /// Record that the compaction of the snapshot taken at `generation`
+    /// failed with `error`. Until a completion is removed, no compaction is
+    /// needed and changes adding completions or scores fail, as they could
+    /// only grow the pending changes. Returns false, ignoring the error, if
+    /// the indexes were since rebuilt from a later snapshot.
+    pub fn fail_compaction(&mut self, generation: u64, error: String) -> bool {
+        if generation <= self.indexed_generation {
+            return false;
+        }
+        self.compaction_generation = self.indexed_generation;
+        self.compaction_error = Some(error);
+        true
+    }
+
+    /// Get the error of the last compaction, if it failed and no completion
+    /// was removed since
+    pub fn compaction_error(&self) -> Option<&str> {
+        self.compaction_error.as_deref()
+    }
+
+    fn check_compaction(&self) -> Result<(), String> {
+        match &self.compaction_error {
+            Some(error) => Err(format!("indexes cannot be rebuilt: {}", error)),
+            None => Ok(()),
+        }
+    }
+
+    /// Rebuild the indexes over the current completions, clearing the
+    /// pending changes. A failure is recorded like with `fail_compaction`.
+    pub fn compact(&mut self) -> Result<(), String> {
+        let compaction = self.compaction();
+        let generation = compaction.generation();
+        match compaction.run() {
+            Ok(indexes) => {
+                self.finish_compaction(indexes);
+                Ok(())
+            }
+            Err(error) => {
+                self.fail_compaction(generation, error.clone());
+                Err(error)
+            }
+        }
+    }
+
+    /// Check if `COMPACTION_THRESHOLD` changes were made since the last
+    /// compaction started, unless the last one failed
+    pub fn needs_compaction(&self) -> bool {
+        self.compaction_error.is_none()
+            && self.generation - self.compaction_generation >= COMPACTION_THRESHOLD
+    }
+
+    /// Get the number of changes to completions so far
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Get the generation the indexes were last rebuilt at
+    pub fn indexed_generation(&self) -> u64 {
+        self.indexed_generation
+    }
+
+    /// Get the number of completions changed since the indexes were rebuilt
+    pub fn num_pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Normalize `query`, keeping a trailing separator so that its last
+    /// term still counts as complete
+    fn normalize_query(&self, query: &str) -> String {
+        let mut normalized = self.normalizer.normalize(query);
+        let ends_with_space = |s: &str| s.ends_with(char::is_whitespace);
+        if ends_with_space(query) && !normalized.is_empty() && !ends_with_space(&normalized) {
+            normalized.push(' ');
+        }
+        normalized
+    }
+
+    /// Return the `k` highest-scoring completions starting with `prefix`,
+    /// best first, breaking ties by lexicographic order of the normalized
+    /// text, then by id. Without the character trie, their first terms are
+    /// the complete terms of `prefix` and their next term is prefixed by its
+    /// last token.
+    pub fn complete(&self, prefix: &str, k: usize) -> Vec<(String, ScoreType)> {
+        self.search(prefix, k, CompletionMode::Prefix, &TagFilter::new())
+            .expect("prefix queries are always enabled")
+    }
+
+    /// Return the `k` highest-scoring completions containing every complete
+    /// term of `query` and a term prefixed by its last token, best first,
+    /// breaking ties by lexicographic order of the normalized text, then by id
+    pub fn conjunctive_complete(&self, query: &str, k: usize) -> Vec<(String, ScoreType)> {
+        self.search(query, k, CompletionMode::Conjunctive, &TagFilter::new())
+            .expect("conjunctive queries are always enabled")
+    }
+
+    /// Return the `k` best completions starting with a string within
+    /// `max_edits` edits of `prefix`, ranked by their score discounted by
+    /// `FUZZY_EDIT_DISCOUNT` per edit, then lexicographically. Fails unless
+    /// the `IndexConfig` keeps the character trie.
+    pub fn fuzzy_complete(
+        &self,
+        prefix: &str,
+        max_edits: u32,
+        k: usize,
+    ) -> Result<Vec<(String, ScoreType)>, String> {
+        self.search(prefix, k, CompletionMode::Fuzzy { max_edits }, &TagFilter::new())
+    }
+
+    fn fuzzy_trie(&self) -> Result<&Trie, String> {
+        self.trie.as_ref().ok_or_else(|| {
+            "fuzzy queries are not enabled for this index, which has no character trie".to_string()
+        })
+    }
+
+    /// Return the `k` highest-scoring completions with a word starting with
+    /// `query`, best first, breaking ties by lexicographic order. Fails
+    /// unless infix queries are enabled in the `IndexConfig`.
+    pub fn infix_complete(
+        &self,
+        query: &str,
+        k: usize,
+    ) -> Result<Vec<(String, ScoreType)>, String> {
+        self.search(query, k, CompletionMode::Infix, &TagFilter::new())
+    }
+
+    /// Return the `k` highest-scoring completions containing `query` at any
+    /// position, best first, breaking ties by lexicographic order. Fails
+    /// unless infix queries are enabled in the `IndexConfig`.
+    pub fn substring_complete(
+        &self,
+        query: &str,
+        k: usize,
+    ) -> Result<Vec<(String, ScoreType)>, String> {
+        self.search(query, k, CompletionMode::Substring, &TagFilter::new())
+    }
+
+    fn infix_index(&self) -> Result<&InfixIndex, String> {
+        self.infix
+            .as_ref()
+            .ok_or_else(|| "infix queries are not enabled for this index".to_string())
+    }
+
+    /// Return the `k` highest-scoring completions of `query` under `mode`.
+    /// Fails if `mode` is not enabled in the `IndexConfig`.
+    pub fn complete_with_mode(
+        &self,
+        query: &str,
+        k: usize,
+        mode: CompletionMode,
+    ) -> Result<Vec<(String, ScoreType)>, String> {
+        self.search(query, k, mode, &TagFilter::new())
+    }
+
+    /// Return the `k` highest-scoring completions of `query` under `mode`,
+    /// in the given order
+    pub fn complete_with_order(
+        &self,
+        query: &str,
+        k: usize,
+        mode: CompletionMode,
+        order: ResultOrder,
+    ) -> Result<Vec<(String, ScoreType)>, String> {
+        self.complete_with_filter(query, k, mode, order, &TagFilter::new())
+    }
+
+    /// Return the `k` highest-scoring completions of `query` under `mode`
+    /// that pass `filter`, in the given order. Completions are filtered
+    /// while searching, so up to `k` of them are returned even if better
+    /// ones are rejected.
+    pub fn complete_with_filter(
+        &self,
+        query: &str,
+        k: usize,
+        mode: CompletionMode,
+        order: ResultOrder,
+        filter: &TagFilter,
+    ) -> Result<Vec<(String, ScoreType)>, String> {
+        let mut completions = self.search(query, k, mode, filter)?;
+        if order == ResultOrder::Lexicographic {
+            completions.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        Ok(completions)
+    }
+
+    /// Return the `k` best completions of `query` under `mode` that pass
+    /// `filter`, best first. A query left empty by the normalizer matches
+    /// nothing, while an empty query matches every completion in the modes
+    /// that accept it.
+    fn search(
+        &self,
+        query: &str,
+        k: usize,
+        mode: CompletionMode,
+        filter: &TagFilter,
+    ) -> Result<Vec<(String, ScoreType)>, String> {
+        let normalized = self.normalize_query(query);
+        if normalized.is_empty() && !query.is_empty() {
+            self.check_mode(mode)?;
+            return Ok(Vec::new());
+        }
+        let query = normalized;
+        let ids = self.topk(&query, k, mode, &self.tags.matcher(filter))?;
+        Ok(self.scored_completions(ids))
+    }
+
+    /// Check that the structures answering `mode` are enabled
+    fn check_mode(&self, mode: CompletionMode) -> Result<(), String> {
+        match mode {
+            CompletionMode::Fuzzy { .. } => self.fuzzy_trie().map(|_| ()),
+            CompletionMode::Infix | CompletionMode::Substring => self.infix_index().map(|_| ()),
+            CompletionMode::Prefix | CompletionMode::Conjunctive => Ok(()),
+        }
+    }
+
+    /// Return the ids of the `k` best completions matching the normalized
+    /// `query` under `mode` whose ids are accepted by `filter`, merging the
+    /// results of the indexes with the pending completions
+    fn topk(
+        &self,
+        query: &str,
+        k: usize,
+        mode: CompletionMode,
+        filter: &dyn Fn(IdType) -> bool,
+    ) -> Result<Vec<IdType>, String> {
+        // The trie is changed in place, so nothing is pending for it
+        if let (CompletionMode::Prefix, Some(trie)) = (mode, &self.trie) {
+            let ids = trie.topk_filtered(query, k, filter);
+            return Ok(ids.into_iter().map(|(id, _)| id).collect());
+        }
+        let indexed = |id| !self.pending.contains_key(&id) && filter(id);
+        let (ids, matches): (_, fn(&PendingText, &str) -> bool) = match mode {
+            CompletionMode::Prefix => (
+                self.index.prefix_topk_filtered(query, k, &indexed),
+                |text, query| prefix_matches_terms(&text.terms, query),
+            ),
+            CompletionMode::Conjunctive => (
+                self.index.conjunctive_topk_filtered(query, k, &indexed),
+                |text, query| conjunctive_matches_terms(&text.terms, query),
+            ),
+            CompletionMode::Infix => (
+                self.infix_index()?.infix_topk_filtered(query, k, &indexed),
+                |text, query| infix_matches(&text.normalized, query),
+            ),
+            CompletionMode::Substring => (
+                self.infix_index()?.substring_topk_filtered(query, k, &indexed),
+                |text, query| substring_matches(&text.normalized, query),
+            ),
+            // Like prefix queries, fuzzy ones are answered by the trie alone
+            CompletionMode::Fuzzy { max_edits } => {
+                let ids = self.fuzzy_trie()?.fuzzy_topk_filtered(query, max_edits, k, filter);
+                return Ok(ids.into_iter().map(|(id, _, _)| id).collect());
+            }
+        };
+        if self.pending.is_empty() {
+            return Ok(ids);
+        }
+
+        // Pending completions carry their normalized text, and only the
+        // results of the indexes are normalized again
+        let pending = self.pending.iter().filter_map(|(&id, change)| {
+            let text = change.text.as_ref().filter(|_| filter(id))?;
+            matches(text, query).then_some((id, Cow::Borrowed(text.normalized.as_str())))
+        });
+        let normalized = |id| {
+            let text = self.dictionary.get(id)?;
+            Some(Cow::Owned(self.normalizer.normalize(&text)))
+        };
+        let mut merged: Vec<(IdType, Cow<str>)> = ids
             .into_iter()
-            .filter_map(|(id, score)| {
-                self.dictionary.get(id).map(|text| (text.to_string(), score))
+            .filter_map(|id| Some((id, normalized(id)?)))
+            .chain(pending)
+            .collect();
+        let score = |id: IdType| self.scores[id as usize];
+        merged.sort_by(|a, b| {
+            score(b.0).total_cmp(&score(a.0)).then_with(|| a.1.cmp(&b.1)).then(a.0.cmp(&b.0))
+        });
+        Ok(merged.into_iter().take(k).map(|(id, _)| id).collect())
+    }
+
+    /// Get the inclusive range of lexicographic positions of the completions
+    /// whose first terms are the complete terms of `query` and whose next
+    /// term is prefixed by its last token. The number of matches is the
+    /// length of the range, which is invalid if nothing matches. Positions
+    /// are those of the completions at the last compaction, pending changes
+    /// excluded.
+    pub fn locate_prefix(&self, query: &str) -> ValueRange {
+        self.index.locate_prefix(&self.normalize_query(query))
+    }
+
+    /// Get the text and score of each of the completions `ids`
+    fn scored_completions(&self, ids: Vec<IdType>) -> Vec<(String, ScoreType)> {
+        ids.into_iter()
+            .filter_map(|id| {
+                self.dictionary
+                    .get(id)
+                    .map(|text| (text.to_string(), self.scores[id as usize]))
             })
             .collect()
     }
 
+    /// Get the payload attached to the completion `text`, as returned by the
+    /// completion methods
+    pub fn payload(&self, text: &str) -> Option<&Payload> {
+        self.dictionary.get_id(text).and_then(|id| self.dictionary.payload(id))
+    }
+
+    /// Get the tags of the completion `text`, sorted
+    pub fn tags(&self, text: &str) -> Vec<&str> {
+        match self.dictionary.get_id(text) {
+            Some(id) => self.tags.tags(id),
+            None => Vec::new(),
+        }
+    }
+
+    /// Get the type of the index answering term-level queries
+    pub fn index_type(&self) -> IndexType {
+        self.config.index_type
+    }
+
+    /// Get the structures built by this autocomplete
+    pub fn config(&self) -> IndexConfig {
+        self.config
+    }
+
     pub fn num_terms(&self) -> usize {
         self.dictionary.len()
     }
 
     pub fn bytes(&self) -> usize {
-        // TODO: Implement actual memory usage calculation
-        0
+        self.dictionary.bytes()
+            + self.scores.len() * std::mem::size_of::<ScoreType>()
+            + self.index.bytes()
+            + self.infix.as_ref().map_or(0, InfixIndex::bytes)
+            + self.tags.bytes()
+            + self.trie.as_ref().map_or(0, Trie::bytes)
+            + self.pending.len() * std::mem::size_of::<(IdType, PendingChange)>()
+            + self
+                .pending
+                .values()
+                .filter_map(|change| change.text.as_ref())
+                .map(PendingText::bytes)
+                .sum::<usize>()
     }
-} 
\ No newline at end of file
+}
+
+impl Default for Autocomplete {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,126 @@
+use crate::ef::EfSequence;
+use crate::index::{bits_for, CompactVector, CompactVectorBuilder};
+use crate::types::{IdType, ValueRange};
+
+/// Forward index mapping each doc to the ids of its terms, bit-packed.
+///
+/// The term lists of all docs are concatenated in a compact vector, with
+/// every term id taking `ceil(log2(num_terms + 1))` bits, and the start of
+/// each list is kept in an Elias-Fano sequence. This lets a search check which terms a candidate
+/// completion contains without decoding its string.
+#[derive(Debug, Clone)]
+pub struct CompactForwardIndex {
+    num_terms: usize,
+    pointers: EfSequence,
+    data: CompactVector,
+}
+
+impl CompactForwardIndex {
+    /// Create a new empty index
+    pub fn new() -> Self {
+        Self {
+            num_terms: 0,
+            pointers: EfSequence::build([0]).expect("a single value is sorted"),
+            data: CompactVector::new(),
+        }
+    }
+
+    /// Build the index from the term ids, from 1 to `num_terms`, of each doc
+    pub fn build<I, T>(forward: I, num_terms: usize) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[IdType]>,
+    {
+        let mut pointers = vec![0];
+        let mut data = CompactVectorBuilder::new(bits_for(num_terms as u64));
+        for terms in forward {
+            for &term_id in terms.as_ref() {
+                if term_id == 0 || term_id as usize > num_terms {
+                    return Err(format!("term id {} out of range 1..={}", term_id, num_terms));
+                }
+                data.push(term_id as u64);
+            }
+            pointers.push(data.len() as u64);
+        }
+        Ok(Self {
+            num_terms,
+            pointers: EfSequence::build(pointers)?,
+            data: data.build(),
+        })
+    }
+
+    /// Iterate over the term ids of `doc_id`, in their original order
+    pub fn iter(&self, doc_id: IdType) -> impl Iterator<Item = IdType> + '_ {
+        assert!((doc_id as usize) < self.num_docs(), "doc id {} out of bounds", doc_id);
+        let begin = self.pointers.access(doc_id as usize) as usize;
+        let end = self.pointers.access(doc_id as usize + 1) as usize;
+        (begin..end).map(move |i| self.data.access(i) as IdType)
+    }
+
+    /// Check if `doc_id` contains a term in the inclusive `range`
+    pub fn contains_term_in_range(&self, doc_id: IdType, range: ValueRange) -> bool {
+        !range.is_invalid() && self.iter(doc_id).any(|term_id| range.contains(term_id as u64))
+    }
+
+    /// Get the number of docs
+    pub fn num_docs(&self) -> usize {
+        self.pointers.len() - 1
+    }
+
+    /// Get the number of terms
+    pub fn num_terms(&self) -> usize {
+        self.num_terms
+    }
+
+    /// Get the total number of term ids stored
+    pub fn num_integers(&self) -> usize {
+        self.pointers.universe() as usize
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        std::mem::size_of_val(&self.num_terms)
+            + self.pointers.bytes()
+            + self.data.bytes()
+    }
+}
+
+impl Default for CompactForwardIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iter_and_contains_term_in_range() {
+        let forward: Vec<Vec<IdType>> = vec![vec![2, 6, 5], vec![], vec![6, 2, 4], vec![1]];
+        let index = CompactForwardIndex::build(&forward, 6).unwrap();
+        assert_eq!(index.num_docs(), 4);
+        assert_eq!(index.num_integers(), 7);
+        for (doc_id, terms) in forward.iter().enumerate() {
+            assert_eq!(index.iter(doc_id as IdType).collect::<Vec<_>>(), *terms);
+        }
+
+        let range = |begin, end| ValueRange { begin, end };
+        assert!(index.contains_term_in_range(0, range(5, 5)));
+        assert!(index.contains_term_in_range(2, range(3, 4)));
+        assert!(!index.contains_term_in_range(2, range(5, 5)));
+        assert!(!index.contains_term_in_range(1, range(1, 6)));
+        assert!(!index.contains_term_in_range(3, crate::types::global::INVALID_RANGE));
+    }
+
+    #[test]
+    fn test_bit_packing() {
+        let forward: Vec<Vec<IdType>> = (0..1000).map(|i| vec![i % 1000 + 1; 4]).collect();
+        let index = CompactForwardIndex::build(&forward, 1000).unwrap();
+        // 10 bits per term id, plus the pointers
+        assert!(index.bytes() < 4000 * std::mem::size_of::<IdType>() / 2);
+        assert_eq!(index.iter(999).collect::<Vec<_>>(), vec![1000; 4]);
+        assert!(CompactForwardIndex::build([vec![7]], 6).is_err());
+        assert_eq!(CompactForwardIndex::new().num_docs(), 0);
+    }
+}
@@ -0,0 +1,412 @@
+use crate::ef::EfSequence;
+use crate::index::CompactVector;
+use crate::parameters::Parameters;
+use crate::types::{global, CompletionType, IdType, ValueRange};
+
+/// Static trie over sorted completions, given as sequences of term ids,
+/// stored level by level in compressed arrays.
+///
+/// Level `i` holds one node per distinct prefix of `i + 1` terms, in
+/// lexicographic order. For each node it keeps its last term id (its label),
+/// the position of its first child in level `i + 1` and the range of
+/// lexicographic positions, starting from 0, of the completions below it.
+/// Labels are bit-packed, while child pointers, range starts and prefix sums
+/// of range lengths are non-decreasing and kept in Elias-Fano sequences.
+#[derive(Debug, Clone, Default)]
+pub struct SuccinctCompletionTrie {
+    size: usize,
+    nodes: Vec<CompactVector>,
+    pointers: Vec<EfSequence>,
+    left_extremes: Vec<EfSequence>,
+    sizes: Vec<EfSequence>,
+}
+
+impl SuccinctCompletionTrie {
+    /// Create a new empty trie
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the trie from strictly increasing completions
+    pub fn build<I, C>(completions: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = C>,
+        C: AsRef<[IdType]>,
+    {
+        let mut builder = SuccinctCompletionTrieBuilder::new();
+        for c in completions {
+            builder.push(c.as_ref())?;
+        }
+        Ok(builder.build())
+    }
+
+    /// Get the inclusive range of lexicographic positions of the completions
+    /// starting with the term ids of `prefix` and continuing with a term id
+    /// in `suffix_range`, or `global::INVALID_RANGE` if there is none
+    pub fn locate_prefix(&self, prefix: &[IdType], suffix_range: ValueRange) -> ValueRange {
+        if suffix_range.is_invalid() {
+            return global::INVALID_RANGE;
+        }
+        let level = prefix.len();
+        if level >= self.num_levels() {
+            return global::INVALID_RANGE;
+        }
+        let Some((lo, hi)) = self.children_of(prefix) else {
+            return global::INVALID_RANGE;
+        };
+
+        let labels = &self.nodes[level];
+        let begin = partition_point(labels, lo, hi, |label| label < suffix_range.begin);
+        let end = partition_point(labels, begin, hi, |label| label <= suffix_range.end);
+        if begin == end {
+            return global::INVALID_RANGE;
+        }
+        ValueRange {
+            begin: self.left_extremes[level].access(begin),
+            end: self.right_extreme(level, end - 1),
+        }
+    }
+
+    /// Check if the trie contains the completion `c`
+    pub fn is_member(&self, c: &[IdType]) -> bool {
+        let Some((&last, prefix)) = c.split_last() else {
+            return false;
+        };
+        if c.len() > self.num_levels() {
+            return false;
+        }
+        let Some((lo, hi)) = self.children_of(prefix) else {
+            return false;
+        };
+        let level = prefix.len();
+        let pos = partition_point(&self.nodes[level], lo, hi, |label| label < last as u64);
+        // A node is a completion if it is not entirely covered by its children
+        pos < hi
+            && self.nodes[level].access(pos) == last as u64
+            && self.is_completion(level, pos)
+    }
+
+    /// Get the number of completions
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Check if the trie is empty
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Get the number of levels, that is the length of the longest completion
+    pub fn num_levels(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Get the number of nodes of each level
+    pub fn nodes_per_level(&self) -> Vec<usize> {
+        self.nodes.iter().map(CompactVector::size).collect()
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.nodes.iter().map(CompactVector::bytes).sum::<usize>()
+            + self.pointers.iter().map(EfSequence::bytes).sum::<usize>()
+            + self.left_extremes.iter().map(EfSequence::bytes).sum::<usize>()
+            + self.sizes.iter().map(EfSequence::bytes).sum::<usize>()
+    }
+
+    /// Get the range of positions, in level `prefix.len()`, of the children
+    /// of the node reached by following `prefix` from the root
+    fn children_of(&self, prefix: &[IdType]) -> Option<(usize, usize)> {
+        let (mut lo, mut hi) = (0, self.nodes.first().map_or(0, CompactVector::size));
+        for (level, &term_id) in prefix.iter().enumerate() {
+            let labels = &self.nodes[level];
+            let pos = partition_point(labels, lo, hi, |label| label < term_id as u64);
+            if pos == hi || labels.access(pos) != term_id as u64 {
+                return None;
+            }
+            lo = self.pointers[level].access(pos) as usize;
+            hi = self.pointers[level].access(pos + 1) as usize;
+        }
+        Some((lo, hi))
+    }
+
+    /// Get the last lexicographic position below the node at `pos` in `level`
+    fn right_extreme(&self, level: usize, pos: usize) -> u64 {
+        let sizes = &self.sizes[level];
+        let size = sizes.access(pos) - if pos > 0 { sizes.access(pos - 1) } else { 0 };
+        self.left_extremes[level].access(pos) + size
+    }
+
+    fn is_completion(&self, level: usize, pos: usize) -> bool {
+        if level + 1 == self.num_levels() {
+            return true;
+        }
+        let first_child = self.pointers[level].access(pos) as usize;
+        let last_child = self.pointers[level].access(pos + 1) as usize;
+        // The completion equal to the node's prefix comes before its children
+        first_child == last_child
+            || self.left_extremes[level + 1].access(first_child)
+                > self.left_extremes[level].access(pos)
+    }
+}
+
+/// Builder of a `SuccinctCompletionTrie` from completions pushed in
+/// strictly increasing order
+#[derive(Debug, Clone, Default)]
+pub struct SuccinctCompletionTrieBuilder {
+    max_levels: Option<usize>,
+    size: usize,
+    prev: CompletionType,
+    levels: Vec<LevelBuilder>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct LevelBuilder {
+    nodes: Vec<IdType>,
+    pointers: Vec<u64>,
+    left_extremes: Vec<u64>,
+    right_extremes: Vec<u64>,
+}
+
+impl LevelBuilder {
+    fn with_capacity(num_nodes: usize) -> Self {
+        Self {
+            nodes: Vec::with_capacity(num_nodes),
+            pointers: Vec::with_capacity(num_nodes + 1),
+            left_extremes: Vec::with_capacity(num_nodes),
+            right_extremes: Vec::with_capacity(num_nodes),
+        }
+    }
+}
+
+impl SuccinctCompletionTrieBuilder {
+    /// Create a builder adding levels as longer completions are pushed
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a builder pre-sized from the statistics of a collection, with
+    /// `num_levels` levels of `nodes_per_level` nodes each
+    pub fn with_parameters(params: &Parameters) -> Self {
+        let levels = (0..params.num_levels as usize)
+            .map(|i| {
+                let num_nodes = params.nodes_per_level.get(i).copied().unwrap_or(0);
+                LevelBuilder::with_capacity(num_nodes as usize)
+            })
+            .collect();
+        Self {
+            max_levels: Some(params.num_levels as usize),
+            prev: CompletionType::with_capacity(params.num_levels as usize),
+            levels,
+            ..Self::default()
+        }
+    }
+
+    /// Append a completion, which must follow the previous one in
+    /// lexicographic order
+    pub fn push(&mut self, c: &[IdType]) -> Result<(), String> {
+        if c.is_empty() {
+            return Err("completions must have at least one term".to_string());
+        }
+        if self.size > 0 && c <= self.prev.as_slice() {
+            return Err(format!(
+                "completions must be sorted and unique, but {:?} follows {:?}",
+                c, self.prev
+            ));
+        }
+        if let Some(max_levels) = self.max_levels {
+            if c.len() > max_levels {
+                return Err(format!(
+                    "completion {:?} has more than num_levels ({}) terms",
+                    c, max_levels
+                ));
+            }
+        }
+        if self.levels.len() < c.len() {
+            self.levels.resize_with(c.len(), LevelBuilder::default);
+        }
+
+        let pos = self.size as u64;
+        let lcp = self.prev.iter().zip(c).take_while(|(x, y)| x == y).count();
+        for level in &mut self.levels[..lcp] {
+            *level.right_extremes.last_mut().expect("shared prefixes have a node") = pos;
+        }
+        for (i, &term_id) in c.iter().enumerate().skip(lcp) {
+            let first_child = self.levels.get(i + 1).map_or(0, |next| next.nodes.len());
+            let level = &mut self.levels[i];
+            level.nodes.push(term_id);
+            level.pointers.push(first_child as u64);
+            level.left_extremes.push(pos);
+            level.right_extremes.push(pos);
+        }
+
+        self.size += 1;
+        self.prev.clear();
+        self.prev.extend_from_slice(c);
+        Ok(())
+    }
+
+    /// Get the number of completions pushed so far
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Check if no completion was pushed
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Build the trie, dropping the levels no completion reached
+    pub fn build(mut self) -> SuccinctCompletionTrie {
+        while self.levels.last().is_some_and(|level| level.nodes.is_empty()) {
+            self.levels.pop();
+        }
+
+        let num_levels = self.levels.len();
+        let mut trie = SuccinctCompletionTrie {
+            size: self.size,
+            nodes: Vec::with_capacity(num_levels),
+            pointers: Vec::with_capacity(num_levels.saturating_sub(1)),
+            left_extremes: Vec::with_capacity(num_levels),
+            sizes: Vec::with_capacity(num_levels),
+        };
+        for i in 0..num_levels {
+            let level = &self.levels[i];
+            trie.nodes.push(CompactVector::build(level.nodes.iter().map(|&label| label as u64)));
+            if i + 1 != num_levels {
+                let num_children = self.levels[i + 1].nodes.len() as u64;
+                let pointers = level.pointers.iter().copied().chain([num_children]);
+                trie.pointers.push(EfSequence::build(pointers).expect("pointers are sorted"));
+            }
+            trie.left_extremes.push(
+                EfSequence::build(level.left_extremes.iter().copied())
+                    .expect("nodes of a level are sorted"),
+            );
+            let sizes = level
+                .left_extremes
+                .iter()
+                .zip(&level.right_extremes)
+                .scan(0, |sum, (begin, end)| {
+                    *sum += end - begin;
+                    Some(*sum)
+                });
+            trie.sizes.push(EfSequence::build(sizes).expect("prefix sums are sorted"));
+        }
+        trie
+    }
+}
+
+/// Get the first position in `lo..hi` whose label does not satisfy `pred`,
+/// given that it holds for a prefix of the labels in the range
+fn partition_point<P>(labels: &CompactVector, mut lo: usize, mut hi: usize, pred: P) -> usize
+where
+    P: Fn(u64) -> bool,
+{
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if pred(labels.access(mid)) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_fc_dictionary::IntegerFcDictionary;
+
+    fn completions() -> Vec<CompletionType> {
+        vec![
+            vec![1],
+            vec![1, 4],
+            vec![1, 4, 2],
+            vec![1, 5],
+            vec![1, 5, 3, 300],
+            vec![1, 7],
+            vec![2, 4],
+            vec![3],
+            vec![3, 1, 1],
+            vec![200, 1],
+        ]
+    }
+
+    fn range(begin: u64, end: u64) -> ValueRange {
+        ValueRange { begin, end }
+    }
+
+    #[test]
+    fn test_locate_prefix() {
+        let trie = SuccinctCompletionTrie::build(completions()).unwrap();
+        assert_eq!(trie.size(), 10);
+        assert_eq!(trie.nodes_per_level(), vec![4, 6, 3, 1]);
+        assert_eq!(trie.locate_prefix(&[1], range(4, 5)), range(1, 4));
+        assert_eq!(trie.locate_prefix(&[1], range(5, 5)), range(3, 4));
+        assert_eq!(trie.locate_prefix(&[1, 5], range(3, 3)), range(4, 4));
+        assert_eq!(trie.locate_prefix(&[], range(1, 3)), range(0, 8));
+        assert!(trie.locate_prefix(&[1], range(6, 6)).is_invalid());
+        assert!(trie.locate_prefix(&[4], range(1, 9)).is_invalid());
+        assert!(trie.locate_prefix(&[1, 5, 3, 300], range(1, 9)).is_invalid());
+        assert!(trie.locate_prefix(&[1], global::INVALID_RANGE).is_invalid());
+    }
+
+    #[test]
+    fn test_matches_front_coded_dictionary() {
+        let completions = completions();
+        let trie = SuccinctCompletionTrie::build(&completions).unwrap();
+        let dictionary = IntegerFcDictionary::build(&completions, 2).unwrap();
+        let prefixes: Vec<&[IdType]> = vec![&[], &[1], &[1, 4], &[1, 5], &[1, 5, 3], &[3], &[3, 1]];
+        for prefix in prefixes {
+            for begin in [1, 2, 4, 200] {
+                for end in [begin, begin + 3, 300] {
+                    let suffix_range = range(begin, end);
+                    assert_eq!(
+                        trie.locate_prefix(prefix, suffix_range),
+                        dictionary.locate_prefix(prefix, suffix_range),
+                        "{:?} {:?}",
+                        prefix,
+                        suffix_range
+                    );
+                }
+            }
+        }
+        for c in &completions {
+            assert!(trie.is_member(c));
+        }
+        assert!(!trie.is_member(&[1, 5, 3]));
+        assert!(!trie.is_member(&[2]));
+        assert!(!trie.is_member(&[]));
+    }
+
+    #[test]
+    fn test_build_with_parameters() {
+        let params = Parameters {
+            num_completions: 10,
+            num_levels: 4,
+            nodes_per_level: vec![4, 6, 3, 1],
+            ..Parameters::default()
+        };
+        let mut builder = SuccinctCompletionTrieBuilder::with_parameters(&params);
+        for c in completions() {
+            builder.push(&c).unwrap();
+        }
+        assert!(builder.push(&[200, 1, 1, 1, 1]).is_err());
+        assert!(builder.push(&[200, 1]).is_err());
+        assert!(builder.push(&[]).is_err());
+        let trie = builder.build();
+        let nodes_per_level: Vec<u32> = trie.nodes_per_level().iter().map(|&n| n as u32).collect();
+        assert_eq!(nodes_per_level, params.nodes_per_level);
+        assert_eq!(trie.locate_prefix(&[3], range(1, 1)), range(8, 8));
+    }
+
+    #[test]
+    fn test_empty() {
+        let trie = SuccinctCompletionTrie::build(Vec::<CompletionType>::new()).unwrap();
+        assert!(trie.is_empty());
+        assert!(trie.locate_prefix(&[], range(1, 9)).is_invalid());
+        assert!(!trie.is_member(&[1]));
+    }
+}
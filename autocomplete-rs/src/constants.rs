@@ -2,7 +2,18 @@
 pub const MAX_K: u32 = 15;
 pub const MAX_NUM_TERMS_PER_QUERY: u32 = 64;
 pub const MAX_NUM_CHARS_PER_QUERY: u32 = 128;
+pub const DEFAULT_K: u32 = 10;
+pub const MAX_RESULTS_CEILING: u32 = 1024;
+pub const DEFAULT_MAX_EDITS: u32 = 1;
+pub const MAX_EDITS: u32 = 3;
+/// Factor applied to the score of a fuzzy completion for each edit
+pub const FUZZY_EDIT_DISCOUNT: f32 = 0.5;
+/// Number of changes to an `Autocomplete` after which the server rebuilds
+/// its indexes in the background
+pub const COMPACTION_THRESHOLD: u64 = 1024;
 pub const POOL_SIZE: usize = (MAX_K as usize) * (MAX_NUM_CHARS_PER_QUERY as usize);
 
 // Compile-time assertion
-const _: () = assert!(MAX_NUM_TERMS_PER_QUERY < 256, "MAX_NUM_TERMS_PER_QUERY must be < 256"); 
\ No newline at end of file
+const _: () = assert!(MAX_NUM_TERMS_PER_QUERY < 256, "MAX_NUM_TERMS_PER_QUERY must be < 256");
+const _: () = assert!(MAX_K <= MAX_RESULTS_CEILING, "MAX_K must be <= MAX_RESULTS_CEILING"); 
+const _: () = assert!(DEFAULT_MAX_EDITS <= MAX_EDITS, "DEFAULT_MAX_EDITS must be <= MAX_EDITS");
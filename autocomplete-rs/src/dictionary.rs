@@ -1,46 +1,351 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use crate::fc_dictionary::{FcDictionary, DEFAULT_BUCKET_SIZE};
+use crate::index::CompactVector;
 use crate::types::IdType;
 
+/// Opaque data attached to a completion and returned with it, such as the id,
+/// URL or category of the entity behind a suggestion
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Payload {
+    pub data: Vec<u8>,
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl Payload {
+    /// Check if the payload has neither data nor attributes
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty() && self.attributes.is_empty()
+    }
+}
+
+/// Front-coded strings of a `Dictionary`, shared with its snapshots
+#[derive(Clone, Default)]
+struct CompactedStrings {
+    strings: FcDictionary,
+    /// Id of the string at each position of `strings`, minus one
+    ids: CompactVector,
+    /// Position in `strings` of the string with each id, 0 if it is not there
+    positions: CompactVector,
+}
+
+impl CompactedStrings {
+    /// Front-code `strings`, with ids below `num_ids`
+    fn build(mut strings: Vec<(IdType, Cow<'_, str>)>, num_ids: usize) -> Self {
+        strings.sort_unstable_by(|a, b| a.1.cmp(&b.1));
+        let mut positions = vec![0; num_ids];
+        for (pos, &(id, _)) in strings.iter().enumerate() {
+            positions[id as usize] = pos as u64 + 1;
+        }
+        Self {
+            strings: FcDictionary::build(strings.iter().map(|(_, s)| s), DEFAULT_BUCKET_SIZE)
+                .expect("dictionary strings are unique"),
+            ids: CompactVector::build(strings.iter().map(|&(id, _)| id as u64)),
+            positions: CompactVector::build(positions),
+        }
+    }
+
+    /// Get the position of the string with the given id, 0 if it is not there
+    fn position(&self, id: IdType) -> u64 {
+        self.positions.get(id as usize).unwrap_or(0)
+    }
+
+    /// Iterate over the ids and strings whose ids are not in `removed`,
+    /// followed by the `inserted` ones
+    fn iter_with<'a, I>(
+        &'a self,
+        removed: &'a HashSet<IdType>,
+        inserted: I,
+    ) -> impl Iterator<Item = (IdType, Cow<'a, str>)> + 'a
+    where
+        I: Iterator<Item = (IdType, &'a str)> + 'a,
+    {
+        self.strings
+            .iter()
+            .enumerate()
+            .map(|(pos, string)| (self.ids.access(pos) as IdType, Cow::Owned(string)))
+            .filter(|(id, _)| !removed.contains(id))
+            .chain(inserted.map(|(id, string)| (id, Cow::Borrowed(string))))
+    }
+}
+
+/// Bidirectional map between strings and ids. Ids are assigned in insertion
+/// order and never reused, so the id of a removed string stays unassigned.
+///
+/// Strings are stored once, front-coded in lexicographic order, with the id
+/// of the string at each position and the position of each id bit-packed
+/// next to them. Strings inserted since the last compaction are kept in a
+/// hash map and removed ones are only marked, until a compaction rebuilds
+/// the front-coded strings. The compaction can be built from a `snapshot`
+/// while the dictionary keeps changing.
 #[derive(Clone)]
 pub struct Dictionary {
-    strings: Vec<String>,
-    id_map: std::collections::HashMap<String, IdType>,
-    next_id: IdType,
+    compacted: Arc<CompactedStrings>,
+    /// Strings inserted since the last compaction, by id
+    inserted: HashMap<IdType, String>,
+    /// Ids of the strings of `inserted`
+    inserted_ids: HashMap<String, IdType>,
+    /// Ids of strings removed since the last compaction
+    removed: HashSet<IdType>,
+    payloads: Vec<Payload>,
+    len: usize,
 }
 
+/// Strings of a `Dictionary` when the snapshot was taken, from which a
+/// compaction is built without borrowing the dictionary
+pub struct DictionarySnapshot {
+    compacted: Arc<CompactedStrings>,
+    inserted: Vec<(IdType, String)>,
+    removed: HashSet<IdType>,
+    num_ids: usize,
+}
+
+impl DictionarySnapshot {
+    /// Iterate over the ids and strings, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (IdType, Cow<'_, str>)> + '_ {
+        let inserted = self.inserted.iter().map(|(id, string)| (*id, string.as_str()));
+        self.compacted.iter_with(&self.removed, inserted)
+    }
+
+    /// Front-code the strings
+    pub fn compact(&self) -> CompactedDictionary {
+        let compacted = CompactedStrings::build(self.iter().collect(), self.num_ids);
+        CompactedDictionary(Arc::new(compacted))
+    }
+}
+
+/// Front-coded strings of a `DictionarySnapshot`, to be swapped into the
+/// dictionary with `finish_compaction`
+pub struct CompactedDictionary(Arc<CompactedStrings>);
+
 impl Dictionary {
     pub fn new() -> Self {
         Self {
-            strings: Vec::new(),
-            id_map: std::collections::HashMap::new(),
-            next_id: 0,
+            compacted: Arc::new(CompactedStrings::default()),
+            inserted: HashMap::new(),
+            inserted_ids: HashMap::new(),
+            removed: HashSet::new(),
+            payloads: Vec::new(),
+            len: 0,
         }
     }
 
     pub fn insert(&mut self, string: String) -> IdType {
-        if let Some(&id) = self.id_map.get(&string) {
+        if let Some(id) = self.get_id(&string) {
             return id;
         }
 
-        let id = self.next_id;
-        self.next_id += 1;
-        self.strings.push(string.clone());
-        self.id_map.insert(string, id);
+        let id = self.payloads.len() as IdType;
+        self.payloads.push(Payload::default());
+        self.inserted.insert(id, string.clone());
+        self.inserted_ids.insert(string, id);
+        self.len += 1;
         id
     }
 
-    pub fn get(&self, id: IdType) -> Option<&str> {
-        self.strings.get(id as usize).map(|s| s.as_str())
+    /// Remove `string`, returning its id if it was present
+    pub fn remove(&mut self, string: &str) -> Option<IdType> {
+        let id = match self.inserted_ids.remove(string) {
+            Some(id) => {
+                self.inserted.remove(&id);
+                id
+            }
+            None => self.compacted_id(string)?,
+        };
+        // Also marked if inserted, in case a compaction in progress has it
+        self.removed.insert(id);
+        self.payloads[id as usize] = Payload::default();
+        self.len -= 1;
+        Some(id)
+    }
+
+    /// Get the string with the given id, or `None` if it was removed
+    pub fn get(&self, id: IdType) -> Option<Cow<'_, str>> {
+        if let Some(string) = self.inserted.get(&id) {
+            return Some(Cow::Borrowed(string));
+        }
+        if self.removed.contains(&id) {
+            return None;
+        }
+        match self.compacted.position(id) {
+            0 => None,
+            pos => self.compacted.strings.extract(pos as IdType).map(Cow::Owned),
+        }
+    }
+
+    /// Get the payload of the string with the given id, empty unless set
+    pub fn payload(&self, id: IdType) -> Option<&Payload> {
+        self.contains_id(id).then(|| &self.payloads[id as usize])
+    }
+
+    /// Replace the payload of the string with the given id, returning false
+    /// if there is no such string
+    pub fn set_payload(&mut self, id: IdType, payload: Payload) -> bool {
+        if !self.contains_id(id) {
+            return false;
+        }
+        self.payloads[id as usize] = payload;
+        true
     }
 
     pub fn get_id(&self, string: &str) -> Option<IdType> {
-        self.id_map.get(string).copied()
+        self.inserted_ids
+            .get(string)
+            .copied()
+            .or_else(|| self.compacted_id(string))
+    }
+
+    /// Iterate over the ids and strings, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (IdType, Cow<'_, str>)> + '_ {
+        let inserted = self.inserted.iter().map(|(&id, string)| (id, string.as_str()));
+        self.compacted.iter_with(&self.removed, inserted)
+    }
+
+    /// Front-code the strings inserted since the last compaction together
+    /// with the others, and forget the removed ones
+    pub fn compact(&mut self) {
+        let compacted = self.snapshot().compact();
+        self.finish_compaction(compacted);
+    }
+
+    /// Take a snapshot of the strings, copying only those changed since the
+    /// last compaction
+    pub fn snapshot(&self) -> DictionarySnapshot {
+        DictionarySnapshot {
+            compacted: Arc::clone(&self.compacted),
+            inserted: self.inserted.iter().map(|(&id, s)| (id, s.clone())).collect(),
+            removed: self.removed.clone(),
+            num_ids: self.num_ids(),
+        }
+    }
+
+    /// Swap in the front-coded strings of a snapshot taken after the last
+    /// compaction. Strings inserted or removed since the snapshot stay
+    /// pending until the next compaction.
+    pub fn finish_compaction(&mut self, compacted: CompactedDictionary) {
+        let compacted = compacted.0;
+        self.inserted.retain(|&id, _| compacted.position(id) == 0);
+        self.inserted_ids.retain(|_, id| compacted.position(*id) == 0);
+        self.removed.retain(|&id| compacted.position(id) > 0);
+        self.compacted = compacted;
     }
 
+    /// Get the number of strings, removed ones excluded
     pub fn len(&self) -> usize {
-        self.strings.len()
+        self.len
     }
 
     pub fn is_empty(&self) -> bool {
-        self.strings.is_empty()
+        self.len == 0
+    }
+
+    /// Get the number of ids assigned so far, removed ones included
+    pub fn num_ids(&self) -> usize {
+        self.payloads.len()
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        let inserted: usize = self.inserted.values().map(|string| 2 * string.len()).sum();
+        let payloads: usize = self
+            .payloads
+            .iter()
+            .map(|payload| {
+                payload.data.len()
+                    + payload.attributes.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>()
+            })
+            .sum();
+        self.compacted.strings.bytes()
+            + self.compacted.ids.bytes()
+            + self.compacted.positions.bytes()
+            + inserted
+            + self.removed.len() * std::mem::size_of::<IdType>()
+            + self.payloads.len() * std::mem::size_of::<Payload>()
+            + payloads
+    }
+
+    /// Get the id of `string` among the front-coded strings
+    fn compacted_id(&self, string: &str) -> Option<IdType> {
+        let pos = self.compacted.strings.locate(string)?;
+        let id = self.compacted.ids.access(pos as usize - 1) as IdType;
+        (!self.removed.contains(&id)).then_some(id)
+    }
+
+    fn contains_id(&self, id: IdType) -> bool {
+        self.inserted.contains_key(&id)
+            || (!self.removed.contains(&id) && self.compacted.position(id) > 0)
     }
-} 
\ No newline at end of file
+}
+
+impl Default for Dictionary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_remove_and_compact() {
+        let mut dictionary = Dictionary::new();
+        for string in ["pear", "apple", "fig"] {
+            dictionary.insert(string.to_string());
+        }
+        assert_eq!(dictionary.insert("apple".to_string()), 1);
+        dictionary.compact();
+        assert_eq!(dictionary.get(0).as_deref(), Some("pear"));
+        assert_eq!(dictionary.get_id("fig"), Some(2));
+        assert!(dictionary.set_payload(2, Payload { data: vec![7], ..Payload::default() }));
+
+        // Changes after a compaction are visible before the next one
+        assert_eq!(dictionary.remove("apple"), Some(1));
+        assert_eq!(dictionary.remove("apple"), None);
+        assert_eq!(dictionary.insert("kiwi".to_string()), 3);
+        assert_eq!(dictionary.insert("apple".to_string()), 4);
+        assert_eq!(dictionary.get(1), None);
+        assert_eq!(dictionary.get(4).as_deref(), Some("apple"));
+        assert_eq!(dictionary.len(), 4);
+
+        for _ in 0..2 {
+            let mut strings: Vec<(IdType, String)> =
+                dictionary.iter().map(|(id, s)| (id, s.into_owned())).collect();
+            strings.sort_unstable();
+            let expected = [(0, "pear"), (2, "fig"), (3, "kiwi"), (4, "apple")];
+            assert_eq!(strings, expected.map(|(id, s)| (id, s.to_string())));
+            assert_eq!(dictionary.get_id("kiwi"), Some(3));
+            assert_eq!(dictionary.payload(2).unwrap().data, vec![7]);
+            assert_eq!(dictionary.payload(1), None);
+            dictionary.compact();
+        }
+        assert_eq!(dictionary.num_ids(), 5);
+        assert_eq!(dictionary.get(5), None);
+    }
+
+    #[test]
+    fn test_changes_during_compaction() {
+        let mut dictionary = Dictionary::new();
+        for string in ["pear", "apple", "fig"] {
+            dictionary.insert(string.to_string());
+        }
+        let snapshot = dictionary.snapshot();
+
+        // Changes made while the compaction is built survive its swap
+        assert_eq!(dictionary.remove("apple"), Some(1));
+        assert_eq!(dictionary.insert("kiwi".to_string()), 3);
+        dictionary.finish_compaction(snapshot.compact());
+        for _ in 0..2 {
+            assert_eq!(dictionary.get(1), None);
+            assert_eq!(dictionary.get_id("apple"), None);
+            assert_eq!(dictionary.get_id("kiwi"), Some(3));
+            assert_eq!(dictionary.get(2).as_deref(), Some("fig"));
+            let mut ids: Vec<IdType> = dictionary.iter().map(|(id, _)| id).collect();
+            ids.sort_unstable();
+            assert_eq!(ids, vec![0, 2, 3]);
+            assert_eq!(dictionary.len(), 3);
+            dictionary.compact();
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use super::select_in_word;
+
+const BLOCK_SIZE: usize = 1024;
+const SUBBLOCK_SIZE: usize = 32;
+const MAX_IN_BLOCK_DISTANCE: u64 = 1 << 16;
+
+/// Constant-time select index over the ones (or zeros) of a bit vector.
+///
+/// Positions are grouped into blocks of `BLOCK_SIZE`. A dense block stores
+/// the position of its first bit and, every `SUBBLOCK_SIZE` positions, a
+/// 16-bit offset from it; a select then scans at most a few words from the
+/// closest sample. A sparse block, spanning more than `MAX_IN_BLOCK_DISTANCE`
+/// bits, stores all of its positions explicitly.
+#[derive(Debug, Clone, Default)]
+pub struct DArray {
+    ones: bool,
+    num_positions: usize,
+    block_inventory: Vec<i64>,
+    subblock_inventory: Vec<u16>,
+    overflow_positions: Vec<u64>,
+}
+
+impl DArray {
+    /// Build a select index over the first `num_bits` bits of `words`,
+    /// selecting set bits if `ones` is true and clear bits otherwise
+    pub fn new(words: &[u64], num_bits: usize, ones: bool) -> Self {
+        let mut darray = Self {
+            ones,
+            ..Self::default()
+        };
+        let mut block_positions = Vec::with_capacity(BLOCK_SIZE);
+        for (word_idx, _) in words.iter().enumerate() {
+            let mut word = darray.word(words, word_idx);
+            while word != 0 {
+                let pos = (word_idx << 6) + word.trailing_zeros() as usize;
+                if pos >= num_bits {
+                    break;
+                }
+                block_positions.push(pos as u64);
+                if block_positions.len() == BLOCK_SIZE {
+                    darray.flush_block(&mut block_positions);
+                }
+                darray.num_positions += 1;
+                word &= word - 1;
+            }
+        }
+        if !block_positions.is_empty() {
+            darray.flush_block(&mut block_positions);
+        }
+        darray
+    }
+
+    /// Get the position of the `i`-th (0-based) selected bit of `words`
+    pub fn select(&self, words: &[u64], i: usize) -> usize {
+        assert!(i < self.num_positions, "select index out of bounds");
+        let block = i / BLOCK_SIZE;
+        let block_pos = self.block_inventory[block];
+        if block_pos < 0 {
+            // Sparse block
+            let overflow_pos = (-block_pos - 1) as usize;
+            return self.overflow_positions[overflow_pos + i % BLOCK_SIZE] as usize;
+        }
+
+        let subblock = i / SUBBLOCK_SIZE;
+        let start_pos = block_pos as usize + self.subblock_inventory[subblock] as usize;
+        let mut remainder = i % SUBBLOCK_SIZE;
+        if remainder == 0 {
+            return start_pos;
+        }
+
+        let mut word_idx = start_pos >> 6;
+        let mut word = self.word(words, word_idx) & (u64::MAX << (start_pos & 63));
+        loop {
+            let popcount = word.count_ones() as usize;
+            if remainder < popcount {
+                break;
+            }
+            remainder -= popcount;
+            word_idx += 1;
+            word = self.word(words, word_idx);
+        }
+        (word_idx << 6) + select_in_word(word, remainder as u32) as usize
+    }
+
+    /// Get the number of selectable positions
+    pub fn num_positions(&self) -> usize {
+        self.num_positions
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        std::mem::size_of_val(&self.ones)
+            + std::mem::size_of_val(&self.num_positions)
+            + self.block_inventory.len() * std::mem::size_of::<i64>()
+            + self.subblock_inventory.len() * std::mem::size_of::<u16>()
+            + self.overflow_positions.len() * std::mem::size_of::<u64>()
+    }
+
+    fn word(&self, words: &[u64], idx: usize) -> u64 {
+        if self.ones {
+            words[idx]
+        } else {
+            !words[idx]
+        }
+    }
+
+    fn flush_block(&mut self, positions: &mut Vec<u64>) {
+        let first = positions[0];
+        if positions[positions.len() - 1] - first < MAX_IN_BLOCK_DISTANCE {
+            self.block_inventory.push(first as i64);
+            for pos in positions.iter().step_by(SUBBLOCK_SIZE) {
+                self.subblock_inventory.push((pos - first) as u16);
+            }
+        } else {
+            self.block_inventory.push(-(self.overflow_positions.len() as i64) - 1);
+            self.overflow_positions.extend_from_slice(positions);
+            for _ in positions.iter().step_by(SUBBLOCK_SIZE) {
+                self.subblock_inventory.push(u16::MAX);
+            }
+        }
+        positions.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words_from_positions(positions: &[usize], num_bits: usize) -> Vec<u64> {
+        let mut words = vec![0u64; num_bits.div_ceil(64)];
+        for &pos in positions {
+            words[pos >> 6] |= 1 << (pos & 63);
+        }
+        words
+    }
+
+    #[test]
+    fn test_select_ones_and_zeros() {
+        let num_bits = 5000;
+        let positions: Vec<usize> = (0..num_bits)
+            .filter(|i| i % 3 == 0 || i % 7 == 0)
+            .collect();
+        let words = words_from_positions(&positions, num_bits);
+
+        let ones = DArray::new(&words, num_bits, true);
+        assert_eq!(ones.num_positions(), positions.len());
+        for (i, &pos) in positions.iter().enumerate() {
+            assert_eq!(ones.select(&words, i), pos);
+        }
+
+        let zeros_positions: Vec<usize> = (0..num_bits)
+            .filter(|i| i % 3 != 0 && i % 7 != 0)
+            .collect();
+        let zeros = DArray::new(&words, num_bits, false);
+        assert_eq!(zeros.num_positions(), zeros_positions.len());
+        for (i, &pos) in zeros_positions.iter().enumerate() {
+            assert_eq!(zeros.select(&words, i), pos);
+        }
+    }
+
+    #[test]
+    fn test_select_sparse_blocks() {
+        let num_bits = 2048 * 200;
+        let positions: Vec<usize> = (0..2048).map(|i| i * 200 + (i % 5)).collect();
+        let words = words_from_positions(&positions, num_bits);
+        let ones = DArray::new(&words, num_bits, true);
+        for (i, &pos) in positions.iter().enumerate() {
+            assert_eq!(ones.select(&words, i), pos);
+        }
+    }
+}
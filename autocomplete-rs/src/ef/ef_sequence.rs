@@ -0,0 +1,208 @@
+use crate::index::{BitVector, BitVectorBuilder};
+
+/// Monotone sequence of integers compressed with Elias-Fano encoding.
+///
+/// Each value is split into `l` low bits, stored verbatim in a packed array,
+/// and the remaining high bits, stored in unary as the gaps between
+/// consecutive high parts. A value takes about `2 + log2(universe / size)`
+/// bits, and select over the high bits gives constant-time access.
+#[derive(Debug, Clone)]
+pub struct EfSequence {
+    size: usize,
+    l: u32,
+    low_bits: BitVector,
+    high_bits: BitVector,
+}
+
+impl EfSequence {
+    /// Create a new empty sequence
+    pub fn new() -> Self {
+        Self {
+            size: 0,
+            l: 0,
+            low_bits: BitVectorBuilder::new().build_unindexed(),
+            high_bits: BitVector::new(0),
+        }
+    }
+
+    /// Build the sequence from non-decreasing values
+    pub fn build<I>(values: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        let values: Vec<u64> = values.into_iter().collect();
+        if let Some(i) = values.windows(2).position(|w| w[0] > w[1]) {
+            return Err(format!(
+                "values must be non-decreasing, but {} follows {}",
+                values[i + 1],
+                values[i]
+            ));
+        }
+        let Some(&last) = values.last() else {
+            return Ok(Self::new());
+        };
+
+        let size = values.len();
+        let ratio = (last + 1) / size as u64;
+        let l = if ratio > 1 { ratio.ilog2() } else { 0 };
+
+        let mut low_bits = BitVectorBuilder::new();
+        let mut high_bits = BitVectorBuilder::with_size(size + (last >> l) as usize + 1);
+        for (i, &value) in values.iter().enumerate() {
+            low_bits.append_bits(value, l as usize);
+            high_bits.set((value >> l) as usize + i, true);
+        }
+        Ok(Self {
+            size,
+            l,
+            low_bits: low_bits.build_unindexed(),
+            high_bits: high_bits.build(),
+        })
+    }
+
+    /// Get the value at position `i`
+    pub fn access(&self, i: usize) -> u64 {
+        assert!(i < self.size, "index {} out of bounds for size {}", i, self.size);
+        let high = self.high_bits.select1(i) - i;
+        ((high as u64) << self.l) | self.low(i)
+    }
+
+    /// Get the position and value of the first element that is `>= x`, or
+    /// `None` if every element is smaller
+    pub fn next_geq(&self, x: u64) -> Option<(usize, u64)> {
+        if self.is_empty() || x > self.universe() {
+            return None;
+        }
+        let high = (x >> self.l) as usize;
+        // Skip the elements whose high part is smaller than the one of `x`
+        let (pos, high_pos) = if high == 0 {
+            (0, 0)
+        } else {
+            let high_pos = self.high_bits.select0(high - 1) + 1;
+            (high_pos - high, high_pos)
+        };
+        self.iter_from(pos, high_pos)
+            .zip(pos..)
+            .find(|&(value, _)| value >= x)
+            .map(|(value, i)| (i, value))
+    }
+
+    /// Iterate over the values in order
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.iter_from(0, 0)
+    }
+
+    /// Get the number of values
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Check if the sequence is empty
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Get the largest value, or 0 if the sequence is empty
+    pub fn universe(&self) -> u64 {
+        if self.is_empty() {
+            0
+        } else {
+            self.access(self.size - 1)
+        }
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        std::mem::size_of_val(&self.size)
+            + std::mem::size_of_val(&self.l)
+            + self.low_bits.bytes()
+            + self.high_bits.bytes()
+    }
+
+    /// Iterate from the element at `pos`, whose high bit is at or after
+    /// `high_pos`
+    fn iter_from(&self, pos: usize, high_pos: usize) -> impl Iterator<Item = u64> + '_ {
+        self.high_bits
+            .ones_from(high_pos)
+            .zip(pos..self.size)
+            .map(move |(bit, i)| (((bit - i) as u64) << self.l) | self.low(i))
+    }
+
+    fn low(&self, i: usize) -> u64 {
+        let l = self.l as usize;
+        self.low_bits.get_bits(i * l, l)
+    }
+}
+
+impl Default for EfSequence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> Vec<u64> {
+        let mut values = Vec::new();
+        let mut value = 3;
+        for i in 0..3000u64 {
+            value += (i * 7919) % 97;
+            values.push(value);
+        }
+        values
+    }
+
+    #[test]
+    fn test_access_and_iter() {
+        let values = values();
+        let sequence = EfSequence::build(values.iter().copied()).unwrap();
+        assert_eq!(sequence.len(), values.len());
+        assert_eq!(sequence.universe(), *values.last().unwrap());
+        for (i, &value) in values.iter().enumerate() {
+            assert_eq!(sequence.access(i), value);
+        }
+        assert_eq!(sequence.iter().collect::<Vec<_>>(), values);
+    }
+
+    #[test]
+    fn test_next_geq() {
+        let values = values();
+        let sequence = EfSequence::build(values.iter().copied()).unwrap();
+        for x in 0..=sequence.universe() + 1 {
+            let expected = values
+                .iter()
+                .position(|&v| v >= x)
+                .map(|i| (i, values[i]));
+            assert_eq!(sequence.next_geq(x), expected);
+        }
+    }
+
+    #[test]
+    fn test_duplicates_and_small_universe() {
+        let values = vec![0, 0, 1, 1, 1, 2, 5, 5];
+        let sequence = EfSequence::build(values.iter().copied()).unwrap();
+        assert_eq!(sequence.iter().collect::<Vec<_>>(), values);
+        assert_eq!(sequence.next_geq(1), Some((2, 1)));
+        assert_eq!(sequence.next_geq(3), Some((6, 5)));
+        assert_eq!(sequence.next_geq(6), None);
+    }
+
+    #[test]
+    fn test_build_errors_and_empty() {
+        assert!(EfSequence::build([3, 2]).is_err());
+
+        let sequence = EfSequence::build([]).unwrap();
+        assert!(sequence.is_empty());
+        assert_eq!(sequence.iter().count(), 0);
+        assert_eq!(sequence.next_geq(0), None);
+    }
+
+    #[test]
+    fn test_compression() {
+        let values: Vec<u64> = (0..10_000).map(|i| i * 10).collect();
+        let sequence = EfSequence::build(values.iter().copied()).unwrap();
+        assert!(sequence.bytes() < values.len() * std::mem::size_of::<u32>() / 4);
+    }
+}
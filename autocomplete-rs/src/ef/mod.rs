@@ -0,0 +1,16 @@
+//! Elias-Fano encoding of monotone integer sequences
+
+pub mod darray;
+pub mod ef_sequence;
+
+pub use darray::*;
+pub use ef_sequence::*;
+
+/// Position of the `k`-th (0-based) set bit of `word`
+pub(crate) fn select_in_word(mut word: u64, k: u32) -> u32 {
+    debug_assert!(k < word.count_ones());
+    for _ in 0..k {
+        word &= word - 1;
+    }
+    word.trailing_zeros()
+}
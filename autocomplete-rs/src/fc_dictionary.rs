@@ -0,0 +1,323 @@
+use crate::index::CompactVector;
+use crate::types::{global, IdType, ValueRange};
+
+/// Default number of front-coded strings following each bucket header
+pub const DEFAULT_BUCKET_SIZE: usize = 16;
+
+/// Dictionary of sorted strings compressed with bucketed front coding.
+///
+/// Strings are grouped into buckets of `bucket_size + 1` consecutive
+/// strings. The first string of a bucket (its header) is stored verbatim,
+/// and every following string is stored as the length of the prefix it
+/// shares with its predecessor plus the remaining suffix. Ids are
+/// lexicographic ranks starting from 1, as id 0 is reserved to mark the end
+/// of a completion.
+#[derive(Debug, Clone)]
+pub struct FcDictionary {
+    bucket_size: usize,
+    size: usize,
+    header_offsets: CompactVector,
+    headers: Vec<u8>,
+    bucket_offsets: CompactVector,
+    buckets: Vec<u8>,
+}
+
+impl FcDictionary {
+    /// Create a new empty dictionary
+    pub fn new() -> Self {
+        Self {
+            bucket_size: DEFAULT_BUCKET_SIZE,
+            size: 0,
+            header_offsets: CompactVector::build([0]),
+            headers: Vec::new(),
+            bucket_offsets: CompactVector::build([0]),
+            buckets: Vec::new(),
+        }
+    }
+
+    /// Build the dictionary from strictly increasing strings
+    pub fn build<I, S>(strings: I, bucket_size: usize) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        if bucket_size == 0 {
+            return Err("bucket size must be > 0".to_string());
+        }
+
+        let mut dictionary = Self {
+            bucket_size,
+            ..Self::new()
+        };
+        let mut header_offsets = vec![0];
+        let mut bucket_offsets = vec![0];
+        let mut prev = String::new();
+        for s in strings {
+            let s = s.as_ref();
+            if dictionary.size > 0 && s <= prev.as_str() {
+                return Err(format!(
+                    "strings must be sorted and unique, but {:?} follows {:?}",
+                    s, prev
+                ));
+            }
+
+            if dictionary.size.is_multiple_of(bucket_size + 1) {
+                if dictionary.size > 0 {
+                    bucket_offsets.push(dictionary.buckets.len() as u64);
+                }
+                dictionary.headers.extend_from_slice(s.as_bytes());
+                header_offsets.push(dictionary.headers.len() as u64);
+            } else {
+                let lcp = lcp(prev.as_bytes(), s.as_bytes());
+                write_varint(&mut dictionary.buckets, lcp);
+                write_varint(&mut dictionary.buckets, s.len() - lcp);
+                dictionary.buckets.extend_from_slice(&s.as_bytes()[lcp..]);
+            }
+
+            dictionary.size += 1;
+            prev.clear();
+            prev.push_str(s);
+        }
+        if dictionary.size > 0 {
+            bucket_offsets.push(dictionary.buckets.len() as u64);
+        }
+        dictionary.header_offsets = CompactVector::build(header_offsets);
+        dictionary.bucket_offsets = CompactVector::build(bucket_offsets);
+        Ok(dictionary)
+    }
+
+    /// Get the id of a string
+    pub fn locate(&self, s: &str) -> Option<IdType> {
+        let pos = self.partition_point(|t| t < s.as_bytes());
+        match self.extract(pos as IdType + 1) {
+            Some(t) if t == s => Some(pos as IdType + 1),
+            _ => None,
+        }
+    }
+
+    /// Get the string with the given id
+    pub fn extract(&self, id: IdType) -> Option<String> {
+        let pos = (id as usize).checked_sub(1)?;
+        if pos >= self.size {
+            return None;
+        }
+        let bucket = pos / (self.bucket_size + 1);
+        let decoded = self
+            .bucket_strings(bucket)
+            .nth(pos % (self.bucket_size + 1))
+            .unwrap();
+        Some(String::from_utf8(decoded).expect("dictionary strings are valid UTF-8"))
+    }
+
+    /// Get the inclusive range of ids of the strings starting with `prefix`,
+    /// or `global::INVALID_RANGE` if there is none
+    pub fn locate_prefix(&self, prefix: &str) -> ValueRange {
+        let p = prefix.as_bytes();
+        let begin = self.partition_point(|t| t < p);
+        let end = self.partition_point(|t| t < p || t.starts_with(p));
+        if begin == end {
+            return global::INVALID_RANGE;
+        }
+        ValueRange {
+            begin: begin as u64 + 1,
+            end: end as u64,
+        }
+    }
+
+    /// Iterate over the strings in lexicographic order
+    pub fn iter(&self) -> impl Iterator<Item = String> + '_ {
+        (0..self.num_buckets()).flat_map(move |bucket| {
+            self.bucket_strings(bucket)
+                .map(|s| String::from_utf8(s).expect("dictionary strings are valid UTF-8"))
+        })
+    }
+
+    /// Get the number of strings
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Check if the dictionary is empty
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Get the number of strings following each bucket header
+    pub fn bucket_size(&self) -> usize {
+        self.bucket_size
+    }
+
+    /// Get the number of buckets
+    pub fn num_buckets(&self) -> usize {
+        self.header_offsets.size() - 1
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.header_offsets.bytes()
+            + self.bucket_offsets.bytes()
+            + self.headers.len()
+            + self.buckets.len()
+    }
+
+    fn header(&self, bucket: usize) -> &[u8] {
+        let begin = self.header_offsets.access(bucket) as usize;
+        let end = self.header_offsets.access(bucket + 1) as usize;
+        &self.headers[begin..end]
+    }
+
+    /// Decode the strings of a bucket, header first
+    fn bucket_strings(&self, bucket: usize) -> impl Iterator<Item = Vec<u8>> + '_ {
+        let mut curr = self.header(bucket).to_vec();
+        let mut pos = self.bucket_offsets.access(bucket) as usize;
+        let end = self.bucket_offsets.access(bucket + 1) as usize;
+        let mut header = true;
+        std::iter::from_fn(move || {
+            if header {
+                header = false;
+                return Some(curr.clone());
+            }
+            if pos == end {
+                return None;
+            }
+            let lcp = read_varint(&self.buckets, &mut pos);
+            let suffix_len = read_varint(&self.buckets, &mut pos);
+            curr.truncate(lcp);
+            curr.extend_from_slice(&self.buckets[pos..pos + suffix_len]);
+            pos += suffix_len;
+            Some(curr.clone())
+        })
+    }
+
+    /// Get the number of strings for which `pred` holds, given that it holds
+    /// for a prefix of the strings in lexicographic order
+    fn partition_point<P>(&self, pred: P) -> usize
+    where
+        P: Fn(&[u8]) -> bool,
+    {
+        // Find the last bucket whose header satisfies the predicate
+        let (mut lo, mut hi) = (0, self.num_buckets());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if pred(self.header(mid)) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        if lo == 0 {
+            return 0;
+        }
+        let bucket = lo - 1;
+        let in_bucket = self
+            .bucket_strings(bucket)
+            .take_while(|s| pred(s))
+            .count();
+        bucket * (self.bucket_size + 1) + in_bucket
+    }
+}
+
+impl Default for FcDictionary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn lcp(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+pub(crate) fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte < 0x80 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms() -> Vec<String> {
+        let mut terms: Vec<String> = [
+            "a", "new", "newark", "news", "newspaper", "next", "nexus", "tickets",
+            "times", "timestamp", "tiny", "york", "yorkshire", "zebra",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        terms.sort();
+        terms
+    }
+
+    #[test]
+    fn test_locate_and_extract() {
+        let terms = terms();
+        for bucket_size in [1, 2, 3, 16] {
+            let dictionary = FcDictionary::build(&terms, bucket_size).unwrap();
+            assert_eq!(dictionary.size(), terms.len());
+            for (i, term) in terms.iter().enumerate() {
+                let id = i as IdType + 1;
+                assert_eq!(dictionary.locate(term), Some(id));
+                assert_eq!(dictionary.extract(id).as_deref(), Some(term.as_str()));
+            }
+            assert_eq!(dictionary.locate("newt"), None);
+            assert_eq!(dictionary.locate(""), None);
+            assert_eq!(dictionary.locate("zz"), None);
+            assert_eq!(dictionary.extract(0), None);
+            assert_eq!(dictionary.extract(terms.len() as IdType + 1), None);
+            assert_eq!(dictionary.iter().collect::<Vec<_>>(), terms);
+        }
+    }
+
+    #[test]
+    fn test_locate_prefix() {
+        let terms = terms();
+        for bucket_size in [1, 2, 3, 16] {
+            let dictionary = FcDictionary::build(&terms, bucket_size).unwrap();
+            assert_eq!(dictionary.locate_prefix("new"), ValueRange { begin: 2, end: 5 });
+            assert_eq!(dictionary.locate_prefix("ne"), ValueRange { begin: 2, end: 7 });
+            assert_eq!(dictionary.locate_prefix("ti"), ValueRange { begin: 8, end: 11 });
+            assert_eq!(dictionary.locate_prefix("zebra"), ValueRange { begin: 14, end: 14 });
+            assert_eq!(dictionary.locate_prefix(""), ValueRange { begin: 1, end: 14 });
+            assert!(dictionary.locate_prefix("newt").is_invalid());
+            assert!(dictionary.locate_prefix("b").is_invalid());
+        }
+    }
+
+    #[test]
+    fn test_build_errors_and_empty() {
+        assert!(FcDictionary::build(["b", "a"], 4).is_err());
+        assert!(FcDictionary::build(["a", "a"], 4).is_err());
+        assert!(FcDictionary::build(["a"], 0).is_err());
+
+        let dictionary = FcDictionary::build(Vec::<String>::new(), 4).unwrap();
+        assert!(dictionary.is_empty());
+        assert_eq!(dictionary.locate("a"), None);
+        assert!(dictionary.locate_prefix("").is_invalid());
+    }
+
+    #[test]
+    fn test_front_coding_saves_space() {
+        let terms: Vec<String> = (0..1000).map(|i| format!("prefix_shared_{:04}", i)).collect();
+        let dictionary = FcDictionary::build(&terms, DEFAULT_BUCKET_SIZE).unwrap();
+        let raw: usize = terms.iter().map(|t| t.len()).sum();
+        assert!(dictionary.bytes() < raw / 2);
+    }
+}
@@ -1,53 +1,276 @@
-use async_graphql::{Object, Schema, SimpleObject, InputObject, EmptySubscription};
-use crate::autocomplete::Autocomplete;
+use std::collections::BTreeMap;
+use async_graphql::{Enum, Object, Schema, SimpleObject, InputObject, EmptySubscription, Json};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use crate::autocomplete::{self as core, Autocomplete, CompletionEntry};
+use crate::dictionary;
+use crate::tag_index;
+use crate::limits::{resolve_max_edits, ResultLimit};
+use crate::server::{init_autocomplete, Compactions};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+/// A single completion result
 #[derive(SimpleObject)]
 struct Completion {
     text: String,
     score: f32,
+    /// Payload given for the completion at initialization, null if empty
+    payload: Option<Payload>,
 }
 
+/// Opaque data attached to a completion
+#[derive(SimpleObject)]
+struct Payload {
+    /// Base64-encoded bytes
+    data: String,
+    /// String attributes, as a JSON object
+    attributes: Json<BTreeMap<String, String>>,
+}
+
+impl From<&dictionary::Payload> for Payload {
+    fn from(payload: &dictionary::Payload) -> Self {
+        Self {
+            data: BASE64.encode(&payload.data),
+            attributes: Json(payload.attributes.clone()),
+        }
+    }
+}
+
+/// Opaque data to attach to a completion
+#[derive(InputObject)]
+struct PayloadInput {
+    /// Base64-encoded bytes
+    #[graphql(default)]
+    data: String,
+    /// String attributes, as a JSON object
+    #[graphql(default)]
+    attributes: Json<BTreeMap<String, String>>,
+}
+
+impl TryFrom<PayloadInput> for dictionary::Payload {
+    type Error = String;
+
+    fn try_from(payload: PayloadInput) -> Result<Self, Self::Error> {
+        let data = BASE64
+            .decode(&payload.data)
+            .map_err(|e| format!("invalid payload data: {}", e))?;
+        Ok(Self {
+            data,
+            attributes: payload.attributes.0,
+        })
+    }
+}
+
+/// How the prefix is matched against the completions
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+enum CompletionMode {
+    /// Completions starting with the prefix string, matched term by term if
+    /// the server has no character trie
+    Prefix,
+    /// Completions containing every complete term of the prefix and a term
+    /// starting with its last token, in any position
+    Conjunctive,
+    /// Completions starting with a string within maxEdits edits of the
+    /// prefix, their score discounted per edit. Requires the server to keep
+    /// its character trie.
+    Fuzzy,
+    /// Completions with a word starting with the prefix string. Requires the
+    /// server to keep suffix arrays for infix queries.
+    Infix,
+    /// Completions containing the prefix string at any position. Requires
+    /// the server to keep suffix arrays for infix queries.
+    Substring,
+}
+
+impl CompletionMode {
+    /// Convert a requested mode, resolving `max_edits` for fuzzy completion
+    fn resolve(self, max_edits: Option<i32>) -> Result<core::CompletionMode, String> {
+        Ok(match self {
+            CompletionMode::Prefix => core::CompletionMode::Prefix,
+            CompletionMode::Conjunctive => core::CompletionMode::Conjunctive,
+            CompletionMode::Fuzzy => core::CompletionMode::Fuzzy {
+                max_edits: resolve_max_edits(max_edits)?,
+            },
+            CompletionMode::Infix => core::CompletionMode::Infix,
+            CompletionMode::Substring => core::CompletionMode::Substring,
+        })
+    }
+}
+
+/// Order of the completions returned
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+enum ResultOrder {
+    /// By descending score, ties broken by the normalized text
+    Score,
+    /// The completions selected by score, in lexicographic order of their
+    /// text
+    Lexicographic,
+}
+
+impl From<ResultOrder> for core::ResultOrder {
+    fn from(order: ResultOrder) -> Self {
+        match order {
+            ResultOrder::Score => core::ResultOrder::Score,
+            ResultOrder::Lexicographic => core::ResultOrder::Lexicographic,
+        }
+    }
+}
+
+/// How `init` treats the strings already indexed
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+enum InitMode {
+    /// Add the strings, replacing the score, payload and tags of indexed ones
+    Append,
+    /// Index only the given strings, swapping the new index in at once.
+    /// Changes made while it is built are applied on top of it, and dropped
+    /// for strings it lacks. Fails if another replacement begins meanwhile.
+    Replace,
+}
+
+impl From<InitMode> for core::InitMode {
+    fn from(mode: InitMode) -> Self {
+        match mode {
+            InitMode::Append => core::InitMode::Append,
+            InitMode::Replace => core::InitMode::Replace,
+        }
+    }
+}
+
+/// Response type for completions
 #[derive(SimpleObject)]
 struct CompleteResponse {
     completions: Vec<Completion>,
 }
 
+/// System statistics
 #[derive(SimpleObject)]
 struct Stats {
     num_terms: i32,
     memory_bytes: i64,
 }
 
+/// Response type for initialization
 #[derive(SimpleObject)]
 struct InitResponse {
     success: bool,
     error: Option<String>,
 }
 
+/// Outcome of an incremental change
+#[derive(SimpleObject)]
+struct MutationResponse {
+    success: bool,
+    error: Option<String>,
+    /// Number of strings after the change
+    num_terms: i32,
+}
+
+impl MutationResponse {
+    fn new(result: Result<(), String>, autocomplete: &Autocomplete) -> Self {
+        Self {
+            success: result.is_ok(),
+            error: result.err(),
+            num_terms: autocomplete.num_terms() as i32,
+        }
+    }
+}
+
+/// Input type for string with score
 #[derive(InputObject)]
 struct StringScoreInput {
     text: String,
+    /// Must be finite
     score: f32,
+    /// Data returned with the completion
+    payload: Option<PayloadInput>,
+    /// Tags matched by the filter of complete
+    #[graphql(default)]
+    tags: Vec<String>,
+}
+
+/// Restriction of the completions to those carrying some tags
+#[derive(InputObject)]
+struct TagFilterInput {
+    /// Accept only completions with at least one of these tags, unless empty
+    #[graphql(default)]
+    include: Vec<String>,
+    /// Reject completions with any of these tags
+    #[graphql(default)]
+    exclude: Vec<String>,
+}
+
+impl TryFrom<StringScoreInput> for CompletionEntry {
+    type Error = String;
+
+    fn try_from(string: StringScoreInput) -> Result<Self, Self::Error> {
+        let payload = string.payload.map(TryInto::try_into).transpose()?;
+        Ok(Self {
+            text: string.text,
+            score: string.score,
+            payload: payload.unwrap_or_default(),
+            tags: string.tags,
+        })
+    }
+}
+
+/// Convert the inputs of a mutation into entries to index
+fn completion_entries(strings: Vec<StringScoreInput>) -> Result<Vec<CompletionEntry>, String> {
+    strings.into_iter().map(TryInto::try_into).collect()
+}
+
+impl From<TagFilterInput> for tag_index::TagFilter {
+    fn from(filter: TagFilterInput) -> Self {
+        Self {
+            include: filter.include,
+            exclude: filter.exclude,
+        }
+    }
 }
 
 pub struct QueryRoot {
     autocomplete: Arc<Mutex<Autocomplete>>,
+    result_limit: ResultLimit,
 }
 
 #[Object]
 impl QueryRoot {
-    async fn complete(&self, prefix: String, _max_results: Option<i32>) -> CompleteResponse {
+    /// Get completions for a prefix, in the given order. maxResults defaults
+    /// to the server default and is clamped to the server's ceiling.
+    /// maxEdits applies to fuzzy completion, defaults to the server default
+    /// and may be 0 for exact matches only. filter keeps only the
+    /// completions with matching tags.
+    #[allow(clippy::too_many_arguments)]
+    async fn complete(
+        &self,
+        prefix: String,
+        max_results: Option<i32>,
+        #[graphql(default_with = "CompletionMode::Prefix")] mode: CompletionMode,
+        #[graphql(default_with = "ResultOrder::Score")] order: ResultOrder,
+        max_edits: Option<i32>,
+        filter: Option<TagFilterInput>,
+    ) -> async_graphql::Result<CompleteResponse> {
+        let k = self.result_limit.resolve(max_results)?;
+        let mode = mode.resolve(max_edits)?;
+        let filter = filter.map(Into::into).unwrap_or_default();
         let autocomplete = self.autocomplete.lock().await;
-        let completions = autocomplete.complete(&prefix);
+        let completions =
+            autocomplete.complete_with_filter(&prefix, k, mode, order.into(), &filter)?;
         let completions = completions.into_iter()
-            .map(|(text, score)| Completion { text, score })
+            .map(|(text, score)| Completion {
+                payload: autocomplete
+                    .payload(&text)
+                    .filter(|payload| !payload.is_empty())
+                    .map(Payload::from),
+                text,
+                score,
+            })
             .collect();
         
-        CompleteResponse { completions }
+        Ok(CompleteResponse { completions })
     }
 
+    /// Get system statistics
     async fn stats(&self) -> Stats {
         let autocomplete = self.autocomplete.lock().await;
         Stats {
@@ -59,18 +282,29 @@ impl QueryRoot {
 
 pub struct MutationRoot {
     autocomplete: Arc<Mutex<Autocomplete>>,
+    compactions: Compactions,
 }
 
 #[Object]
 impl MutationRoot {
-    async fn init(&self, strings: Vec<StringScoreInput>) -> InitResponse {
-        let strings: Vec<(String, f32)> = strings
-            .into_iter()
-            .map(|s| (s.text, s.score))
-            .collect();
-            
-        let mut autocomplete = self.autocomplete.lock().await;
-        match autocomplete.init(&strings) {
+    /// Initialize the autocomplete system with strings and scores. mode
+    /// tells whether they are added to the strings already indexed or
+    /// replace them.
+    async fn init(
+        &self,
+        strings: Vec<StringScoreInput>,
+        #[graphql(default_with = "InitMode::Append")] mode: InitMode,
+    ) -> InitResponse {
+        let entries = match completion_entries(strings) {
+            Ok(entries) => entries,
+            Err(e) => return InitResponse {
+                success: false,
+                error: Some(e),
+            },
+        };
+
+        let mode = mode.into();
+        match init_autocomplete(&self.autocomplete, &entries, mode, &self.compactions).await {
             Ok(_) => InitResponse {
                 success: true,
                 error: None,
@@ -81,14 +315,46 @@ impl MutationRoot {
             },
         }
     }
+
+    /// Add a string that is not indexed yet
+    async fn add(&self, string: StringScoreInput) -> MutationResponse {
+        let mut autocomplete = self.autocomplete.lock().await;
+        let result = CompletionEntry::try_from(string).and_then(|entry| autocomplete.add(entry));
+        self.compactions.spawn_if_needed(&self.autocomplete, &mut autocomplete);
+        MutationResponse::new(result, &autocomplete)
+    }
+
+    /// Change the score of an indexed string to a finite score
+    async fn update_score(&self, text: String, score: f32) -> MutationResponse {
+        let mut autocomplete = self.autocomplete.lock().await;
+        let result = autocomplete.update_score(&text, score);
+        self.compactions.spawn_if_needed(&self.autocomplete, &mut autocomplete);
+        MutationResponse::new(result, &autocomplete)
+    }
+
+    /// Remove an indexed string
+    async fn remove(&self, text: String) -> MutationResponse {
+        let mut autocomplete = self.autocomplete.lock().await;
+        let result = autocomplete.remove(&text);
+        self.compactions.spawn_if_needed(&self.autocomplete, &mut autocomplete);
+        MutationResponse::new(result, &autocomplete)
+    }
+
+    /// Add strings, or replace the score, payload and tags of indexed ones
+    async fn upsert(&self, strings: Vec<StringScoreInput>) -> MutationResponse {
+        let mut autocomplete = self.autocomplete.lock().await;
+        let result = completion_entries(strings).and_then(|entries| autocomplete.upsert(&entries));
+        self.compactions.spawn_if_needed(&self.autocomplete, &mut autocomplete);
+        MutationResponse::new(result, &autocomplete)
+    }
 }
 
 pub type AppSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
 
-pub fn create_schema(autocomplete: Arc<Mutex<Autocomplete>>) -> AppSchema {
+pub fn create_schema(autocomplete: Arc<Mutex<Autocomplete>>, result_limit: ResultLimit) -> AppSchema {
     Schema::build(
-        QueryRoot { autocomplete: autocomplete.clone() },
-        MutationRoot { autocomplete },
+        QueryRoot { autocomplete: autocomplete.clone(), result_limit },
+        MutationRoot { autocomplete, compactions: Compactions::default() },
         EmptySubscription,
     )
     .finish()
@@ -1,60 +1,187 @@
-use crate::types::IdType;
+use std::sync::OnceLock;
+use crate::ef::{DArray, EfSequence};
+use crate::inverted_index::DocCursor;
+use crate::types::{IdType, ValueRange};
 
-/// Block in the inverted index
+/// Default fraction of the number of docs bounding the postings of a block
+pub const DEFAULT_BLOCK_FRACTION: f32 = 0.01;
+
+/// Block of consecutive terms in the blocked inverted index
+#[derive(Debug, Clone)]
 struct Block {
-    term_id: IdType,
-    num_docs: usize,
-    docs: Vec<IdType>,
+    first_term: IdType,
+    /// Docs containing any term of the block, in increasing order
+    docs: EfSequence,
+    /// Offsets into `terms` of the term list of each doc
+    offsets: EfSequence,
+    /// Sorted term ids of each doc within the block, minus `first_term`
+    terms: CompactVector,
 }
 
 impl Block {
-    /// Create a new block
-    fn new(term_id: IdType) -> Self {
-        Self {
-            term_id,
-            num_docs: 0,
-            docs: Vec::new(),
-        }
+    /// Check if the doc at position `pos` contains a term in `range`
+    fn contains_term_in_range(&self, pos: usize, range: ValueRange) -> bool {
+        let begin = self.offsets.access(pos) as usize;
+        let end = self.offsets.access(pos + 1) as usize;
+        (begin..end).any(|i| range.contains(self.terms.access(i) + self.first_term as u64))
     }
 
-    /// Add a document to the block
-    fn add_doc(&mut self, doc_id: IdType) {
-        self.docs.push(doc_id);
-        self.num_docs += 1;
-    }
-
-    /// Get the number of documents in the block
-    fn size(&self) -> usize {
-        self.num_docs
+    fn bytes(&self) -> usize {
+        std::mem::size_of_val(&self.first_term)
+            + self.docs.bytes()
+            + self.offsets.bytes()
+            + self.terms.bytes()
     }
 }
 
-/// Blocked inverted index for efficient document retrieval
+/// Inverted index grouping consecutive terms into blocks.
+///
+/// Each block stores the union of the posting lists of its terms and, for
+/// every doc in the union, which of the block's terms it contains. Terms
+/// are added to a block until it holds at least a fraction `c` of the
+/// number of docs in postings, so that the docs containing any term of a
+/// range are found by scanning a few lists rather than one per term.
+#[derive(Debug, Clone)]
 pub struct BlockedInvertedIndex {
-    blocks: Vec<Vec<IdType>>,
-    block_size: usize,
+    num_docs: usize,
+    num_terms: usize,
+    num_postings: usize,
+    /// Last term id of each block
+    boundaries: Vec<IdType>,
+    blocks: Vec<Block>,
 }
 
 impl BlockedInvertedIndex {
-    /// Create a new blocked inverted index
-    pub fn new(block_size: usize) -> Self {
+    /// Create a new empty index
+    pub fn new() -> Self {
         Self {
+            num_docs: 0,
+            num_terms: 0,
+            num_postings: 0,
+            boundaries: Vec::new(),
             blocks: Vec::new(),
-            block_size,
         }
     }
 
-    /// Add a document to the index
-    pub fn insert(&mut self, id: IdType) {
-        if self.blocks.is_empty() || self.blocks.last().unwrap().len() >= self.block_size {
-            self.blocks.push(Vec::with_capacity(self.block_size));
+    /// Build the index from a forward index, listing the ids (from 1 to
+    /// `num_terms`) of the terms of each doc
+    pub fn build<I, T>(forward: I, num_terms: usize, c: f32) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[IdType]>,
+    {
+        if !(c > 0.0 && c <= 1.0) {
+            return Err(format!("c must be in (0, 1], got {}", c));
+        }
+
+        let forward: Vec<T> = forward.into_iter().collect();
+        let mut postings = vec![Vec::new(); num_terms];
+        for (doc_id, terms) in forward.iter().enumerate() {
+            for &term_id in terms.as_ref() {
+                if term_id == 0 || term_id as usize > num_terms {
+                    return Err(format!("term id {} out of range 1..={}", term_id, num_terms));
+                }
+                let list = &mut postings[term_id as usize - 1];
+                if list.last() != Some(&(doc_id as IdType)) {
+                    list.push(doc_id as IdType);
+                }
+            }
+        }
+
+        let mut index = Self {
+            num_docs: forward.len(),
+            num_terms,
+            ..Self::new()
+        };
+        let min_postings = ((forward.len() as f32 * c) as usize).max(1);
+        let mut first_term = 1;
+        let mut block_postings = 0;
+        for term_id in 1..=num_terms {
+            block_postings += postings[term_id - 1].len();
+            if block_postings < min_postings && term_id < num_terms {
+                continue;
+            }
+
+            let mut docs: Vec<IdType> = postings[first_term - 1..term_id]
+                .iter()
+                .flatten()
+                .copied()
+                .collect();
+            docs.sort_unstable();
+            docs.dedup();
+
+            let range = first_term as IdType..=term_id as IdType;
+            let mut offsets = vec![0];
+            let mut terms = Vec::new();
+            for &doc_id in &docs {
+                let mut doc_terms: Vec<IdType> = forward[doc_id as usize]
+                    .as_ref()
+                    .iter()
+                    .filter(|t| range.contains(t))
+                    .map(|t| t - first_term as IdType)
+                    .collect();
+                doc_terms.sort_unstable();
+                doc_terms.dedup();
+                terms.extend(doc_terms.into_iter().map(u64::from));
+                offsets.push(terms.len() as u64);
+            }
+
+            index.num_postings += block_postings;
+            index.boundaries.push(term_id as IdType);
+            index.blocks.push(Block {
+                first_term: first_term as IdType,
+                docs: EfSequence::build(docs.iter().map(|&d| d as u64))?,
+                offsets: EfSequence::build(offsets)?,
+                terms: CompactVector::build(terms),
+            });
+            first_term = term_id + 1;
+            block_postings = 0;
+        }
+        Ok(index)
+    }
+
+    /// Get the block holding `term_id`
+    pub fn block_id(&self, term_id: IdType) -> usize {
+        self.boundaries.partition_point(|&last| last < term_id)
+    }
+
+    /// Get a cursor over the docs containing any term in the inclusive
+    /// `range` of term ids, in increasing order
+    pub fn docs_in_range(&self, range: ValueRange) -> BlockedDocsCursor<'_> {
+        let cursors = if range.is_invalid() || range.begin > range.end {
+            Vec::new()
+        } else {
+            let first = self.block_id(range.begin as IdType);
+            let last = self
+                .block_id(range.end as IdType)
+                .min(self.blocks.len().saturating_sub(1));
+            self.blocks
+                .get(first..=last)
+                .unwrap_or_default()
+                .iter()
+                .map(|block| BlockCursor { block, pos: 0 })
+                .collect()
+        };
+        BlockedDocsCursor {
+            range,
+            cursors,
+            position: 0,
         }
-        self.blocks.last_mut().unwrap().push(id);
     }
 
-    /// Get documents for a term
-    pub fn get(&self, block_id: usize) -> Option<&[IdType]> {
-        self.blocks.get(block_id).map(|v| v.as_slice())
+    /// Get the number of docs
+    pub fn num_docs(&self) -> usize {
+        self.num_docs
+    }
+
+    /// Get the number of terms
+    pub fn num_terms(&self) -> usize {
+        self.num_terms
+    }
+
+    /// Get the total length of the posting lists of all terms
+    pub fn num_postings(&self) -> usize {
+        self.num_postings
     }
 
     /// Get the number of blocks
@@ -62,102 +189,411 @@ impl BlockedInvertedIndex {
         self.blocks.len()
     }
 
-    /// Get the block size
-    pub fn block_size(&self) -> usize {
-        self.block_size
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        std::mem::size_of_val(&self.num_docs)
+            + std::mem::size_of_val(&self.num_terms)
+            + std::mem::size_of_val(&self.num_postings)
+            + self.boundaries.len() * std::mem::size_of::<IdType>()
+            + self.blocks.iter().map(Block::bytes).sum::<usize>()
+    }
+}
+
+impl Default for BlockedInvertedIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Position within the docs of a block
+struct BlockCursor<'a> {
+    block: &'a Block,
+    pos: usize,
+}
+
+impl BlockCursor<'_> {
+    /// Move to the first doc `>= x` containing a term in `range`
+    fn seek(&mut self, x: IdType, range: ValueRange) {
+        let docs = &self.block.docs;
+        if self.pos < docs.len() && docs.access(self.pos) < x as u64 {
+            self.pos = docs.next_geq(x as u64).map_or(docs.len(), |(pos, _)| pos);
+        }
+        while self.pos < docs.len() && !self.block.contains_term_in_range(self.pos, range) {
+            self.pos += 1;
+        }
+    }
+
+    fn current(&self) -> Option<IdType> {
+        (self.pos < self.block.docs.len()).then(|| self.block.docs.access(self.pos) as IdType)
+    }
+}
+
+/// Cursor over the docs containing any term of a range, in increasing order
+pub struct BlockedDocsCursor<'a> {
+    range: ValueRange,
+    cursors: Vec<BlockCursor<'a>>,
+    /// Smallest doc that may still be returned
+    position: IdType,
+}
+
+impl DocCursor for BlockedDocsCursor<'_> {
+    fn next_geq(&mut self, x: IdType) -> Option<IdType> {
+        let x = x.max(self.position);
+        let mut min = None;
+        for cursor in &mut self.cursors {
+            cursor.seek(x, self.range);
+            min = match (min, cursor.current()) {
+                (Some(a), Some(b)) => Some(IdType::min(a, b)),
+                (a, b) => a.or(b),
+            };
+        }
+        self.position = min?;
+        min
+    }
+}
+
+impl Iterator for BlockedDocsCursor<'_> {
+    type Item = IdType;
+
+    fn next(&mut self) -> Option<IdType> {
+        let doc_id = self.next_geq(self.position)?;
+        self.position = doc_id + 1;
+        Some(doc_id)
     }
 }
 
-/// Compact vector for efficient storage
+/// Vector of integers bit-packed with a fixed width.
+///
+/// Every value takes `width` bits, chosen from the largest value when built
+/// from an iterator, so a vector of 17-bit values costs 17 bits per value.
+#[derive(Debug, Clone)]
 pub struct CompactVector {
-    data: Vec<u8>,
-    element_size: usize,
-    num_elements: usize,
+    size: usize,
+    width: usize,
+    data: BitVector,
 }
 
 impl CompactVector {
-    /// Create a new compact vector
-    pub fn new(element_size: usize) -> Self {
-        Self {
-            data: Vec::new(),
-            element_size,
-            num_elements: 0,
+    /// Create a new empty vector
+    pub fn new() -> Self {
+        CompactVectorBuilder::new(1).build()
+    }
+
+    /// Build the vector from `values`, using the fewest bits holding the
+    /// largest one
+    pub fn build<I>(values: I) -> Self
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        let values: Vec<u64> = values.into_iter().collect();
+        let max = values.iter().copied().max().unwrap_or(0);
+        let mut builder = CompactVectorBuilder::new(bits_for(max));
+        for value in values {
+            builder.push(value);
         }
+        builder.build()
     }
 
-    /// Add an element to the vector
-    pub fn push(&mut self, element: &[u8]) {
-        assert_eq!(element.len(), self.element_size);
-        self.data.extend_from_slice(element);
-        self.num_elements += 1;
+    /// Get the value at position `i`
+    pub fn access(&self, i: usize) -> u64 {
+        assert!(i < self.size, "index {} out of bounds for size {}", i, self.size);
+        self.data.get_bits(i * self.width, self.width)
     }
 
-    /// Get an element from the vector
-    pub fn get(&self, index: usize) -> Option<&[u8]> {
-        if index >= self.num_elements {
-            return None;
-        }
-        let start = index * self.element_size;
-        let end = start + self.element_size;
-        Some(&self.data[start..end])
+    /// Get the value at position `i`, or `None` if out of bounds
+    pub fn get(&self, i: usize) -> Option<u64> {
+        (i < self.size).then(|| self.access(i))
     }
 
-    /// Get the number of elements
+    /// Iterate over the values in order
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.size).map(move |i| self.access(i))
+    }
+
+    /// Get the number of bits per value
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the number of values
     pub fn size(&self) -> usize {
-        self.num_elements
+        self.size
+    }
+
+    /// Check if the vector is empty
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
     }
 
     /// Get the size in bytes
     pub fn bytes(&self) -> usize {
-        self.data.len()
+        std::mem::size_of_val(&self.size) + std::mem::size_of_val(&self.width) + self.data.bytes()
+    }
+}
+
+impl Default for CompactVector {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-/// Bit vector for efficient bit-level operations
+/// Builder of a `CompactVector` with a given width
+#[derive(Debug, Clone)]
+pub struct CompactVectorBuilder {
+    size: usize,
+    width: usize,
+    bits: BitVectorBuilder,
+}
+
+impl CompactVectorBuilder {
+    /// Create a new empty builder for values of `width` bits
+    pub fn new(width: usize) -> Self {
+        assert!((1..=64).contains(&width), "width must be in 1..=64, got {}", width);
+        Self {
+            size: 0,
+            width,
+            bits: BitVectorBuilder::new(),
+        }
+    }
+
+    /// Create a builder holding `size` zeros of `width` bits
+    pub fn with_size(size: usize, width: usize) -> Self {
+        let mut builder = Self::new(width);
+        builder.size = size;
+        builder.bits.zero_extend(size * width);
+        builder
+    }
+
+    /// Append a value
+    pub fn push(&mut self, value: u64) {
+        self.check_width(value);
+        self.bits.append_bits(value, self.width);
+        self.size += 1;
+    }
+
+    /// Overwrite the value at position `i`
+    pub fn set(&mut self, i: usize, value: u64) {
+        assert!(i < self.size, "index {} out of bounds for size {}", i, self.size);
+        self.check_width(value);
+        self.bits.set_bits(i * self.width, value, self.width);
+    }
+
+    /// Get the number of values
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Check if no values were pushed
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Build the vector
+    pub fn build(self) -> CompactVector {
+        CompactVector {
+            size: self.size,
+            width: self.width,
+            data: self.bits.build_unindexed(),
+        }
+    }
+
+    fn check_width(&self, value: u64) {
+        assert!(
+            self.width == 64 || value >> self.width == 0,
+            "value {} does not fit in {} bits",
+            value,
+            self.width
+        );
+    }
+}
+
+/// Get the number of bits needed to write `value`, at least 1
+pub fn bits_for(value: u64) -> usize {
+    (u64::BITS - value.leading_zeros()).max(1) as usize
+}
+
+/// Number of bits covered by each rank sample
+const RANK_BLOCK_BITS: usize = 512;
+
+/// Bit vector with constant-time rank and select.
+///
+/// Bits are stored in 64-bit words, with cumulative popcounts sampled every
+/// `RANK_BLOCK_BITS` bits and `DArray` select indexes over the ones and the
+/// zeros. The indexes are built on the first rank or select, and flipping a
+/// bit with `set` or `clear` drops them so that the next call rebuilds them.
+#[derive(Debug, Clone)]
 pub struct BitVector {
-    data: Vec<u8>,
+    words: Vec<u64>,
     num_bits: usize,
+    index: OnceLock<RankSelectIndex>,
+}
+
+/// Rank samples and select indexes of a `BitVector`
+#[derive(Debug, Clone)]
+struct RankSelectIndex {
+    rank_samples: Vec<u64>,
+    select1: DArray,
+    select0: DArray,
+}
+
+impl RankSelectIndex {
+    fn build(words: &[u64], num_bits: usize) -> Self {
+        let words_per_block = RANK_BLOCK_BITS / 64;
+        let mut ones = 0;
+        let mut rank_samples = Vec::with_capacity(words.len() / words_per_block + 1);
+        for block in words.chunks(words_per_block) {
+            rank_samples.push(ones);
+            ones += block.iter().map(|w| w.count_ones() as u64).sum::<u64>();
+        }
+        rank_samples.push(ones);
+        Self {
+            rank_samples,
+            select1: DArray::new(words, num_bits, true),
+            select0: DArray::new(words, num_bits, false),
+        }
+    }
+
+    fn bytes(&self) -> usize {
+        self.rank_samples.len() * std::mem::size_of::<u64>()
+            + self.select1.bytes()
+            + self.select0.bytes()
+    }
 }
 
 impl BitVector {
     /// Create a new bit vector
     pub fn new(num_bits: usize) -> Self {
-        let num_bytes = (num_bits + 7) / 8;
-        Self {
-            data: vec![0; num_bytes],
+        Self::from_words(vec![0; num_bits.div_ceil(64)], num_bits, true)
+    }
+
+    fn from_words(words: Vec<u64>, num_bits: usize, index: bool) -> Self {
+        let mut bit_vector = Self {
+            words,
             num_bits,
+            index: OnceLock::new(),
+        };
+        if index {
+            bit_vector.build_index();
         }
+        bit_vector
     }
 
     /// Set a bit
     pub fn set(&mut self, index: usize) {
-        if index < self.num_bits {
-            let byte_idx = index / 8;
-            let bit_idx = index % 8;
-            self.data[byte_idx] |= 1 << bit_idx;
+        if index < self.num_bits && !self.test(index) {
+            self.words[index >> 6] |= 1 << (index & 63);
+            self.index.take();
         }
     }
 
     /// Clear a bit
     pub fn clear(&mut self, index: usize) {
-        if index < self.num_bits {
-            let byte_idx = index / 8;
-            let bit_idx = index % 8;
-            self.data[byte_idx] &= !(1 << bit_idx);
+        if self.test(index) {
+            self.words[index >> 6] &= !(1 << (index & 63));
+            self.index.take();
         }
     }
 
     /// Test a bit
     pub fn test(&self, index: usize) -> bool {
         if index < self.num_bits {
-            let byte_idx = index / 8;
-            let bit_idx = index % 8;
-            (self.data[byte_idx] & (1 << bit_idx)) != 0
+            (self.words[index >> 6] >> (index & 63)) & 1 != 0
         } else {
             false
         }
     }
 
+    /// Get the `len <= 64` bits starting at `pos`, least significant first
+    pub fn get_bits(&self, pos: usize, len: usize) -> u64 {
+        assert!(len <= 64 && pos + len <= self.num_bits, "bits out of bounds");
+        if len == 0 {
+            return 0;
+        }
+        let (word, shift) = (pos >> 6, pos & 63);
+        let mut bits = self.words[word] >> shift;
+        if shift + len > 64 {
+            bits |= self.words[word + 1] << (64 - shift);
+        }
+        if len == 64 {
+            bits
+        } else {
+            bits & ((1 << len) - 1)
+        }
+    }
+
+    /// Build the rank and select indexes now rather than on the next rank or
+    /// select
+    pub fn build_index(&mut self) {
+        self.index();
+    }
+
+    /// Get the number of set bits
+    pub fn num_ones(&self) -> usize {
+        self.index().select1.num_positions()
+    }
+
+    /// Get the number of set bits before position `i`.
+    ///
+    /// # Panics
+    ///
+    /// If `i` is past the end.
+    pub fn rank1(&self, i: usize) -> usize {
+        assert!(i <= self.num_bits, "rank position out of bounds");
+        let block = i / RANK_BLOCK_BITS;
+        let word = i >> 6;
+        let mut rank = self.index().rank_samples[block] as usize;
+        for w in &self.words[block * RANK_BLOCK_BITS / 64..word] {
+            rank += w.count_ones() as usize;
+        }
+        if i & 63 != 0 {
+            rank += (self.words[word] & ((1 << (i & 63)) - 1)).count_ones() as usize;
+        }
+        rank
+    }
+
+    /// Get the number of clear bits before position `i`
+    pub fn rank0(&self, i: usize) -> usize {
+        i - self.rank1(i)
+    }
+
+    /// Get the position of the `i`-th (0-based) set bit
+    pub fn select1(&self, i: usize) -> usize {
+        self.index().select1.select(&self.words, i)
+    }
+
+    /// Get the position of the `i`-th (0-based) clear bit
+    pub fn select0(&self, i: usize) -> usize {
+        self.index().select0.select(&self.words, i)
+    }
+
+    /// Iterate over the positions of the set bits
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.ones_from(0)
+    }
+
+    /// Iterate over the positions of the set bits at or after `pos`
+    pub fn ones_from(&self, pos: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut word_idx = pos >> 6;
+        let mut word = self
+            .words
+            .get(word_idx)
+            .map_or(0, |w| w & (u64::MAX << (pos & 63)));
+        std::iter::from_fn(move || {
+            while word == 0 {
+                word_idx += 1;
+                word = *self.words.get(word_idx)?;
+            }
+            let bit = (word_idx << 6) + word.trailing_zeros() as usize;
+            word &= word - 1;
+            Some(bit)
+        })
+    }
+
+    /// Get the underlying words
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
     /// Get the number of bits
     pub fn size(&self) -> usize {
         self.num_bits
@@ -165,6 +601,280 @@ impl BitVector {
 
     /// Get the size in bytes
     pub fn bytes(&self) -> usize {
-        self.data.len()
+        std::mem::size_of_val(&self.num_bits)
+            + self.words.len() * std::mem::size_of::<u64>()
+            + self.index.get().map_or(0, RankSelectIndex::bytes)
+    }
+
+    fn index(&self) -> &RankSelectIndex {
+        self.index
+            .get_or_init(|| RankSelectIndex::build(&self.words, self.num_bits))
+    }
+}
+
+/// Builder appending bits to a `BitVector`
+#[derive(Debug, Clone, Default)]
+pub struct BitVectorBuilder {
+    words: Vec<u64>,
+    num_bits: usize,
+}
+
+impl BitVectorBuilder {
+    /// Create a new empty builder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a builder holding `num_bits` clear bits
+    pub fn with_size(num_bits: usize) -> Self {
+        Self {
+            words: vec![0; num_bits.div_ceil(64)],
+            num_bits,
+        }
+    }
+
+    /// Append a bit
+    pub fn push(&mut self, bit: bool) {
+        self.append_bits(bit as u64, 1);
+    }
+
+    /// Append the `len <= 64` low bits of `bits`, least significant first
+    pub fn append_bits(&mut self, bits: u64, len: usize) {
+        assert!(len <= 64, "at most 64 bits can be appended at once");
+        if len == 0 {
+            return;
+        }
+        let pos = self.num_bits;
+        self.zero_extend(len);
+        self.set_bits(pos, bits, len);
+    }
+
+    /// Append `n` clear bits
+    pub fn zero_extend(&mut self, n: usize) {
+        self.num_bits += n;
+        self.words.resize(self.num_bits.div_ceil(64), 0);
+    }
+
+    /// Set or clear the bit at `pos`
+    pub fn set(&mut self, pos: usize, bit: bool) {
+        assert!(pos < self.num_bits, "bit out of bounds");
+        let mask = 1 << (pos & 63);
+        if bit {
+            self.words[pos >> 6] |= mask;
+        } else {
+            self.words[pos >> 6] &= !mask;
+        }
+    }
+
+    /// Overwrite the `len <= 64` bits starting at `pos` with the low bits of
+    /// `bits`
+    pub fn set_bits(&mut self, pos: usize, bits: u64, len: usize) {
+        assert!(len <= 64 && pos + len <= self.num_bits, "bits out of bounds");
+        if len == 0 {
+            return;
+        }
+        let mask = if len == 64 { u64::MAX } else { (1 << len) - 1 };
+        let bits = bits & mask;
+        let (word, shift) = (pos >> 6, pos & 63);
+        self.words[word] &= !(mask << shift);
+        self.words[word] |= bits << shift;
+        let stored = 64 - shift;
+        if stored < len {
+            self.words[word + 1] &= !(mask >> stored);
+            self.words[word + 1] |= bits >> stored;
+        }
+    }
+
+    /// Get the number of bits
+    pub fn len(&self) -> usize {
+        self.num_bits
+    }
+
+    /// Check if no bits were appended
+    pub fn is_empty(&self) -> bool {
+        self.num_bits == 0
+    }
+
+    /// Build the bit vector and its rank and select indexes
+    pub fn build(self) -> BitVector {
+        BitVector::from_words(self.words, self.num_bits, true)
+    }
+
+    /// Build the bit vector without building its rank and select indexes
+    /// up front, for bits that are only read with `test` and `get_bits`
+    pub fn build_unindexed(self) -> BitVector {
+        BitVector::from_words(self.words, self.num_bits, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_vector() {
+        let values: Vec<u64> = (0..1000).map(|i| (i * 7919) % 100_000).collect();
+        let vector = CompactVector::build(values.iter().copied());
+        assert_eq!(vector.width(), 17);
+        assert_eq!(vector.size(), values.len());
+        for (i, &value) in values.iter().enumerate() {
+            assert_eq!(vector.access(i), value);
+        }
+        assert_eq!(vector.iter().collect::<Vec<_>>(), values);
+        assert_eq!(vector.get(1000), None);
+        assert!(vector.bytes() < values.len() * 17 / 8 + 64);
+
+        let mut builder = CompactVectorBuilder::with_size(3, 64);
+        builder.set(1, u64::MAX);
+        builder.push(5);
+        let vector = builder.build();
+        assert_eq!(vector.iter().collect::<Vec<_>>(), vec![0, u64::MAX, 0, 5]);
+
+        assert_eq!(CompactVector::build([0, 0]).width(), 1);
+        assert!(CompactVector::new().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit")]
+    fn test_compact_vector_builder_width() {
+        CompactVectorBuilder::new(3).push(8);
+    }
+
+    fn forward() -> Vec<Vec<IdType>> {
+        (0..300u32)
+            .map(|doc| {
+                let mut terms = vec![doc % 17 + 1, (doc * 7) % 40 + 1, 41 + doc % 3];
+                terms.sort_unstable();
+                terms.dedup();
+                terms
+            })
+            .collect()
     }
-} 
\ No newline at end of file
+
+    fn docs_with_term_in(forward: &[Vec<IdType>], begin: u64, end: u64) -> Vec<IdType> {
+        (0..forward.len() as IdType)
+            .filter(|&doc| {
+                forward[doc as usize]
+                    .iter()
+                    .any(|&t| (begin..=end).contains(&(t as u64)))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_blocked_inverted_index_ranges() {
+        let forward = forward();
+        for c in [0.01, 0.1, 1.0] {
+            let index = BlockedInvertedIndex::build(&forward, 43, c).unwrap();
+            assert_eq!(index.num_docs(), 300);
+            assert_eq!(index.num_postings(), forward.iter().map(Vec::len).sum::<usize>());
+            for (begin, end) in [(1, 1), (3, 9), (1, 43), (17, 41), (42, 43), (30, 30)] {
+                let range = ValueRange { begin, end };
+                assert_eq!(
+                    index.docs_in_range(range).collect::<Vec<_>>(),
+                    docs_with_term_in(&forward, begin, end),
+                    "range [{}, {}] with c = {}", begin, end, c
+                );
+            }
+            assert_eq!(index.docs_in_range(crate::types::global::INVALID_RANGE).next(), None);
+        }
+        assert!(BlockedInvertedIndex::build(&forward, 43, 0.0).is_err());
+        assert!(BlockedInvertedIndex::build(&forward, 40, 0.5).is_err());
+    }
+
+    #[test]
+    fn test_blocked_docs_cursor_next_geq() {
+        let forward = forward();
+        let index = BlockedInvertedIndex::build(&forward, 43, 0.05).unwrap();
+        let expected = docs_with_term_in(&forward, 5, 20);
+        let mut cursor = index.docs_in_range(ValueRange { begin: 5, end: 20 });
+        let first = expected.iter().copied().find(|&d| d >= 100);
+        assert_eq!(cursor.next_geq(100), first);
+        assert_eq!(cursor.next_geq(0), first);
+        assert_eq!(cursor.next(), first);
+        let second = expected.iter().copied().find(|&d| d > first.unwrap());
+        assert_eq!(cursor.next(), second);
+        assert_eq!(cursor.next_geq(1000), None);
+    }
+
+    fn bit_vector(num_bits: usize) -> (BitVector, Vec<bool>) {
+        let bits: Vec<bool> = (0..num_bits).map(|i| (i * 31 + i / 7) % 5 < 2).collect();
+        let mut builder = BitVectorBuilder::new();
+        for &bit in &bits {
+            builder.push(bit);
+        }
+        (builder.build(), bits)
+    }
+
+    #[test]
+    fn test_rank_select() {
+        let (bit_vector, bits) = bit_vector(3000);
+        let ones: Vec<usize> = (0..bits.len()).filter(|&i| bits[i]).collect();
+        let zeros: Vec<usize> = (0..bits.len()).filter(|&i| !bits[i]).collect();
+
+        assert_eq!(bit_vector.num_ones(), ones.len());
+        for i in 0..=bits.len() {
+            let rank = bits[..i].iter().filter(|&&b| b).count();
+            assert_eq!(bit_vector.rank1(i), rank);
+            assert_eq!(bit_vector.rank0(i), i - rank);
+        }
+        for (i, &pos) in ones.iter().enumerate() {
+            assert_eq!(bit_vector.select1(i), pos);
+        }
+        for (i, &pos) in zeros.iter().enumerate() {
+            assert_eq!(bit_vector.select0(i), pos);
+        }
+        assert_eq!(bit_vector.iter_ones().collect::<Vec<_>>(), ones);
+        assert_eq!(
+            bit_vector.ones_from(1000).collect::<Vec<_>>(),
+            ones.iter().copied().filter(|&p| p >= 1000).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_builder_bits() {
+        let mut builder = BitVectorBuilder::with_size(3);
+        builder.set(1, true);
+        builder.append_bits(0b1011, 4);
+        builder.append_bits(u64::MAX, 64);
+        builder.zero_extend(2);
+        builder.set_bits(60, 0, 4);
+        assert_eq!(builder.len(), 73);
+
+        let bit_vector = builder.build();
+        assert_eq!(bit_vector.get_bits(0, 7), 0b1011010);
+        assert_eq!(bit_vector.get_bits(7, 53), (1 << 53) - 1);
+        assert_eq!(bit_vector.get_bits(60, 13), 0b0011111110000);
+        assert_eq!(bit_vector.num_ones(), 1 + 3 + 60);
+    }
+
+    #[test]
+    fn test_set_clear_and_reindex() {
+        let mut bit_vector = BitVector::new(100);
+        bit_vector.set(3);
+        bit_vector.set(64);
+        bit_vector.set(200); // Out of bounds, ignored
+        bit_vector.clear(3);
+        bit_vector.set(99);
+        assert!(bit_vector.test(64) && bit_vector.test(99) && !bit_vector.test(3));
+
+        bit_vector.build_index();
+        assert_eq!(bit_vector.rank1(100), 2);
+        assert_eq!(bit_vector.select1(1), 99);
+        assert_eq!(bit_vector.select0(64), 65);
+    }
+
+    #[test]
+    fn test_rank_and_select_after_set_and_clear() {
+        let mut bit_vector = BitVector::new(100);
+        assert_eq!(bit_vector.num_ones(), 0);
+        bit_vector.set(3);
+        bit_vector.set(70);
+        assert_eq!(bit_vector.rank1(10), 1);
+        assert_eq!(bit_vector.num_ones(), 2);
+        bit_vector.clear(3);
+        assert_eq!(bit_vector.rank1(10), 0);
+        assert_eq!(bit_vector.select1(0), 70);
+        assert_eq!(bit_vector.select0(3), 3);
+    }
+}
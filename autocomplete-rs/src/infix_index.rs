@@ -0,0 +1,300 @@
+use std::cmp::Ordering;
+use crate::types::{global, IdType, ScoreType, ValueRange};
+use crate::unsorted_list::UnsortedList;
+
+/// Byte terminating every completion in the text of an `InfixIndex`
+const TERMINATOR: u8 = 0;
+
+/// Index answering which completions contain a query string, either at the
+/// start of one of their words (infix queries) or at any character position
+/// (substring queries).
+///
+/// The completions are concatenated, each followed by a terminator, and
+/// numbered by decreasing score, then by text and by id, so that doc id 0 is
+/// the best completion. Two suffix arrays list the starting positions of the
+/// words and of every character in lexicographic order of the suffixes they
+/// start, cut at the terminator. The suffixes prefixed by a query occupy a
+/// contiguous range of either array, whose best completions are found with
+/// range minimum queries over the doc ids of the suffixes.
+#[derive(Clone, Default)]
+pub struct InfixIndex {
+    text: Vec<u8>,
+    ids: Vec<IdType>,
+    word_suffixes: Vec<u32>,
+    word_docs: UnsortedList,
+    suffixes: Vec<u32>,
+    docs: UnsortedList,
+}
+
+impl InfixIndex {
+    /// Create an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the index of the given `(id, text, score)` completions. Text
+    /// containing the terminator byte is cut before it. Fails if the
+    /// concatenated completions do not fit in `u32::MAX` bytes.
+    pub fn build<'a, I>(completions: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = (IdType, &'a str, ScoreType)>,
+    {
+        let mut completions: Vec<(IdType, &str, ScoreType)> = completions
+            .into_iter()
+            .map(|(id, text, score)| (id, until_terminator(text), score))
+            .collect();
+        completions.sort_by(|a, b| {
+            b.2.total_cmp(&a.2).then_with(|| a.1.cmp(b.1)).then(a.0.cmp(&b.0))
+        });
+
+        // Suffixes are (position, end, doc id), the end being the position
+        // of the terminator of their completion
+        let mut text = Vec::new();
+        let mut word_suffixes = Vec::new();
+        let mut suffixes = Vec::new();
+        for (doc_id, (_, completion, _)) in completions.iter().enumerate() {
+            let end = u32::try_from(text.len() + completion.len()).map_err(|_| {
+                format!("infix index text exceeds {} bytes", u32::MAX)
+            })?;
+            let start = end - completion.len() as u32;
+            for (i, word_start) in suffix_starts(completion) {
+                let suffix = (start + i as u32, end, doc_id as IdType);
+                suffixes.push(suffix);
+                if word_start {
+                    word_suffixes.push(suffix);
+                }
+            }
+            text.extend_from_slice(completion.as_bytes());
+            text.push(TERMINATOR);
+        }
+
+        let slice = |&(pos, end, _): &(u32, u32, IdType)| &text[pos as usize..end as usize];
+        word_suffixes.sort_unstable_by(|a, b| slice(a).cmp(slice(b)));
+        suffixes.sort_unstable_by(|a, b| slice(a).cmp(slice(b)));
+        let docs_of = |suffixes: &[(u32, u32, IdType)]| {
+            UnsortedList::build(suffixes.iter().map(|&(_, _, doc_id)| doc_id).collect())
+        };
+        let positions_of = |suffixes: &[(u32, u32, IdType)]| -> Vec<u32> {
+            suffixes.iter().map(|&(pos, _, _)| pos).collect()
+        };
+        Ok(Self {
+            word_docs: docs_of(&word_suffixes),
+            docs: docs_of(&suffixes),
+            ids: completions.iter().map(|&(id, _, _)| id).collect(),
+            word_suffixes: positions_of(&word_suffixes),
+            suffixes: positions_of(&suffixes),
+            text,
+        })
+    }
+
+    /// Return the ids of the `k` best completions with a word starting with
+    /// `query`, best first
+    pub fn infix_topk(&self, query: &str, k: usize) -> Vec<IdType> {
+        self.infix_topk_filtered(query, k, &|_| true)
+    }
+
+    /// Return the ids of the `k` best completions with a word starting with
+    /// `query` whose ids are accepted by `filter`, best first
+    pub fn infix_topk_filtered(
+        &self,
+        query: &str,
+        k: usize,
+        filter: &dyn Fn(IdType) -> bool,
+    ) -> Vec<IdType> {
+        let range = self.locate(&self.word_suffixes, query);
+        self.topk(&self.word_docs, range, k, filter)
+    }
+
+    /// Return the ids of the `k` best completions containing `query` at any
+    /// position, best first
+    pub fn substring_topk(&self, query: &str, k: usize) -> Vec<IdType> {
+        self.substring_topk_filtered(query, k, &|_| true)
+    }
+
+    /// Return the ids of the `k` best completions containing `query` at any
+    /// position whose ids are accepted by `filter`, best first
+    pub fn substring_topk_filtered(
+        &self,
+        query: &str,
+        k: usize,
+        filter: &dyn Fn(IdType) -> bool,
+    ) -> Vec<IdType> {
+        let range = self.locate(&self.suffixes, query);
+        self.topk(&self.docs, range, k, filter)
+    }
+
+    /// Get the number of completions
+    pub fn size(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Check if the index has no completions
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        self.text.len()
+            + self.ids.len() * std::mem::size_of::<IdType>()
+            + (self.word_suffixes.len() + self.suffixes.len()) * std::mem::size_of::<u32>()
+            + self.word_docs.bytes()
+            + self.docs.bytes()
+    }
+
+    /// Get the inclusive range of `positions` whose suffixes start with
+    /// `query`, which is invalid if none does
+    fn locate(&self, positions: &[u32], query: &str) -> ValueRange {
+        let query = query.as_bytes();
+        if query.contains(&TERMINATOR) {
+            return global::INVALID_RANGE;
+        }
+        // Only the first `query.len()` bytes of each suffix are compared
+        let compare = |&pos: &u32| {
+            let pos = pos as usize;
+            let window = &self.text[pos..self.text.len().min(pos + query.len())];
+            let end = window.iter().position(|&b| b == TERMINATOR).unwrap_or(window.len());
+            window[..end].cmp(query)
+        };
+        let begin = positions.partition_point(|pos| compare(pos) == Ordering::Less);
+        let end = positions.partition_point(|pos| compare(pos) != Ordering::Greater);
+        if begin == end {
+            return global::INVALID_RANGE;
+        }
+        ValueRange {
+            begin: begin as u64,
+            end: (end - 1) as u64,
+        }
+    }
+
+    /// Return the ids of the `k` best distinct completions in `range` of
+    /// `docs` accepted by `filter`. A completion matching at several
+    /// positions appears several times in the range, and is reported once.
+    fn topk(
+        &self,
+        docs: &UnsortedList,
+        range: ValueRange,
+        k: usize,
+        filter: &dyn Fn(IdType) -> bool,
+    ) -> Vec<IdType> {
+        let mut last = None;
+        docs.topk_filtered(range, k, |doc_id| {
+            // Doc ids come in increasing order, so repeats are consecutive
+            let first = last != Some(doc_id);
+            last = Some(doc_id);
+            first && filter(self.ids[doc_id as usize])
+        })
+        .into_iter()
+        .map(|doc_id| self.ids[doc_id as usize])
+        .collect()
+    }
+}
+
+/// Check if a word of `text` starts with `query`, as matched by
+/// `infix_topk`
+pub fn infix_matches(text: &str, query: &str) -> bool {
+    suffix_matches(text, query, true)
+}
+
+/// Check if `text` contains `query`, as matched by `substring_topk`
+pub fn substring_matches(text: &str, query: &str) -> bool {
+    suffix_matches(text, query, false)
+}
+
+fn suffix_matches(text: &str, query: &str, words_only: bool) -> bool {
+    if query.as_bytes().contains(&TERMINATOR) {
+        return false;
+    }
+    let text = until_terminator(text);
+    suffix_starts(text)
+        .any(|(i, word_start)| (word_start || !words_only) && text[i..].starts_with(query))
+}
+
+/// Cut `text` before the first terminator byte
+fn until_terminator(text: &str) -> &str {
+    let end = text.bytes().position(|b| b == TERMINATOR).unwrap_or(text.len());
+    &text[..end]
+}
+
+/// Iterate over the byte positions of the characters of `text`, telling
+/// whether each starts a word
+fn suffix_starts(text: &str) -> impl Iterator<Item = (usize, bool)> + '_ {
+    let mut previous_space = true;
+    text.char_indices().map(move |(i, c)| {
+        let space = c.is_whitespace();
+        let word_start = previous_space && !space;
+        previous_space = space;
+        (i, word_start)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> InfixIndex {
+        InfixIndex::build([
+            (10, "iphone pro max", 0.9),
+            (11, "ipad pro", 0.7),
+            (12, "macbook pro", 1.0),
+            (13, "promax cable", 0.5),
+            (14, "airpods", 0.8),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_infix_topk() {
+        let index = sample();
+        assert_eq!(index.size(), 5);
+        assert_eq!(index.infix_topk("pro", 10), vec![12, 10, 11, 13]);
+        assert_eq!(index.infix_topk("pro max", 10), vec![10]);
+        assert_eq!(index.infix_topk("pro", 2), vec![12, 10]);
+        assert_eq!(index.infix_topk("pods", 10), Vec::<IdType>::new());
+        assert_eq!(index.infix_topk("", 10), vec![12, 10, 14, 11, 13]);
+    }
+
+    #[test]
+    fn test_substring_topk() {
+        let index = sample();
+        assert_eq!(index.substring_topk("pods", 10), vec![14]);
+        assert_eq!(index.substring_topk("o", 3), vec![12, 10, 14]);
+        assert_eq!(index.substring_topk("max", 10), vec![10, 13]);
+        assert!(index.substring_topk("pro\0", 10).is_empty());
+        assert!(InfixIndex::new().substring_topk("a", 10).is_empty());
+    }
+
+    #[test]
+    fn test_repeated_matches_are_reported_once() {
+        let index = InfixIndex::build([
+            (1, "la la la la land", 1.0),
+            (2, "lalaland", 0.9),
+            (3, "island", 0.5),
+        ])
+        .unwrap();
+        assert_eq!(index.substring_topk("la", 2), vec![1, 2]);
+        assert_eq!(index.substring_topk("la", 10), vec![1, 2, 3]);
+        assert_eq!(index.infix_topk("la", 10), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_match_predicates() {
+        let texts = ["iphone pro max", " pro", "la\0la", "", "ipad  pro"];
+        let completions = || texts.iter().enumerate().map(|(i, &text)| (i as IdType, text, 1.0));
+        let index = InfixIndex::build(completions()).unwrap();
+        for query in ["pro", "pro max", "ro", "", " ", "pad  p", "la", "a\0"] {
+            let sorted = |mut ids: Vec<IdType>| {
+                ids.sort_unstable();
+                ids
+            };
+            let matching = |matches: fn(&str, &str) -> bool| -> Vec<IdType> {
+                completions()
+                    .filter(|&(_, text, _)| matches(text, query))
+                    .map(|(id, _, _)| id)
+                    .collect()
+            };
+            assert_eq!(sorted(index.infix_topk(query, 10)), matching(infix_matches), "{:?}", query);
+            assert_eq!(sorted(index.substring_topk(query, 10)), matching(substring_matches));
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use crate::fc_dictionary::{read_varint, write_varint, DEFAULT_BUCKET_SIZE};
+use crate::index::CompactVector;
+use crate::types::{global, CompletionType, IdType, ValueRange};
+
+/// Dictionary of sorted completions, given as sequences of term ids,
+/// compressed with bucketed front coding.
+///
+/// Completions are grouped into buckets of `bucket_size + 1` consecutive
+/// completions. The first completion of a bucket (its header) is stored
+/// verbatim, and every following completion is stored as the number of
+/// leading term ids it shares with its predecessor plus the remaining term
+/// ids, all as variable-byte integers. Ids are lexicographic ranks starting
+/// from 0.
+#[derive(Debug, Clone)]
+pub struct IntegerFcDictionary {
+    bucket_size: usize,
+    size: usize,
+    header_offsets: CompactVector,
+    headers: Vec<IdType>,
+    bucket_offsets: CompactVector,
+    buckets: Vec<u8>,
+}
+
+impl IntegerFcDictionary {
+    /// Create a new empty dictionary
+    pub fn new() -> Self {
+        Self {
+            bucket_size: DEFAULT_BUCKET_SIZE,
+            size: 0,
+            header_offsets: CompactVector::build([0]),
+            headers: Vec::new(),
+            bucket_offsets: CompactVector::build([0]),
+            buckets: Vec::new(),
+        }
+    }
+
+    /// Build the dictionary from strictly increasing completions
+    pub fn build<I, C>(completions: I, bucket_size: usize) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = C>,
+        C: AsRef<[IdType]>,
+    {
+        if bucket_size == 0 {
+            return Err("bucket size must be > 0".to_string());
+        }
+
+        let mut dictionary = Self {
+            bucket_size,
+            ..Self::new()
+        };
+        let mut header_offsets = vec![0];
+        let mut bucket_offsets = vec![0];
+        let mut prev = CompletionType::new();
+        for c in completions {
+            let c = c.as_ref();
+            if dictionary.size > 0 && c <= prev.as_slice() {
+                return Err(format!(
+                    "completions must be sorted and unique, but {:?} follows {:?}",
+                    c, prev
+                ));
+            }
+
+            if dictionary.size.is_multiple_of(bucket_size + 1) {
+                if dictionary.size > 0 {
+                    bucket_offsets.push(dictionary.buckets.len() as u64);
+                }
+                dictionary.headers.extend_from_slice(c);
+                header_offsets.push(dictionary.headers.len() as u64);
+            } else {
+                let lcp = prev.iter().zip(c).take_while(|(x, y)| x == y).count();
+                write_varint(&mut dictionary.buckets, lcp);
+                write_varint(&mut dictionary.buckets, c.len() - lcp);
+                for &term_id in &c[lcp..] {
+                    write_varint(&mut dictionary.buckets, term_id as usize);
+                }
+            }
+
+            dictionary.size += 1;
+            prev.clear();
+            prev.extend_from_slice(c);
+        }
+        if dictionary.size > 0 {
+            bucket_offsets.push(dictionary.buckets.len() as u64);
+        }
+        dictionary.header_offsets = CompactVector::build(header_offsets);
+        dictionary.bucket_offsets = CompactVector::build(bucket_offsets);
+        Ok(dictionary)
+    }
+
+    /// Get the id of a completion
+    pub fn locate(&self, c: &[IdType]) -> Option<IdType> {
+        let pos = self.partition_point(|t| t < c);
+        match self.extract(pos as IdType) {
+            Some(t) if t == c => Some(pos as IdType),
+            _ => None,
+        }
+    }
+
+    /// Get the completion with the given id
+    pub fn extract(&self, id: IdType) -> Option<CompletionType> {
+        let pos = id as usize;
+        if pos >= self.size {
+            return None;
+        }
+        let bucket = pos / (self.bucket_size + 1);
+        self.bucket_completions(bucket).nth(pos % (self.bucket_size + 1))
+    }
+
+    /// Get the inclusive range of ids of the completions starting with the
+    /// term ids of `prefix` and continuing with a term id in `suffix_range`,
+    /// or `global::INVALID_RANGE` if there is none
+    pub fn locate_prefix(&self, prefix: &[IdType], suffix_range: ValueRange) -> ValueRange {
+        if suffix_range.is_invalid() {
+            return global::INVALID_RANGE;
+        }
+        let n = prefix.len();
+        let first = suffix_range.begin as IdType;
+        let last = suffix_range.end as IdType;
+        let before = |t: &[IdType]| -> bool {
+            let common = t.len().min(n);
+            match t[..common].cmp(&prefix[..common]) {
+                std::cmp::Ordering::Equal => t.len() <= n || t[n] < first,
+                ordering => ordering.is_lt(),
+            }
+        };
+        let not_after = |t: &[IdType]| -> bool {
+            let common = t.len().min(n);
+            match t[..common].cmp(&prefix[..common]) {
+                std::cmp::Ordering::Equal => t.len() <= n || t[n] <= last,
+                ordering => ordering.is_lt(),
+            }
+        };
+        let begin = self.partition_point(before);
+        let end = self.partition_point(not_after);
+        if begin == end {
+            return global::INVALID_RANGE;
+        }
+        ValueRange {
+            begin: begin as u64,
+            end: end as u64 - 1,
+        }
+    }
+
+    /// Iterate over the completions in lexicographic order
+    pub fn iter(&self) -> impl Iterator<Item = CompletionType> + '_ {
+        (0..self.num_buckets()).flat_map(move |bucket| self.bucket_completions(bucket))
+    }
+
+    /// Get the number of completions
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Check if the dictionary is empty
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Get the number of completions following each bucket header
+    pub fn bucket_size(&self) -> usize {
+        self.bucket_size
+    }
+
+    /// Get the number of buckets
+    pub fn num_buckets(&self) -> usize {
+        self.header_offsets.size() - 1
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.header_offsets.bytes()
+            + self.bucket_offsets.bytes()
+            + self.headers.len() * std::mem::size_of::<IdType>()
+            + self.buckets.len()
+    }
+
+    fn header(&self, bucket: usize) -> &[IdType] {
+        let begin = self.header_offsets.access(bucket) as usize;
+        let end = self.header_offsets.access(bucket + 1) as usize;
+        &self.headers[begin..end]
+    }
+
+    /// Decode the completions of a bucket, header first
+    fn bucket_completions(&self, bucket: usize) -> impl Iterator<Item = CompletionType> + '_ {
+        let mut curr = self.header(bucket).to_vec();
+        let mut pos = self.bucket_offsets.access(bucket) as usize;
+        let end = self.bucket_offsets.access(bucket + 1) as usize;
+        let mut header = true;
+        std::iter::from_fn(move || {
+            if header {
+                header = false;
+                return Some(curr.clone());
+            }
+            if pos == end {
+                return None;
+            }
+            let lcp = read_varint(&self.buckets, &mut pos);
+            let suffix_len = read_varint(&self.buckets, &mut pos);
+            curr.truncate(lcp);
+            for _ in 0..suffix_len {
+                curr.push(read_varint(&self.buckets, &mut pos) as IdType);
+            }
+            Some(curr.clone())
+        })
+    }
+
+    /// Get the number of completions for which `pred` holds, given that it
+    /// holds for a prefix of the completions in lexicographic order
+    fn partition_point<P>(&self, pred: P) -> usize
+    where
+        P: Fn(&[IdType]) -> bool,
+    {
+        // Find the last bucket whose header satisfies the predicate
+        let (mut lo, mut hi) = (0, self.num_buckets());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if pred(self.header(mid)) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        if lo == 0 {
+            return 0;
+        }
+        let bucket = lo - 1;
+        let in_bucket = self
+            .bucket_completions(bucket)
+            .take_while(|c| pred(c))
+            .count();
+        bucket * (self.bucket_size + 1) + in_bucket
+    }
+}
+
+impl Default for IntegerFcDictionary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completions() -> Vec<CompletionType> {
+        vec![
+            vec![1],
+            vec![1, 4],
+            vec![1, 4, 2],
+            vec![1, 5],
+            vec![1, 5, 3, 300],
+            vec![1, 7],
+            vec![2, 4],
+            vec![3],
+            vec![3, 1, 1],
+            vec![200, 1],
+        ]
+    }
+
+    fn range(begin: u64, end: u64) -> ValueRange {
+        ValueRange { begin, end }
+    }
+
+    #[test]
+    fn test_locate_and_extract() {
+        let completions = completions();
+        for bucket_size in [1, 2, 3, 16] {
+            let dictionary = IntegerFcDictionary::build(&completions, bucket_size).unwrap();
+            assert_eq!(dictionary.size(), completions.len());
+            for (i, c) in completions.iter().enumerate() {
+                assert_eq!(dictionary.locate(c), Some(i as IdType));
+                assert_eq!(dictionary.extract(i as IdType).as_ref(), Some(c));
+            }
+            assert_eq!(dictionary.locate(&[1, 6]), None);
+            assert_eq!(dictionary.locate(&[]), None);
+            assert_eq!(dictionary.extract(completions.len() as IdType), None);
+            assert_eq!(dictionary.iter().collect::<Vec<_>>(), completions);
+        }
+    }
+
+    #[test]
+    fn test_locate_prefix() {
+        let completions = completions();
+        for bucket_size in [1, 2, 3, 16] {
+            let dictionary = IntegerFcDictionary::build(&completions, bucket_size).unwrap();
+            assert_eq!(dictionary.locate_prefix(&[1], range(4, 5)), range(1, 4));
+            assert_eq!(dictionary.locate_prefix(&[1], range(5, 5)), range(3, 4));
+            assert_eq!(dictionary.locate_prefix(&[1], range(1, 9)), range(1, 5));
+            assert_eq!(dictionary.locate_prefix(&[1, 5], range(3, 3)), range(4, 4));
+            assert_eq!(dictionary.locate_prefix(&[], range(1, 3)), range(0, 8));
+            assert_eq!(dictionary.locate_prefix(&[], range(3, 300)), range(7, 9));
+            assert!(dictionary.locate_prefix(&[1], range(6, 6)).is_invalid());
+            assert!(dictionary.locate_prefix(&[4], range(1, 9)).is_invalid());
+            assert!(dictionary.locate_prefix(&[1], global::INVALID_RANGE).is_invalid());
+        }
+    }
+
+    #[test]
+    fn test_build_errors() {
+        assert!(IntegerFcDictionary::build([vec![2], vec![1]], 4).is_err());
+        assert!(IntegerFcDictionary::build([vec![1], vec![1]], 4).is_err());
+        assert!(IntegerFcDictionary::build([vec![1]], 0).is_err());
+        assert!(IntegerFcDictionary::build(Vec::<CompletionType>::new(), 4)
+            .unwrap()
+            .is_empty());
+    }
+}
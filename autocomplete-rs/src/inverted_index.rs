@@ -0,0 +1,387 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use crate::ef::EfSequence;
+use crate::types::{IdType, ValueRange};
+
+/// Cursor over doc ids in increasing order that can skip ahead
+pub trait DocCursor: Iterator<Item = IdType> {
+    /// Move to the first remaining doc that is `>= x` and return it,
+    /// skipping every doc before it. The doc is returned again by `next`.
+    fn next_geq(&mut self, x: IdType) -> Option<IdType>;
+}
+
+/// Inverted index mapping each term to the sorted list of the docs
+/// containing it, compressed with Elias-Fano.
+///
+/// The lists are concatenated into a single sequence, in which the doc
+/// `d` of the term `t` is stored as `(t - 1) * num_docs + d`, so that the
+/// whole index is increasing. A second sequence holds the offset of each
+/// list. Term ids start from 1, as in the term dictionary.
+///
+/// Lists are read through cursors supporting `next_geq`, which lets an
+/// `IntersectionIterator` skip over the docs of long lists that cannot be in
+/// the intersection.
+#[derive(Debug, Clone)]
+pub struct InvertedIndex {
+    num_docs: usize,
+    postings: EfSequence,
+    offsets: EfSequence,
+}
+
+impl InvertedIndex {
+    /// Create a new empty index
+    pub fn new() -> Self {
+        Self {
+            num_docs: 0,
+            postings: EfSequence::new(),
+            offsets: EfSequence::build([0]).expect("a single value is sorted"),
+        }
+    }
+
+    /// Build the index from a forward index, listing the ids (from 1 to
+    /// `num_terms`) of the terms of each doc
+    pub fn build<I, T>(forward: I, num_terms: usize) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[IdType]>,
+    {
+        let mut postings: Vec<Vec<u64>> = vec![Vec::new(); num_terms];
+        let mut num_docs = 0;
+        for (doc_id, terms) in forward.into_iter().enumerate() {
+            for &term_id in terms.as_ref() {
+                if term_id == 0 || term_id as usize > num_terms {
+                    return Err(format!("term id {} out of range 1..={}", term_id, num_terms));
+                }
+                let list = &mut postings[term_id as usize - 1];
+                if list.last() != Some(&(doc_id as u64)) {
+                    list.push(doc_id as u64);
+                }
+            }
+            num_docs += 1;
+        }
+
+        let mut offsets = vec![0];
+        let mut concatenated = Vec::new();
+        for (i, list) in postings.iter().enumerate() {
+            let base = i as u64 * num_docs as u64;
+            concatenated.extend(list.iter().map(|&doc_id| base + doc_id));
+            offsets.push(concatenated.len() as u64);
+        }
+        Ok(Self {
+            num_docs,
+            postings: EfSequence::build(concatenated)?,
+            offsets: EfSequence::build(offsets)?,
+        })
+    }
+
+    /// Get a cursor over the docs containing `term_id`, or `None` if the
+    /// term is not indexed
+    pub fn iterator(&self, term_id: IdType) -> Option<PostingCursor<'_>> {
+        let i = (term_id as usize).checked_sub(1)?;
+        if i >= self.num_terms() {
+            return None;
+        }
+        let begin = self.offsets.access(i) as usize;
+        Some(PostingCursor {
+            postings: &self.postings,
+            base: i as u64 * self.num_docs as u64,
+            begin,
+            pos: begin,
+            end: self.offsets.access(i + 1) as usize,
+        })
+    }
+
+    /// Get a cursor over the docs containing any term in the inclusive
+    /// `range` of term ids
+    pub fn union(&self, range: ValueRange) -> UnionCursor<'_> {
+        let cursors = if range.is_invalid() {
+            Vec::new()
+        } else {
+            (range.begin..=range.end)
+                .filter_map(|term_id| self.iterator(term_id as IdType))
+                .collect()
+        };
+        UnionCursor::new(cursors)
+    }
+
+    /// Get an iterator over the docs containing every term of `term_ids`,
+    /// in increasing order
+    pub fn intersection(&self, term_ids: &[IdType]) -> IntersectionIterator<PostingCursor<'_>> {
+        let cursors: Option<Vec<_>> = term_ids.iter().map(|&t| self.iterator(t)).collect();
+        let mut cursors = cursors.unwrap_or_default();
+        cursors.sort_by_key(PostingCursor::size);
+        IntersectionIterator::new(cursors)
+    }
+
+    /// Get the number of terms
+    pub fn num_terms(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Get the number of docs
+    pub fn num_docs(&self) -> usize {
+        self.num_docs
+    }
+
+    /// Get the total length of the posting lists
+    pub fn num_postings(&self) -> usize {
+        self.postings.len()
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        std::mem::size_of_val(&self.num_docs) + self.postings.bytes() + self.offsets.bytes()
+    }
+}
+
+impl Default for InvertedIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cursor over a posting list, in increasing order
+#[derive(Debug, Clone)]
+pub struct PostingCursor<'a> {
+    postings: &'a EfSequence,
+    /// Value added to the docs of the list in `postings`
+    base: u64,
+    /// Range of the list in `postings`
+    begin: usize,
+    pos: usize,
+    end: usize,
+}
+
+impl PostingCursor<'_> {
+    /// Get the length of the posting list
+    pub fn size(&self) -> usize {
+        self.end - self.begin
+    }
+}
+
+impl DocCursor for PostingCursor<'_> {
+    fn next_geq(&mut self, x: IdType) -> Option<IdType> {
+        if self.pos >= self.end {
+            return None;
+        }
+        let current = self.postings.access(self.pos) - self.base;
+        if current >= x as u64 {
+            return Some(current as IdType);
+        }
+        match self.postings.next_geq(self.base + x as u64) {
+            Some((pos, value)) if pos < self.end => {
+                self.pos = pos;
+                Some((value - self.base) as IdType)
+            }
+            _ => {
+                self.pos = self.end;
+                None
+            }
+        }
+    }
+}
+
+impl Iterator for PostingCursor<'_> {
+    type Item = IdType;
+
+    fn next(&mut self) -> Option<IdType> {
+        if self.pos >= self.end {
+            return None;
+        }
+        let doc_id = (self.postings.access(self.pos) - self.base) as IdType;
+        self.pos += 1;
+        Some(doc_id)
+    }
+}
+
+/// Cursor over the docs of several posting lists, in increasing order and
+/// without repeats
+#[derive(Debug, Clone)]
+pub struct UnionCursor<'a> {
+    cursors: Vec<PostingCursor<'a>>,
+    /// Current doc of each cursor that has one, smallest first
+    queue: BinaryHeap<Reverse<(IdType, usize)>>,
+}
+
+impl<'a> UnionCursor<'a> {
+    fn new(mut cursors: Vec<PostingCursor<'a>>) -> Self {
+        let queue = cursors
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, cursor)| cursor.next_geq(0).map(|doc_id| Reverse((doc_id, i))))
+            .collect();
+        Self { cursors, queue }
+    }
+
+    /// Get the total length of the posting lists
+    pub fn size(&self) -> usize {
+        self.cursors.iter().map(PostingCursor::size).sum()
+    }
+}
+
+impl DocCursor for UnionCursor<'_> {
+    fn next_geq(&mut self, x: IdType) -> Option<IdType> {
+        while let Some(&Reverse((doc_id, i))) = self.queue.peek() {
+            if doc_id >= x {
+                return Some(doc_id);
+            }
+            self.queue.pop();
+            if let Some(next) = self.cursors[i].next_geq(x) {
+                self.queue.push(Reverse((next, i)));
+            }
+        }
+        None
+    }
+}
+
+impl Iterator for UnionCursor<'_> {
+    type Item = IdType;
+
+    fn next(&mut self) -> Option<IdType> {
+        let Reverse((doc_id, _)) = *self.queue.peek()?;
+        // Move every cursor on `doc_id` past it
+        while let Some(&Reverse((current, i))) = self.queue.peek() {
+            if current != doc_id {
+                break;
+            }
+            self.queue.pop();
+            if let Some(next) = self.cursors[i].next_geq(doc_id + 1) {
+                self.queue.push(Reverse((next, i)));
+            }
+        }
+        Some(doc_id)
+    }
+}
+
+/// Iterator over the docs common to several cursors
+pub struct IntersectionIterator<C> {
+    /// Cursors from the shortest list to the longest
+    cursors: Vec<C>,
+}
+
+impl<C: DocCursor> IntersectionIterator<C> {
+    /// Intersect `cursors`, which should go from the shortest to the longest
+    pub fn new(cursors: Vec<C>) -> Self {
+        Self { cursors }
+    }
+}
+
+impl<C: DocCursor> Iterator for IntersectionIterator<C> {
+    type Item = IdType;
+
+    fn next(&mut self) -> Option<IdType> {
+        let (shortest, others) = self.cursors.split_first_mut()?;
+        let mut candidate = shortest.next()?;
+        let mut i = 0;
+        while i < others.len() {
+            let doc_id = others[i].next_geq(candidate)?;
+            if doc_id == candidate {
+                i += 1;
+            } else {
+                // Restart from the next doc of the shortest list
+                candidate = shortest.next_geq(doc_id)?;
+                shortest.next();
+                i = 0;
+            }
+        }
+        Some(candidate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forward() -> Vec<Vec<IdType>> {
+        (0..500u32)
+            .map(|doc| vec![1, doc % 3 + 2, doc % 5 + 5, doc % 7 + 10])
+            .collect()
+    }
+
+    fn docs_with(forward: &[Vec<IdType>], term_ids: &[IdType]) -> Vec<IdType> {
+        (0..forward.len() as IdType)
+            .filter(|&doc| term_ids.iter().all(|t| forward[doc as usize].contains(t)))
+            .collect()
+    }
+
+    #[test]
+    fn test_posting_cursor() {
+        let forward = forward();
+        let index = InvertedIndex::build(&forward, 17).unwrap();
+        assert_eq!(index.num_docs(), 500);
+        assert_eq!(index.num_postings(), 2000);
+
+        let mut cursor = index.iterator(3).unwrap();
+        assert_eq!(cursor.size(), docs_with(&forward, &[3]).len());
+        assert_eq!(cursor.next(), Some(1));
+        assert_eq!(cursor.next_geq(100), Some(100));
+        assert_eq!(cursor.next_geq(50), Some(100));
+        assert_eq!(cursor.next(), Some(100));
+        assert_eq!(cursor.next(), Some(103));
+        assert_eq!(cursor.next_geq(1000), None);
+        assert_eq!(cursor.next(), None);
+
+        assert_eq!(index.iterator(1).unwrap().count(), 500);
+        assert!(index.iterator(17).unwrap().next().is_none());
+        assert!(index.iterator(0).is_none());
+        assert!(index.iterator(18).is_none());
+    }
+
+    #[test]
+    fn test_intersection() {
+        let forward = forward();
+        let index = InvertedIndex::build(&forward, 17).unwrap();
+        for term_ids in [&[2, 5][..], &[3, 6, 12], &[1, 4], &[12], &[2, 3], &[1, 17]] {
+            assert_eq!(
+                index.intersection(term_ids).collect::<Vec<_>>(),
+                docs_with(&forward, term_ids),
+                "terms {:?}", term_ids
+            );
+        }
+        assert_eq!(index.intersection(&[]).next(), None);
+        assert_eq!(index.intersection(&[2, 99]).next(), None);
+    }
+
+    #[test]
+    fn test_union() {
+        let forward = forward();
+        let index = InvertedIndex::build(&forward, 17).unwrap();
+        let docs_with_any = |begin: IdType, end: IdType| -> Vec<IdType> {
+            (0..forward.len() as IdType)
+                .filter(|&doc| forward[doc as usize].iter().any(|t| (begin..=end).contains(t)))
+                .collect()
+        };
+        for (begin, end) in [(3, 5), (10, 16), (2, 2), (16, 17)] {
+            let union = index.union(range(begin as u64, end as u64));
+            assert_eq!(union.collect::<Vec<_>>(), docs_with_any(begin, end));
+        }
+
+        let expected = docs_with_any(3, 5);
+        let mut union = index.union(range(3, 5));
+        assert_eq!(union.size(), 167 + 166 + 100);
+        let first = expected.iter().copied().find(|&d| d >= 6);
+        assert_eq!(union.next_geq(6), first);
+        assert_eq!(union.next(), first);
+        assert_eq!(union.next(), expected.iter().copied().find(|&d| d > first.unwrap()));
+        assert_eq!(union.next_geq(500), None);
+        assert_eq!(index.union(crate::types::global::INVALID_RANGE).next(), None);
+
+        // Docs with term 12 and a term in 3..=5
+        let cursors = vec![index.union(range(12, 12)), index.union(range(3, 5))];
+        let with_12: Vec<IdType> = docs_with(&forward, &[12])
+            .into_iter()
+            .filter(|doc| expected.contains(doc))
+            .collect();
+        assert_eq!(IntersectionIterator::new(cursors).collect::<Vec<_>>(), with_12);
+    }
+
+    fn range(begin: u64, end: u64) -> ValueRange {
+        ValueRange { begin, end }
+    }
+
+    #[test]
+    fn test_build_errors() {
+        assert!(InvertedIndex::build([vec![1, 3]], 2).is_err());
+        assert!(InvertedIndex::build([vec![0]], 2).is_err());
+    }
+}
@@ -1,11 +1,26 @@
 pub mod constants;
 pub mod parameters;
 pub mod probe;
+pub mod limits;
 pub mod types;
 pub mod string_pool;
+pub mod normalizer;
 pub mod trie;
 pub mod dictionary;
+pub mod fc_dictionary;
+pub mod integer_fc_dictionary;
+pub mod completion_trie;
+pub mod term_dictionary;
+pub mod term_index;
+pub mod ef;
 pub mod index;
+pub mod compact_forward_index;
+pub mod inverted_index;
+pub mod succinct_rmq;
+pub mod unsorted_list;
+pub mod infix_index;
+pub mod tag_index;
+pub mod topk_index;
 pub mod autocomplete;
 pub mod graphql;
 pub mod server;
@@ -13,9 +28,24 @@ pub mod server;
 pub use constants::*;
 pub use parameters::*;
 pub use probe::*;
+pub use limits::*;
 pub use types::*;
 pub use string_pool::*;
+pub use normalizer::*;
 pub use trie::*;
 pub use dictionary::*;
+pub use fc_dictionary::*;
+pub use integer_fc_dictionary::*;
+pub use completion_trie::*;
+pub use term_dictionary::*;
+pub use term_index::*;
+pub use ef::*;
 pub use index::*;
+pub use compact_forward_index::*;
+pub use inverted_index::*;
+pub use succinct_rmq::*;
+pub use unsorted_list::*;
+pub use infix_index::*;
+pub use tag_index::*;
+pub use topk_index::*;
 pub use autocomplete::*; 
\ No newline at end of file
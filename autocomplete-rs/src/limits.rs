@@ -0,0 +1,87 @@
+use crate::constants::{DEFAULT_K, DEFAULT_MAX_EDITS, MAX_EDITS, MAX_K, MAX_RESULTS_CEILING};
+use crate::types::ScoreType;
+
+/// Bounds on the number of completions a single request may ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResultLimit {
+    default_k: u32,
+    max_k: u32,
+}
+
+impl ResultLimit {
+    /// Create a limit that clamps requests to `max_k` results
+    pub fn new(max_k: u32) -> Result<Self, String> {
+        if max_k == 0 || max_k > MAX_RESULTS_CEILING {
+            return Err(format!(
+                "max_k ({}) must be between 1 and {}",
+                max_k, MAX_RESULTS_CEILING
+            ));
+        }
+        Ok(Self {
+            default_k: DEFAULT_K.min(max_k),
+            max_k,
+        })
+    }
+
+    /// Number of results returned when a request does not specify one
+    pub fn default_k(&self) -> u32 {
+        self.default_k
+    }
+
+    /// Largest number of results returned for a single request
+    pub fn max_k(&self) -> u32 {
+        self.max_k
+    }
+
+    /// Resolve a requested number of results into the `k` to search for.
+    ///
+    /// A missing or zero request falls back to the default, requests above
+    /// the ceiling are clamped to it, and negative requests or requests above
+    /// `MAX_RESULTS_CEILING` are rejected.
+    pub fn resolve(&self, requested: Option<i32>) -> Result<usize, String> {
+        match requested {
+            None | Some(0) => Ok(self.default_k as usize),
+            Some(n) if n < 0 => Err(format!("max_results must not be negative, got {}", n)),
+            Some(n) if n as u32 > MAX_RESULTS_CEILING => Err(format!(
+                "max_results ({}) exceeds the maximum of {}",
+                n, MAX_RESULTS_CEILING
+            )),
+            Some(n) => Ok((n as u32).min(self.max_k) as usize),
+        }
+    }
+}
+
+impl Default for ResultLimit {
+    fn default() -> Self {
+        Self {
+            default_k: DEFAULT_K.min(MAX_K),
+            max_k: MAX_K,
+        }
+    }
+}
+
+/// Resolve a requested number of edits for fuzzy completion.
+///
+/// A missing request falls back to `DEFAULT_MAX_EDITS`, and negative
+/// requests or requests above `MAX_EDITS` are rejected.
+pub fn resolve_max_edits(requested: Option<i32>) -> Result<u32, String> {
+    match requested {
+        None => Ok(DEFAULT_MAX_EDITS),
+        Some(n) if n < 0 => Err(format!("max_edits must not be negative, got {}", n)),
+        Some(n) if n as u32 > MAX_EDITS => Err(format!(
+            "max_edits ({}) exceeds the maximum of {}",
+            n, MAX_EDITS
+        )),
+        Some(n) => Ok(n as u32),
+    }
+}
+
+/// Check the score of a completion. NaN and infinite scores are rejected, as
+/// they cannot be ranked against the others.
+pub fn check_score(score: ScoreType) -> Result<ScoreType, String> {
+    if score.is_finite() {
+        Ok(score)
+    } else {
+        Err(format!("score must be finite, got {}", score))
+    }
+}
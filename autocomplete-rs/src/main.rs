@@ -1,12 +1,12 @@
 use std::error::Error;
 use clap::Parser;
 
-mod autocomplete;
-mod graphql;
-mod server;
-mod string_pool;
-mod trie;
-mod types;
+use autocomplete_rs::autocomplete::IndexConfig;
+use autocomplete_rs::constants::MAX_K;
+use autocomplete_rs::limits::ResultLimit;
+use autocomplete_rs::normalizer::NormalizerChain;
+use autocomplete_rs::server;
+use autocomplete_rs::topk_index::IndexType;
 
 /// Autocomplete service with gRPC and GraphQL support
 #[derive(Parser, Debug)]
@@ -19,19 +19,60 @@ struct Args {
     /// GraphQL server address
     #[arg(short, long, default_value = "[::1]:8000")]
     graphql_addr: String,
+
+    /// Maximum number of completions returned per request
+    #[arg(long, default_value_t = MAX_K)]
+    max_results: u32,
+
+    /// Index answering term-level queries: trie, inverted or blocked
+    #[arg(long, default_value_t = IndexType::Blocked)]
+    index_type: IndexType,
+
+    /// Drop the character trie of the completions to save memory. Prefix
+    /// queries are then matched term by term, and fuzzy queries rejected.
+    #[arg(long)]
+    no_trie: bool,
+
+    /// Keep suffix arrays of the completions to answer infix and substring
+    /// queries
+    #[arg(long)]
+    infix: bool,
+
+    /// Normalizers applied to completions and queries: none, standard, or a
+    /// comma-separated list such as nfkc,case-fold,strip-diacritics
+    #[arg(long, default_value_t = NormalizerChain::new())]
+    normalizer: NormalizerChain,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
+    let result_limit = ResultLimit::new(args.max_results)?;
 
     println!("Starting Autocomplete Service...");
     println!("gRPC server will listen on: {}", args.grpc_addr);
     println!("GraphQL server will listen on: {}", args.graphql_addr);
     println!("GraphQL Playground available at: http://{}/playground", args.graphql_addr);
+    println!("Completions per request are capped at: {}", result_limit.max_k());
+    println!("Term-level queries are answered by the {} index", args.index_type);
+    let enabled = |on: bool| if on { "enabled" } else { "disabled" };
+    println!("Character trie for prefix and fuzzy queries is {}", enabled(!args.no_trie));
+    println!("Infix and substring queries are {}", enabled(args.infix));
+    println!("Completions and queries are normalized with: {}", args.normalizer);
 
     // Start both servers
-    server::run_server(&args.grpc_addr, &args.graphql_addr).await?;
+    server::run_server(
+        &args.grpc_addr,
+        &args.graphql_addr,
+        result_limit,
+        IndexConfig {
+            index_type: args.index_type,
+            trie: !args.no_trie,
+            infix: args.infix,
+        },
+        args.normalizer,
+    )
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,245 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Text transformation applied to completions when they are indexed and to
+/// queries before they are matched
+pub trait Normalizer: Send + Sync {
+    /// Get the normalized form of `text`
+    fn normalize(&self, text: &str) -> String;
+
+    /// Get the name of the normalizer, as accepted by `NormalizerChain`'s
+    /// `FromStr` implementation
+    fn name(&self) -> &'static str;
+}
+
+/// Lower-case every character
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaseFold;
+
+impl Normalizer for CaseFold {
+    fn normalize(&self, text: &str) -> String {
+        text.to_lowercase()
+    }
+
+    fn name(&self) -> &'static str {
+        "case-fold"
+    }
+}
+
+/// Apply Unicode compatibility composition (NFKC), so that equivalent
+/// sequences such as ligatures or full-width letters compare equal
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Nfkc;
+
+impl Normalizer for Nfkc {
+    fn normalize(&self, text: &str) -> String {
+        text.nfkc().collect()
+    }
+
+    fn name(&self) -> &'static str {
+        "nfkc"
+    }
+}
+
+/// Remove accents and other combining marks, turning "héllo" into "hello"
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripDiacritics;
+
+impl Normalizer for StripDiacritics {
+    fn normalize(&self, text: &str) -> String {
+        text.nfd().filter(|&c| !is_combining_mark(c)).nfc().collect()
+    }
+
+    fn name(&self) -> &'static str {
+        "strip-diacritics"
+    }
+}
+
+/// Trim leading and trailing whitespace and replace every other run of
+/// whitespace with a single space
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CollapseWhitespace;
+
+impl Normalizer for CollapseWhitespace {
+    fn normalize(&self, text: &str) -> String {
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn name(&self) -> &'static str {
+        "collapse-whitespace"
+    }
+}
+
+/// Replace every character that is neither alphanumeric nor whitespace with
+/// a space, so that "new-york" splits into the terms "new" and "york"
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PunctuationToSpace;
+
+impl Normalizer for PunctuationToSpace {
+    fn normalize(&self, text: &str) -> String {
+        text.chars()
+            .map(|c| if c.is_alphanumeric() || c.is_whitespace() { c } else { ' ' })
+            .collect()
+    }
+
+    fn name(&self) -> &'static str {
+        "punctuation-to-space"
+    }
+}
+
+/// Remove every character that is neither alphanumeric nor whitespace, so
+/// that "rock'n'roll" becomes "rocknroll"
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RemovePunctuation;
+
+impl Normalizer for RemovePunctuation {
+    fn normalize(&self, text: &str) -> String {
+        text.chars()
+            .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+            .collect()
+    }
+
+    fn name(&self) -> &'static str {
+        "remove-punctuation"
+    }
+}
+
+/// Sequence of normalizers applied in order. The empty chain leaves text
+/// unchanged.
+///
+/// A chain is parsed from the comma-separated names of its steps, for
+/// example "nfkc,case-fold,strip-diacritics".
+#[derive(Clone, Default)]
+pub struct NormalizerChain {
+    steps: Vec<Arc<dyn Normalizer>>,
+}
+
+impl NormalizerChain {
+    /// Create a chain leaving text unchanged
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the chain applying NFKC, lower-casing, diacritic stripping,
+    /// punctuation splitting and whitespace collapsing, in this order
+    pub fn standard() -> Self {
+        Self::new()
+            .with(Nfkc)
+            .with(CaseFold)
+            .with(StripDiacritics)
+            .with(PunctuationToSpace)
+            .with(CollapseWhitespace)
+    }
+
+    /// Append a step to the chain
+    pub fn with<N: Normalizer + 'static>(mut self, normalizer: N) -> Self {
+        self.steps.push(Arc::new(normalizer));
+        self
+    }
+
+    /// Check if the chain leaves text unchanged
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Get the number of steps
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+}
+
+impl Normalizer for NormalizerChain {
+    fn normalize(&self, text: &str) -> String {
+        self.steps
+            .iter()
+            .fold(text.to_string(), |text, step| step.normalize(&text))
+    }
+
+    fn name(&self) -> &'static str {
+        "chain"
+    }
+}
+
+impl fmt::Debug for NormalizerChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.steps.iter().map(|step| step.name()))
+            .finish()
+    }
+}
+
+impl fmt::Display for NormalizerChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("none");
+        }
+        let names: Vec<&str> = self.steps.iter().map(|step| step.name()).collect();
+        f.write_str(&names.join(","))
+    }
+}
+
+impl FromStr for NormalizerChain {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "" | "none" => return Ok(Self::new()),
+            "standard" => return Ok(Self::standard()),
+            _ => {}
+        }
+        s.split(',').try_fold(Self::new(), |chain, name| match name.trim() {
+            "nfkc" => Ok(chain.with(Nfkc)),
+            "case-fold" => Ok(chain.with(CaseFold)),
+            "strip-diacritics" => Ok(chain.with(StripDiacritics)),
+            "collapse-whitespace" => Ok(chain.with(CollapseWhitespace)),
+            "punctuation-to-space" => Ok(chain.with(PunctuationToSpace)),
+            "remove-punctuation" => Ok(chain.with(RemovePunctuation)),
+            other => Err(format!(
+                "unknown normalizer {:?}, expected one of nfkc, case-fold, \
+                 strip-diacritics, collapse-whitespace, punctuation-to-space, \
+                 remove-punctuation",
+                other
+            )),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_normalizers() {
+        assert_eq!(CaseFold.normalize("HeLLo"), "hello");
+        assert_eq!(Nfkc.normalize("ﬁle ＡＢ"), "file AB");
+        assert_eq!(StripDiacritics.normalize("héllo Çà"), "hello Ca");
+        assert_eq!(CollapseWhitespace.normalize("  new \t york  "), "new york");
+        assert_eq!(PunctuationToSpace.normalize("new-york!"), "new york ");
+        assert_eq!(RemovePunctuation.normalize("rock'n'roll!"), "rocknroll");
+    }
+
+    #[test]
+    fn test_chain() {
+        let chain = NormalizerChain::standard();
+        assert_eq!(chain.normalize("  Héllo,  WORLD! "), "hello world");
+        assert_eq!(chain.normalize("Ｃａｆé-Ｎｏｉｒ"), "cafe noir");
+        assert_eq!(NormalizerChain::new().normalize(" Héllo "), " Héllo ");
+    }
+
+    #[test]
+    fn test_chain_from_str() {
+        let chain: NormalizerChain = "case-fold, strip-diacritics".parse().unwrap();
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain.to_string(), "case-fold,strip-diacritics");
+        assert_eq!(chain.normalize("HÉLLO"), "hello");
+
+        assert!("none".parse::<NormalizerChain>().unwrap().is_empty());
+        assert_eq!("standard".parse::<NormalizerChain>().unwrap().len(), 5);
+        assert!("case-fold,soundex".parse::<NormalizerChain>().is_err());
+        let standard = NormalizerChain::standard().to_string();
+        assert_eq!(standard.parse::<NormalizerChain>().unwrap().to_string(), standard);
+    }
+}
@@ -3,6 +3,7 @@ use std::io::{self, BufRead, BufReader};
 use std::path::Path;
 
 use crate::constants::{MAX_NUM_CHARS_PER_QUERY, MAX_NUM_TERMS_PER_QUERY};
+use crate::types::IdType;
 
 /// Parameters for the autocomplete system
 #[derive(Debug, Default)]
@@ -22,12 +23,45 @@ impl Parameters {
         Self::default()
     }
 
+    /// Computes the parameters of a collection of `num_terms` terms given as
+    /// term-id sequences in lexicographic order, as the statistics file
+    /// would record them. The string length is left unknown, at 0.
+    pub fn from_completions<I, C>(completions: I, num_terms: usize) -> Self
+    where
+        I: IntoIterator<Item = C>,
+        C: AsRef<[IdType]>,
+    {
+        let mut params = Self {
+            num_terms: num_terms as u32,
+            ..Self::default()
+        };
+        let mut prev: Vec<IdType> = Vec::new();
+        for completion in completions {
+            let completion = completion.as_ref();
+            if params.nodes_per_level.len() < completion.len() {
+                params.nodes_per_level.resize(completion.len(), 0);
+            }
+            // Each term after the prefix shared with the previous completion
+            // starts a new node
+            let lcp = prev.iter().zip(completion).take_while(|(x, y)| x == y).count();
+            for count in &mut params.nodes_per_level[lcp..completion.len()] {
+                *count += 1;
+            }
+            params.num_completions += 1;
+            prev.clear();
+            prev.extend_from_slice(completion);
+        }
+        params.universe = params.num_completions;
+        params.num_levels = params.nodes_per_level.len() as u32;
+        params
+    }
+
     /// Loads parameters from a statistics file
     pub fn load(&mut self) -> io::Result<()> {
         let stats_path = if self.collection_basename.ends_with(".mapped.stats") {
             Path::new(&self.collection_basename).to_path_buf()
         } else {
-            Path::new(&self.collection_basename).with_extension("mapped.stats")
+            Path::new(&format!("{}.mapped.stats", self.collection_basename)).to_path_buf()
         };
         
         let file = File::open(stats_path)?;
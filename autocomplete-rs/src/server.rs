@@ -6,10 +6,15 @@ use axum::{
     response::IntoResponse,
 };
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
-use crate::autocomplete::Autocomplete;
+use crate::autocomplete::{self as core, Autocomplete, CompletionEntry, IndexConfig};
+use crate::dictionary;
+use crate::tag_index;
+use crate::limits::{check_score, resolve_max_edits, ResultLimit};
+use crate::normalizer::NormalizerChain;
 use crate::graphql::{create_schema, AppSchema};
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 use hyper::Server;
 
 pub mod autocomplete_proto {
@@ -18,14 +23,219 @@ pub mod autocomplete_proto {
 
 use autocomplete_proto::{
     autocomplete_service_server::{AutocompleteService, AutocompleteServiceServer},
-    CompleteRequest, CompleteResponse, Completion,
-    InitRequest, InitResponse,
+    CompleteRequest, CompleteResponse, Completion, CompletionMode, ResultOrder,
+    InitMode, InitRequest, InitResponse, Payload, TagFilter, StringScore,
+    AddRequest, UpdateScoreRequest, RemoveRequest, UpsertRequest, MutationResponse,
     StatsRequest, StatsResponse,
 };
 
+/// Convert a requested mode, resolving `max_edits` for fuzzy completion. An
+/// unset `max_edits` selects the server default.
+fn completion_mode(
+    mode: CompletionMode,
+    max_edits: Option<i32>,
+) -> Result<core::CompletionMode, String> {
+    Ok(match mode {
+        CompletionMode::Prefix => core::CompletionMode::Prefix,
+        CompletionMode::Conjunctive => core::CompletionMode::Conjunctive,
+        CompletionMode::Fuzzy => core::CompletionMode::Fuzzy {
+            max_edits: resolve_max_edits(max_edits)?,
+        },
+        CompletionMode::Infix => core::CompletionMode::Infix,
+        CompletionMode::Substring => core::CompletionMode::Substring,
+    })
+}
+
+impl From<ResultOrder> for core::ResultOrder {
+    fn from(order: ResultOrder) -> Self {
+        match order {
+            ResultOrder::Score => core::ResultOrder::Score,
+            ResultOrder::Lexicographic => core::ResultOrder::Lexicographic,
+        }
+    }
+}
+
+impl From<Payload> for dictionary::Payload {
+    fn from(payload: Payload) -> Self {
+        Self {
+            data: payload.data,
+            attributes: payload.attributes.into_iter().collect(),
+        }
+    }
+}
+
+impl From<&dictionary::Payload> for Payload {
+    fn from(payload: &dictionary::Payload) -> Self {
+        Self {
+            data: payload.data.clone(),
+            attributes: payload.attributes.clone().into_iter().collect(),
+        }
+    }
+}
+
+impl From<TagFilter> for tag_index::TagFilter {
+    fn from(filter: TagFilter) -> Self {
+        Self {
+            include: filter.include,
+            exclude: filter.exclude,
+        }
+    }
+}
+
+impl TryFrom<StringScore> for CompletionEntry {
+    type Error = String;
+
+    fn try_from(string: StringScore) -> Result<Self, Self::Error> {
+        Ok(Self {
+            text: string.text,
+            score: check_score(string.score)?,
+            payload: string.payload.map(Into::into).unwrap_or_default(),
+            tags: string.tags,
+        })
+    }
+}
+
+/// Convert the strings of a request into entries to index
+fn completion_entries(strings: Vec<StringScore>) -> Result<Vec<CompletionEntry>, String> {
+    strings.into_iter().map(TryInto::try_into).collect()
+}
+
+impl From<InitMode> for core::InitMode {
+    fn from(mode: InitMode) -> Self {
+        match mode {
+            InitMode::Append => core::InitMode::Append,
+            InitMode::Replace => core::InitMode::Replace,
+        }
+    }
+}
+
+/// Index `entries` into the shared `autocomplete` under `mode`.
+///
+/// Appending adds the entries as pending changes and rebuilds the indexes
+/// in the background, tracked by `compactions`. Replacing builds the new index on a blocking task
+/// without holding the lock and swaps it in at once, so concurrent queries
+/// see either the whole old index or the whole new one. Changes made while
+/// it is built are replayed on it. The swap fails if another replacement
+/// began meanwhile.
+pub(crate) async fn init_autocomplete(
+    autocomplete: &Arc<Mutex<Autocomplete>>,
+    entries: &[CompletionEntry],
+    mode: core::InitMode,
+    compactions: &Compactions,
+) -> Result<(), String> {
+    match mode {
+        core::InitMode::Append => {
+            let mut locked = autocomplete.lock().await;
+            locked.upsert(entries)?;
+            compactions.spawn(autocomplete, &mut locked);
+            Ok(())
+        }
+        core::InitMode::Replace => {
+            let mut replacement = autocomplete.lock().await.begin_replace();
+            let entries = entries.to_vec();
+            let replacement = tokio::task::spawn_blocking(move || {
+                replacement.fresh.init_entries(&entries).map(|_| replacement)
+            })
+            .await
+            .map_err(|e| e.to_string())??;
+            let mut locked = autocomplete.lock().await;
+            locked.replace(replacement)?;
+            compactions.spawn_if_needed(autocomplete, &mut locked);
+            Ok(())
+        }
+    }
+}
+
+/// Background compactions of a shared autocomplete, kept so that they can
+/// be awaited
+#[derive(Clone, Default)]
+pub(crate) struct Compactions {
+    handles: Arc<std::sync::Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl Compactions {
+    /// Rebuild the indexes of `autocomplete`, the locked content of
+    /// `shared`, in a background task. The lock is taken again only to swap
+    /// the new indexes in, and queries merge the changes pending until then.
+    /// A failure is logged and recorded with `Autocomplete::fail_compaction`.
+    pub(crate) fn spawn(&self, shared: &Arc<Mutex<Autocomplete>>, autocomplete: &mut Autocomplete) {
+        let compaction = autocomplete.compaction();
+        let generation = compaction.generation();
+        let shared = Arc::clone(shared);
+        let handle = tokio::spawn(async move {
+            let result = tokio::task::spawn_blocking(move || compaction.run())
+                .await
+                .unwrap_or_else(|e| Err(e.to_string()));
+            let mut autocomplete = shared.lock().await;
+            match result {
+                Ok(indexes) => {
+                    autocomplete.finish_compaction(indexes);
+                }
+                Err(e) => {
+                    println!("Compaction of generation {} failed: {}", generation, e);
+                    autocomplete.fail_compaction(generation, e);
+                }
+            }
+        });
+        let mut handles = self.handles.lock().expect("compaction handles poisoned");
+        handles.retain(|handle| !handle.is_finished());
+        handles.push(handle);
+    }
+
+    /// Rebuild the indexes of `autocomplete` in the background, like
+    /// `spawn`, once it needs a compaction
+    pub(crate) fn spawn_if_needed(
+        &self,
+        shared: &Arc<Mutex<Autocomplete>>,
+        autocomplete: &mut Autocomplete,
+    ) {
+        if autocomplete.needs_compaction() {
+            self.spawn(shared, autocomplete);
+        }
+    }
+
+    /// Wait for the compactions spawned so far to finish
+    pub(crate) async fn wait(&self) {
+        let handles = {
+            let mut handles = self.handles.lock().expect("compaction handles poisoned");
+            std::mem::take(&mut *handles)
+        };
+        for handle in handles {
+            let _ = handle.await;
+        }
+    }
+}
+
+/// Report the outcome of an incremental change
+fn mutation_response(result: Result<(), String>, autocomplete: &Autocomplete) -> MutationResponse {
+    MutationResponse {
+        success: result.is_ok(),
+        error: result.err().unwrap_or_default(),
+        num_terms: autocomplete.num_terms() as i32,
+    }
+}
+
 #[derive(Clone)]
 pub struct AutocompleteServiceImpl {
     autocomplete: Arc<Mutex<Autocomplete>>,
+    result_limit: ResultLimit,
+    compactions: Compactions,
+}
+
+impl AutocompleteServiceImpl {
+    pub fn new(autocomplete: Arc<Mutex<Autocomplete>>, result_limit: ResultLimit) -> Self {
+        Self {
+            autocomplete,
+            result_limit,
+            compactions: Compactions::default(),
+        }
+    }
+
+    /// Wait for the background compactions started by the changes made so
+    /// far through this service
+    pub async fn wait_for_compactions(&self) {
+        self.compactions.wait().await;
+    }
 }
 
 #[tonic::async_trait]
@@ -35,12 +245,27 @@ impl AutocompleteService for AutocompleteServiceImpl {
         request: Request<CompleteRequest>,
     ) -> Result<Response<CompleteResponse>, Status> {
         let req = request.into_inner();
+        let k = self.result_limit
+            .resolve(Some(req.max_results))
+            .map_err(Status::invalid_argument)?;
+        let mode = CompletionMode::try_from(req.mode)
+            .map_err(|_| Status::invalid_argument(format!("unknown completion mode {}", req.mode)))?;
+        let mode = completion_mode(mode, req.max_edits).map_err(Status::invalid_argument)?;
+        let order = ResultOrder::try_from(req.order)
+            .map_err(|_| Status::invalid_argument(format!("unknown result order {}", req.order)))?;
+        let filter = req.filter.map(Into::into).unwrap_or_default();
         let autocomplete = self.autocomplete.lock().await;
-        let completions = autocomplete.complete(&req.prefix);
+        let completions = autocomplete
+            .complete_with_filter(&req.prefix, k, mode, order.into(), &filter)
+            .map_err(Status::failed_precondition)?;
         
         let response = CompleteResponse {
             completions: completions.into_iter()
                 .map(|(text, score)| Completion {
+                    payload: autocomplete
+                        .payload(&text)
+                        .filter(|payload| !payload.is_empty())
+                        .map(Payload::from),
                     text,
                     score,
                 })
@@ -55,13 +280,12 @@ impl AutocompleteService for AutocompleteServiceImpl {
         request: Request<InitRequest>,
     ) -> Result<Response<InitResponse>, Status> {
         let req = request.into_inner();
-        let strings: Vec<(String, f32)> = req.strings
-            .into_iter()
-            .map(|s| (s.text, s.score))
-            .collect();
-            
-        let mut autocomplete = self.autocomplete.lock().await;
-        match autocomplete.init(&strings) {
+        let mode = InitMode::try_from(req.mode)
+            .map_err(|_| Status::invalid_argument(format!("unknown init mode {}", req.mode)))?;
+        let entries = completion_entries(req.strings).map_err(Status::invalid_argument)?;
+
+        let mode = mode.into();
+        match init_autocomplete(&self.autocomplete, &entries, mode, &self.compactions).await {
             Ok(_) => Ok(Response::new(InitResponse {
                 success: true,
                 error: String::new(),
@@ -73,6 +297,56 @@ impl AutocompleteService for AutocompleteServiceImpl {
         }
     }
 
+    async fn add(
+        &self,
+        request: Request<AddRequest>,
+    ) -> Result<Response<MutationResponse>, Status> {
+        let string = request
+            .into_inner()
+            .string
+            .ok_or_else(|| Status::invalid_argument("missing string"))?;
+        let entry = string.try_into().map_err(Status::invalid_argument)?;
+        let mut autocomplete = self.autocomplete.lock().await;
+        let result = autocomplete.add(entry);
+        self.compactions.spawn_if_needed(&self.autocomplete, &mut autocomplete);
+        Ok(Response::new(mutation_response(result, &autocomplete)))
+    }
+
+    async fn update_score(
+        &self,
+        request: Request<UpdateScoreRequest>,
+    ) -> Result<Response<MutationResponse>, Status> {
+        let req = request.into_inner();
+        let score = check_score(req.score).map_err(Status::invalid_argument)?;
+        let mut autocomplete = self.autocomplete.lock().await;
+        let result = autocomplete.update_score(&req.text, score);
+        self.compactions.spawn_if_needed(&self.autocomplete, &mut autocomplete);
+        Ok(Response::new(mutation_response(result, &autocomplete)))
+    }
+
+    async fn remove(
+        &self,
+        request: Request<RemoveRequest>,
+    ) -> Result<Response<MutationResponse>, Status> {
+        let req = request.into_inner();
+        let mut autocomplete = self.autocomplete.lock().await;
+        let result = autocomplete.remove(&req.text);
+        self.compactions.spawn_if_needed(&self.autocomplete, &mut autocomplete);
+        Ok(Response::new(mutation_response(result, &autocomplete)))
+    }
+
+    async fn upsert(
+        &self,
+        request: Request<UpsertRequest>,
+    ) -> Result<Response<MutationResponse>, Status> {
+        let entries =
+            completion_entries(request.into_inner().strings).map_err(Status::invalid_argument)?;
+        let mut autocomplete = self.autocomplete.lock().await;
+        let result = autocomplete.upsert(&entries);
+        self.compactions.spawn_if_needed(&self.autocomplete, &mut autocomplete);
+        Ok(Response::new(mutation_response(result, &autocomplete)))
+    }
+
     async fn get_stats(
         &self,
         _request: Request<StatsRequest>,
@@ -100,14 +374,19 @@ async fn graphql_playground() -> impl IntoResponse {
     )
 }
 
-pub async fn run_server(grpc_addr: &str, graphql_addr: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let autocomplete = Arc::new(Mutex::new(Autocomplete::new()));
-    let schema = create_schema(autocomplete.clone());
+pub async fn run_server(
+    grpc_addr: &str,
+    graphql_addr: &str,
+    result_limit: ResultLimit,
+    config: IndexConfig,
+    normalizer: NormalizerChain,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let autocomplete = Autocomplete::with_config(config).with_normalizer(normalizer);
+    let autocomplete = Arc::new(Mutex::new(autocomplete));
+    let schema = create_schema(autocomplete.clone(), result_limit);
     
     // Create gRPC service
-    let grpc_service = AutocompleteServiceImpl {
-        autocomplete: autocomplete.clone(),
-    };
+    let grpc_service = AutocompleteServiceImpl::new(autocomplete.clone(), result_limit);
 
     // Create GraphQL router
     let app = Router::new()
@@ -122,12 +401,14 @@ pub async fn run_server(grpc_addr: &str, graphql_addr: &str) -> Result<(), Box<d
     println!("gRPC server listening on {}", grpc_addr);
     println!("GraphQL server listening on {}", graphql_addr);
 
-    tokio::join!(
+    let (grpc_result, graphql_result) = tokio::join!(
         TonicServer::builder()
             .add_service(AutocompleteServiceServer::new(grpc_service))
             .serve(grpc_addr),
         Server::bind(&graphql_addr).serve(app.into_make_service())
     );
+    grpc_result?;
+    graphql_result?;
 
     Ok(())
 } 
\ No newline at end of file
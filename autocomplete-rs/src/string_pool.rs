@@ -104,6 +104,12 @@ impl ScoredStringPool {
     }
 }
 
+impl Default for ScoredStringPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Iterator over scored strings in the pool
 pub struct ScoredStringPoolIterator<'a> {
     pool: &'a ScoredStringPool,
@@ -151,7 +157,7 @@ impl<'a> Iterator for ScoredStringPoolIterator<'a> {
 
 impl ScoredStringPool {
     /// Get an iterator over the scored strings
-    pub fn iter(&self) -> ScoredStringPoolIterator {
+    pub fn iter(&self) -> ScoredStringPoolIterator<'_> {
         ScoredStringPoolIterator::new(self, 0)
     }
 } 
\ No newline at end of file
@@ -0,0 +1,160 @@
+use crate::index::BitVector;
+
+/// Number of bits covered by each leaf of the min-excess tree
+const BLOCK_BITS: usize = 512;
+
+/// Balanced parentheses sequence, with ones as open and zeros as close
+/// parentheses, supporting excess range-minimum queries.
+///
+/// The minimum excess reached within each block of `BLOCK_BITS` bits is kept
+/// in the leaves of a segment tree, so a query scans at most two partial
+/// blocks and descends the tree once.
+#[derive(Debug, Clone)]
+pub struct BpVector {
+    bits: BitVector,
+    num_blocks: usize,
+    /// Segment tree of `(min excess, leftmost block reaching it)`, with
+    /// leaves at `num_leaves..2 * num_leaves`
+    min_tree: Vec<(i32, u32)>,
+}
+
+impl BpVector {
+    /// Index a balanced parentheses sequence
+    pub fn new(bits: BitVector) -> Self {
+        let num_blocks = bits.size().div_ceil(BLOCK_BITS);
+        let num_leaves = num_blocks.next_power_of_two();
+        let mut min_tree = vec![(i32::MAX, u32::MAX); 2 * num_leaves];
+        let mut excess = 0;
+        for block in 0..num_blocks {
+            let begin = block * BLOCK_BITS;
+            let end = (begin + BLOCK_BITS).min(bits.size());
+            let mut min = i32::MAX;
+            for pos in begin..end {
+                excess += if bits.test(pos) { 1 } else { -1 };
+                min = min.min(excess);
+            }
+            min_tree[num_leaves + block] = (min, block as u32);
+        }
+        for node in (1..num_leaves).rev() {
+            min_tree[node] = leftmost_min(min_tree[2 * node], min_tree[2 * node + 1]);
+        }
+        Self {
+            bits,
+            num_blocks,
+            min_tree,
+        }
+    }
+
+    /// Get the number of open minus close parentheses before `pos`
+    pub fn excess(&self, pos: usize) -> i32 {
+        2 * self.bits.rank1(pos) as i32 - pos as i32
+    }
+
+    /// Get the leftmost position in `[a, b]` of the minimum excess, along
+    /// with that excess
+    pub fn excess_rmq(&self, a: usize, b: usize) -> (usize, i32) {
+        assert!(a <= b && b <= self.bits.size(), "invalid excess range");
+        let mut min = (self.excess(a), a);
+        let block_a = a / BLOCK_BITS;
+        let block_b = b.saturating_sub(1) / BLOCK_BITS;
+        if block_a + 1 >= block_b {
+            self.scan(a, b, min.0, &mut min);
+            return (min.1, min.0);
+        }
+
+        // Partial block of `a`, full blocks in between, partial block of `b`
+        let mid_begin = (block_a + 1) * BLOCK_BITS;
+        let mid_end = block_b * BLOCK_BITS;
+        self.scan(a, mid_begin, min.0, &mut min);
+        let (block_min, block) = self.min_blocks(block_a + 1, block_b);
+        if block_min < min.0 {
+            let begin = block as usize * BLOCK_BITS;
+            self.scan(begin, begin + BLOCK_BITS, self.excess(begin), &mut min);
+        }
+        self.scan(mid_end, b, self.excess(mid_end), &mut min);
+        (min.1, min.0)
+    }
+
+    /// Get the number of parentheses
+    pub fn size(&self) -> usize {
+        self.bits.size()
+    }
+
+    /// Get the position of the `i`-th (0-based) close parenthesis
+    pub fn select0(&self, i: usize) -> usize {
+        self.bits.select0(i)
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        self.bits.bytes()
+            + std::mem::size_of_val(&self.num_blocks)
+            + self.min_tree.len() * std::mem::size_of::<(i32, u32)>()
+    }
+
+    /// Update `min` with the excesses after each bit in `[begin, end)`,
+    /// given the excess before `begin`
+    fn scan(&self, begin: usize, end: usize, mut excess: i32, min: &mut (i32, usize)) {
+        for pos in begin..end {
+            excess += if self.bits.test(pos) { 1 } else { -1 };
+            if excess < min.0 {
+                *min = (excess, pos + 1);
+            }
+        }
+    }
+
+    /// Get the minimum excess within blocks `[begin, end)` and the leftmost
+    /// block reaching it
+    fn min_blocks(&self, begin: usize, end: usize) -> (i32, u32) {
+        let num_leaves = self.min_tree.len() / 2;
+        let (mut lo, mut hi) = (begin + num_leaves, end + num_leaves);
+        let (mut left, mut right) = ((i32::MAX, u32::MAX), (i32::MAX, u32::MAX));
+        while lo < hi {
+            if lo & 1 == 1 {
+                left = leftmost_min(left, self.min_tree[lo]);
+                lo += 1;
+            }
+            if hi & 1 == 1 {
+                hi -= 1;
+                right = leftmost_min(self.min_tree[hi], right);
+            }
+            lo /= 2;
+            hi /= 2;
+        }
+        leftmost_min(left, right)
+    }
+}
+
+fn leftmost_min(left: (i32, u32), right: (i32, u32)) -> (i32, u32) {
+    if right.0 < left.0 {
+        right
+    } else {
+        left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::BitVectorBuilder;
+
+    #[test]
+    fn test_excess_rmq() {
+        // Random-looking walk that stays non-negative
+        let mut builder = BitVectorBuilder::new();
+        let mut excess = 0;
+        for i in 0..5000usize {
+            let open = excess == 0 || (i * 7919 + i / 13) % 11 < 6;
+            builder.push(open);
+            excess += if open { 1 } else { -1 };
+        }
+        let bp = BpVector::new(builder.build());
+
+        let excesses: Vec<i32> = (0..=bp.size()).map(|pos| bp.excess(pos)).collect();
+        for (a, b) in [(0, 5000), (3, 4), (10, 10), (100, 700), (511, 1537), (1000, 4999)] {
+            let min = *excesses[a..=b].iter().min().unwrap();
+            let pos = a + excesses[a..=b].iter().position(|&e| e == min).unwrap();
+            assert_eq!(bp.excess_rmq(a, b), (pos, min), "range [{}, {}]", a, b);
+        }
+    }
+}
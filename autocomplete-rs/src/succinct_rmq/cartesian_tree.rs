@@ -0,0 +1,111 @@
+use super::bp_vector::BpVector;
+use crate::index::BitVectorBuilder;
+
+/// Range minimum query structure storing the Cartesian tree of an array as
+/// balanced parentheses, in about `2n` bits plus the excess index.
+///
+/// This is the 2d-Min-Heap DFUDS representation of Fischer and Heun, built
+/// over the reversed array so that the input can be scanned left to right.
+/// On ties, the leftmost minimum wins.
+#[derive(Debug, Clone)]
+pub struct CartesianTree {
+    bp: BpVector,
+}
+
+impl CartesianTree {
+    /// Build the tree over `values`, where `less(x, y)` tells whether `x`
+    /// is smaller than `y`
+    pub fn build<T, F>(values: &[T], less: F) -> Self
+    where
+        F: Fn(&T, &T) -> bool,
+    {
+        let mut bits = Vec::with_capacity(2 * values.len() + 2);
+        let mut stack: Vec<&T> = Vec::new();
+        for value in values {
+            bits.push(false);
+            while stack.last().is_some_and(|&top| less(value, top)) {
+                stack.pop();
+                bits.push(true);
+            }
+            stack.push(value);
+        }
+        // Super-root
+        bits.push(false);
+        bits.extend(std::iter::repeat_n(true, stack.len() + 1));
+
+        let mut builder = BitVectorBuilder::new();
+        for &bit in bits.iter().rev() {
+            builder.push(bit);
+        }
+        Self {
+            bp: BpVector::new(builder.build()),
+        }
+    }
+
+    /// Get the position of the minimum in `[a, b]`
+    pub fn rmq(&self, a: usize, b: usize) -> usize {
+        assert!(a <= b && b < self.size(), "invalid rmq range");
+        if a == b {
+            return a;
+        }
+
+        let n = self.size();
+        let t = self.bp.select0(n - b - 1);
+        let excess_t = t as i32 - 2 * (n - b - 1) as i32;
+        let x = self.bp.select0(n - b);
+        let y = self.bp.select0(n - a);
+        let (w, excess_w) = self.bp.excess_rmq(x, y);
+        if excess_w >= excess_t - 1 {
+            b
+        } else {
+            let rank0_w = (w as i32 - excess_w) as usize / 2;
+            n - rank0_w
+        }
+    }
+
+    /// Get the number of values
+    pub fn size(&self) -> usize {
+        self.bp.size() / 2 - 1
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        self.bp.bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rmq() {
+        let values: Vec<u32> = (0..3000u32).map(|i| (i * 7919 + i / 3) % 1009).collect();
+        let tree = CartesianTree::build(&values, |a, b| a < b);
+        assert_eq!(tree.size(), values.len());
+
+        for (a, b) in [(0, 2999), (5, 5), (5, 6), (17, 900), (1023, 2047), (2000, 2999)] {
+            let min = *values[a..=b].iter().min().unwrap();
+            let expected = a + values[a..=b].iter().position(|&v| v == min).unwrap();
+            assert_eq!(tree.rmq(a, b), expected, "range [{}, {}]", a, b);
+        }
+        for a in 0..60 {
+            for b in a..60 {
+                let min = *values[a..=b].iter().min().unwrap();
+                let expected = a + values[a..=b].iter().position(|&v| v == min).unwrap();
+                assert_eq!(tree.rmq(a, b), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rmq_ties_and_single_value() {
+        let tree = CartesianTree::build(&[4, 1, 3, 1, 1], |a, b| a < b);
+        assert_eq!(tree.rmq(0, 4), 1);
+        assert_eq!(tree.rmq(2, 4), 3);
+        assert_eq!(tree.rmq(0, 0), 0);
+
+        let tree = CartesianTree::build(&[7], |a, b| a < b);
+        assert_eq!(tree.rmq(0, 0), 0);
+    }
+}
@@ -0,0 +1,7 @@
+//! Succinct range minimum queries over static arrays
+
+pub mod bp_vector;
+pub mod cartesian_tree;
+
+pub use bp_vector::*;
+pub use cartesian_tree::*;
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use crate::types::IdType;
+
+/// Restriction of the completions returned by a query to those carrying some
+/// tags. The empty filter accepts every completion.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagFilter {
+    /// Accept only completions with at least one of these tags, unless empty
+    pub include: Vec<String>,
+    /// Reject completions with any of these tags
+    pub exclude: Vec<String>,
+}
+
+impl TagFilter {
+    /// Create a filter accepting every completion
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept only completions with `tag`, or with another included tag
+    pub fn include(mut self, tag: impl Into<String>) -> Self {
+        self.include.push(tag.into());
+        self
+    }
+
+    /// Reject completions with `tag`
+    pub fn exclude(mut self, tag: impl Into<String>) -> Self {
+        self.exclude.push(tag.into());
+        self
+    }
+
+    /// Check if the filter accepts every completion
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+}
+
+/// Tags of the completions, as one bitmap over the completion ids per tag
+#[derive(Debug, Clone, Default)]
+pub struct TagIndex {
+    bitmaps: HashMap<String, Vec<u64>>,
+}
+
+impl TagIndex {
+    /// Create an index without tags
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the tags of the completion `id` with `tags`
+    pub fn set_tags<S: AsRef<str>>(&mut self, id: IdType, tags: &[S]) {
+        let (word, bit) = (id as usize >> 6, 1u64 << (id & 63));
+        for bitmap in self.bitmaps.values_mut() {
+            if let Some(w) = bitmap.get_mut(word) {
+                *w &= !bit;
+            }
+        }
+        for tag in tags {
+            let bitmap = self.bitmaps.entry(tag.as_ref().to_string()).or_default();
+            if bitmap.len() <= word {
+                bitmap.resize(word + 1, 0);
+            }
+            bitmap[word] |= bit;
+        }
+    }
+
+    /// Check if the completion `id` has `tag`
+    pub fn has_tag(&self, id: IdType, tag: &str) -> bool {
+        self.bitmaps.get(tag).is_some_and(|bitmap| test(bitmap, id))
+    }
+
+    /// Get the tags of the completion `id`, sorted
+    pub fn tags(&self, id: IdType) -> Vec<&str> {
+        let mut tags: Vec<&str> = self
+            .bitmaps
+            .iter()
+            .filter(|(_, bitmap)| test(bitmap, id))
+            .map(|(tag, _)| tag.as_str())
+            .collect();
+        tags.sort_unstable();
+        tags
+    }
+
+    /// Get the number of distinct tags ever given to a completion
+    pub fn num_tags(&self) -> usize {
+        self.bitmaps.len()
+    }
+
+    /// Get a predicate on completion ids accepting those that pass `filter`
+    pub fn matcher<'a>(&'a self, filter: &TagFilter) -> impl Fn(IdType) -> bool + 'a {
+        let bitmaps = |tags: &[String]| -> Vec<&'a [u64]> {
+            tags.iter()
+                .filter_map(|tag| self.bitmaps.get(tag).map(Vec::as_slice))
+                .collect()
+        };
+        let include = (!filter.include.is_empty()).then(|| bitmaps(&filter.include));
+        let exclude = bitmaps(&filter.exclude);
+        move |id| {
+            include
+                .as_ref()
+                .is_none_or(|include| include.iter().any(|bitmap| test(bitmap, id)))
+                && !exclude.iter().any(|bitmap| test(bitmap, id))
+        }
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        self.bitmaps
+            .iter()
+            .map(|(tag, bitmap)| tag.len() + bitmap.len() * std::mem::size_of::<u64>())
+            .sum()
+    }
+}
+
+fn test(bitmap: &[u64], id: IdType) -> bool {
+    bitmap
+        .get(id as usize >> 6)
+        .is_some_and(|&word| (word >> (id & 63)) & 1 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_tags() {
+        let mut tags = TagIndex::new();
+        tags.set_tags(3, &["books", "sale"]);
+        tags.set_tags(130, &["people"]);
+        assert!(tags.has_tag(3, "books"));
+        assert!(!tags.has_tag(4, "books"));
+        assert_eq!(tags.tags(3), vec!["books", "sale"]);
+        assert_eq!(tags.num_tags(), 3);
+
+        // Tags are replaced
+        tags.set_tags(3, &["music"]);
+        assert_eq!(tags.tags(3), vec!["music"]);
+        assert!(!tags.has_tag(3, "books"));
+        tags.set_tags::<&str>(130, &[]);
+        assert!(tags.tags(130).is_empty());
+    }
+
+    #[test]
+    fn test_matcher() {
+        let mut tags = TagIndex::new();
+        tags.set_tags(0, &["books"]);
+        tags.set_tags(1, &["books", "sale"]);
+        tags.set_tags(2, &["people"]);
+
+        let accepted = |filter: TagFilter| -> Vec<IdType> {
+            let matcher = tags.matcher(&filter);
+            (0..4).filter(|&id| matcher(id)).collect()
+        };
+        assert_eq!(accepted(TagFilter::new()), vec![0, 1, 2, 3]);
+        assert_eq!(accepted(TagFilter::new().include("books")), vec![0, 1]);
+        assert_eq!(accepted(TagFilter::new().include("books").include("people")), vec![0, 1, 2]);
+        assert_eq!(accepted(TagFilter::new().include("books").exclude("sale")), vec![0]);
+        assert_eq!(accepted(TagFilter::new().exclude("books")), vec![2, 3]);
+        assert!(accepted(TagFilter::new().include("unknown")).is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::fc_dictionary::{FcDictionary, DEFAULT_BUCKET_SIZE};
+use crate::types::{CompletionType, IdType, ValueRange};
+
+/// Dictionary of the distinct terms of a collection.
+///
+/// Terms are numbered in lexicographic order starting from 1, leaving
+/// `global::TERMINATOR` (0) free, so that the terms sharing a prefix form a
+/// contiguous range of ids. Terms are stored in a front-coded dictionary.
+#[derive(Clone, Default)]
+pub struct TermDictionary {
+    terms: FcDictionary,
+}
+
+impl TermDictionary {
+    /// Create a new empty term dictionary
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the dictionary from a collection of terms, in any order and
+    /// possibly repeated
+    pub fn build<I, S>(terms: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::build_with_bucket_size(terms, DEFAULT_BUCKET_SIZE)
+    }
+
+    /// Build the dictionary with the given front-coding bucket size
+    pub fn build_with_bucket_size<I, S>(terms: I, bucket_size: usize) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut terms: Vec<String> = terms
+            .into_iter()
+            .map(|term| term.as_ref().to_string())
+            .collect();
+        terms.sort_unstable();
+        terms.dedup();
+        let terms = FcDictionary::build(&terms, bucket_size.max(1))
+            .expect("terms are sorted and unique");
+        Self { terms }
+    }
+
+    /// Get the id of a term
+    pub fn locate(&self, term: &str) -> Option<IdType> {
+        self.terms.locate(term)
+    }
+
+    /// Get the term with the given id
+    pub fn extract(&self, id: IdType) -> Option<String> {
+        self.terms.extract(id)
+    }
+
+    /// Get the inclusive range of ids of the terms starting with `prefix`,
+    /// or `global::INVALID_RANGE` if there is none
+    pub fn locate_prefix(&self, prefix: &str) -> ValueRange {
+        self.terms.locate_prefix(prefix)
+    }
+
+    /// Map the whitespace-separated terms of `text` to their ids, or `None`
+    /// if one of them is not in the dictionary
+    pub fn tokenize(&self, text: &str) -> Option<CompletionType> {
+        text.split_whitespace().map(|term| self.locate(term)).collect()
+    }
+
+    /// Rebuild the text of a completion from its term ids
+    pub fn decode(&self, completion: &[IdType]) -> String {
+        completion
+            .iter()
+            .filter_map(|&id| self.extract(id))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Get the number of terms
+    pub fn len(&self) -> usize {
+        self.terms.size()
+    }
+
+    /// Check if the dictionary is empty
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        self.terms.bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_term_dictionary() {
+        let dictionary = TermDictionary::build(["york", "new", "times", "new", "tickets"]);
+        assert_eq!(dictionary.len(), 4);
+        assert_eq!(dictionary.locate("new"), Some(1));
+        assert_eq!(dictionary.locate("york"), Some(4));
+        assert_eq!(dictionary.locate("boston"), None);
+        assert_eq!(dictionary.extract(2).as_deref(), Some("tickets"));
+        assert_eq!(dictionary.extract(0), None);
+        assert_eq!(dictionary.extract(5), None);
+        assert_eq!(dictionary.locate_prefix("ti"), ValueRange { begin: 2, end: 3 });
+        assert_eq!(dictionary.locate_prefix("ti").len(), 2);
+        assert!(dictionary.locate_prefix("z").is_invalid());
+        assert_eq!(dictionary.locate_prefix("z").len(), 0);
+    }
+
+    #[test]
+    fn test_tokenize_and_decode() {
+        let dictionary = TermDictionary::build_with_bucket_size(["york", "new", "times"], 1);
+        let completion = dictionary.tokenize("new  york times").unwrap();
+        assert_eq!(completion, vec![1, 3, 2]);
+        assert_eq!(dictionary.decode(&completion), "new york times");
+        assert_eq!(dictionary.tokenize("new jersey"), None);
+    }
+}
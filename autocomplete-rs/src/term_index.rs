@@ -0,0 +1,296 @@
+use crate::ef::EfSequence;
+use crate::index::CompactVector;
+use crate::term_dictionary::TermDictionary;
+use crate::types::{global, CompletionType, IdType, ScoreType, ValueRange};
+use crate::unsorted_list::UnsortedList;
+
+/// Split a query into its complete terms and the trailing (possibly empty)
+/// term prefix. "york new ti" yields `(["york", "new"], "ti")`, while
+/// "york new " yields `(["york", "new"], "")`.
+pub fn parse_query(query: &str) -> (Vec<&str>, &str) {
+    match query.rfind(char::is_whitespace) {
+        Some(pos) => {
+            let (head, tail) = query.split_at(pos);
+            let suffix = &tail[tail.chars().next().map_or(0, char::len_utf8)..];
+            (head.split_whitespace().collect(), suffix)
+        }
+        None => (Vec::new(), query),
+    }
+}
+
+/// Check if the first terms of `text` are the complete terms of `query` and
+/// its next term is prefixed by the last token, as matched by `prefix_topk`
+pub fn prefix_matches(text: &str, query: &str) -> bool {
+    prefix_matches_terms(&text.split_whitespace().collect::<Vec<_>>(), query)
+}
+
+/// Like `prefix_matches`, for a text already split into `words`
+pub fn prefix_matches_terms<T: AsRef<str>>(words: &[T], query: &str) -> bool {
+    let (terms, suffix) = parse_query(query);
+    let mut words = words.iter().map(AsRef::as_ref);
+    terms.into_iter().all(|term| words.next() == Some(term))
+        && words.next().is_some_and(|word| word.starts_with(suffix))
+}
+
+/// Check if `text` contains every complete term of `query` and a term
+/// prefixed by its last token, as matched by `conjunctive_topk`
+pub fn conjunctive_matches(text: &str, query: &str) -> bool {
+    conjunctive_matches_terms(&text.split_whitespace().collect::<Vec<_>>(), query)
+}
+
+/// Like `conjunctive_matches`, for a text already split into `words`
+pub fn conjunctive_matches_terms<T: AsRef<str>>(words: &[T], query: &str) -> bool {
+    let (terms, suffix) = parse_query(query);
+    terms.into_iter().all(|term| words.iter().any(|word| word.as_ref() == term))
+        && words.iter().any(|word| word.as_ref().starts_with(suffix))
+}
+
+/// Term-level core shared by every `TopKIndex` variant: the term
+/// dictionary, and the best completions of each range of lexicographic
+/// positions.
+///
+/// Completions are numbered by decreasing score, then by text and by id
+/// (doc id 0 is the best completion), so that walking a list of doc ids in
+/// increasing order visits completions best first and a search can stop as
+/// soon as `k` matches are found. The best completions of a range of
+/// lexicographic positions, in order of their term ids, are found with range
+/// minimum queries over the doc ids in lexicographic order.
+///
+/// Completions with the same sequence of terms are separate docs, next to
+/// each other in lexicographic order. Each variant locates the range of a
+/// prefix among the distinct sequences, which `doc_range` turns into the
+/// range of the docs with those sequences.
+#[derive(Clone, Default)]
+pub struct TermIndex {
+    dictionary: TermDictionary,
+    lex_docs: UnsortedList,
+    /// Position in `lex_docs` of the first doc of each distinct sequence,
+    /// followed by the number of docs
+    sequence_starts: EfSequence,
+    doc_ids: CompactVector,
+}
+
+/// Term ids of the completions of a `TermIndex`, from which each
+/// `TopKIndex` variant builds its own structures
+pub(crate) struct TermCompletions {
+    /// Term ids of the completion of each doc id
+    pub forward: Vec<CompletionType>,
+    /// Doc ids in lexicographic order of their term ids
+    pub lex_docs: Vec<IdType>,
+}
+
+impl TermCompletions {
+    /// Iterate over the distinct term id sequences of the completions in
+    /// lexicographic order
+    pub fn lex_completions(&self) -> impl Iterator<Item = &CompletionType> + '_ {
+        let mut previous = None;
+        self.lex_docs
+            .iter()
+            .map(|&doc_id| &self.forward[doc_id as usize])
+            .filter(move |&completion| previous.replace(completion) != Some(completion))
+    }
+}
+
+impl TermIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the index over `(id, text, score)` triples, returning the term
+    /// ids of the indexed completions along with it. Completions without
+    /// terms match no query and are left out.
+    pub(crate) fn build<'a, I>(completions: I) -> (Self, TermCompletions)
+    where
+        I: IntoIterator<Item = (IdType, &'a str, ScoreType)>,
+    {
+        let mut completions: Vec<_> = completions
+            .into_iter()
+            .filter(|(_, text, _)| text.split_whitespace().next().is_some())
+            .collect();
+        completions.sort_by(|a, b| {
+            b.2.total_cmp(&a.2).then_with(|| a.1.cmp(b.1)).then(a.0.cmp(&b.0))
+        });
+
+        let dictionary = TermDictionary::build(
+            completions.iter().flat_map(|(_, text, _)| text.split_whitespace()),
+        );
+
+        let forward: Vec<CompletionType> = completions
+            .iter()
+            .map(|(_, text, _)| {
+                dictionary
+                    .tokenize(text)
+                    .expect("every term of the collection is in the dictionary")
+            })
+            .collect();
+
+        // The sort is stable, so docs with the same sequence stay best first
+        let mut lex_docs: Vec<IdType> = (0..forward.len() as IdType).collect();
+        lex_docs.sort_by(|&a, &b| forward[a as usize].cmp(&forward[b as usize]));
+        let sequence_starts = (0..lex_docs.len())
+            .filter(|&i| {
+                i == 0 || forward[lex_docs[i - 1] as usize] != forward[lex_docs[i] as usize]
+            })
+            .chain(std::iter::once(lex_docs.len()))
+            .map(|i| i as u64);
+
+        let index = Self {
+            dictionary,
+            lex_docs: UnsortedList::build(lex_docs.clone()),
+            sequence_starts: EfSequence::build(sequence_starts).expect("starts are increasing"),
+            doc_ids: CompactVector::build(completions.iter().map(|&(id, _, _)| u64::from(id))),
+        };
+        (index, TermCompletions { forward, lex_docs })
+    }
+
+    /// Resolve the complete terms of `query` to term ids and its last token
+    /// to the range of term ids it prefixes, or `None` if nothing can match
+    pub(crate) fn parse(&self, query: &str) -> Option<(CompletionType, ValueRange)> {
+        let (prefix, suffix) = parse_query(query);
+        let prefix = prefix
+            .into_iter()
+            .map(|term| self.dictionary.locate(term))
+            .collect::<Option<CompletionType>>()?;
+        let suffix_range = self.dictionary.locate_prefix(suffix);
+        if suffix_range.is_invalid() {
+            return None;
+        }
+        Some((prefix, suffix_range))
+    }
+
+    /// Get the inclusive range of lexicographic positions of the docs whose
+    /// term id sequences are in the inclusive `range` of positions among the
+    /// distinct sequences
+    pub(crate) fn doc_range(&self, range: ValueRange) -> ValueRange {
+        if range.is_invalid() || range.end as usize + 1 >= self.sequence_starts.len() {
+            return global::INVALID_RANGE;
+        }
+        ValueRange {
+            begin: self.sequence_starts.access(range.begin as usize),
+            end: self.sequence_starts.access(range.end as usize + 1) - 1,
+        }
+    }
+
+    /// Get the term dictionary
+    pub fn dictionary(&self) -> &TermDictionary {
+        &self.dictionary
+    }
+
+    /// Return the ids of the `k` best completions in the given range of
+    /// lexicographic positions, best first
+    pub fn topk_in_range(&self, range: ValueRange, k: usize) -> Vec<IdType> {
+        self.topk_in_range_filtered(range, k, &|_| true)
+    }
+
+    /// Return the ids of the `k` best completions in the given range of
+    /// lexicographic positions whose ids are accepted by `filter`, best first
+    pub fn topk_in_range_filtered(
+        &self,
+        range: ValueRange,
+        k: usize,
+        filter: &dyn Fn(IdType) -> bool,
+    ) -> Vec<IdType> {
+        self.lex_docs
+            .topk_filtered(range, k, |doc_id| filter(self.id(doc_id)))
+            .into_iter()
+            .map(|doc_id| self.id(doc_id))
+            .collect()
+    }
+
+    /// Get the id of the completion at the given lexicographic position
+    pub fn completion_at(&self, pos: usize) -> Option<IdType> {
+        let doc_id = self.lex_docs.get(pos)?;
+        Some(self.id(doc_id))
+    }
+
+    /// Get the id of the completion with the given doc id
+    pub(crate) fn id(&self, doc_id: IdType) -> IdType {
+        self.doc_ids.access(doc_id as usize) as IdType
+    }
+
+    /// Get the number of distinct terms
+    pub fn num_terms(&self) -> usize {
+        self.dictionary.len()
+    }
+
+    /// Get the number of indexed completions
+    pub fn num_docs(&self) -> usize {
+        self.doc_ids.size()
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        self.dictionary.bytes()
+            + self.lex_docs.bytes()
+            + self.sequence_starts.bytes()
+            + self.doc_ids.bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> (TermIndex, TermCompletions) {
+        TermIndex::build(vec![
+            (0, "new york times", 0.9),
+            (1, "new york", 1.0),
+            (2, "york new tickets", 0.3),
+            (3, "new jersey", 0.7),
+            (4, "times square", 0.5),
+        ])
+    }
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(parse_query("york new ti"), (vec!["york", "new"], "ti"));
+        assert_eq!(parse_query("york new "), (vec!["york", "new"], ""));
+        assert_eq!(parse_query("ti"), (vec![], "ti"));
+        assert_eq!(parse_query(""), (vec![], ""));
+    }
+
+    #[test]
+    fn test_completions_as_term_ids() {
+        let (index, completions) = sample();
+        // jersey, new, square, tickets, times, york
+        assert_eq!(index.num_terms(), 6);
+        assert_eq!(index.num_docs(), 5);
+        // Doc ids follow decreasing scores
+        assert_eq!(completions.forward[1], vec![2, 6, 5]);
+        assert_eq!(index.dictionary().decode(&completions.forward[4]), "york new tickets");
+        assert_eq!(index.parse("york new ti"), Some((vec![6, 2], ValueRange { begin: 4, end: 5 })));
+        assert_eq!(index.parse("boston "), None);
+        assert_eq!(index.parse("new zz"), None);
+    }
+
+    #[test]
+    fn test_topk_in_range() {
+        let (index, completions) = sample();
+        // new jersey, new york, new york times, times square, york new tickets
+        assert_eq!(completions.lex_docs, vec![2, 0, 1, 3, 4]);
+        assert_eq!(index.topk_in_range(ValueRange { begin: 0, end: 2 }, 10), vec![1, 0, 3]);
+        assert_eq!(index.topk_in_range(ValueRange { begin: 1, end: 4 }, 2), vec![1, 0]);
+        assert!(index.topk_in_range(crate::types::global::INVALID_RANGE, 10).is_empty());
+        assert_eq!(index.completion_at(3), Some(4));
+        assert_eq!(index.completion_at(5), None);
+    }
+
+    #[test]
+    fn test_duplicate_term_sequences() {
+        let (index, completions) = TermIndex::build(vec![
+            (0, "new  york", 0.5),
+            (1, "new york", 0.8),
+            (2, "newark", 0.1),
+        ]);
+        // Both "new york" are indexed, next to each other and best first
+        assert_eq!(index.num_docs(), 3);
+        assert_eq!(completions.lex_docs, vec![0, 1, 2]);
+        assert_eq!(completions.lex_completions().count(), 2);
+        assert_eq!(index.id(0), 1);
+        let range = |begin, end| ValueRange { begin, end };
+        assert_eq!(index.doc_range(range(0, 0)), range(0, 1));
+        assert_eq!(index.doc_range(range(1, 1)), range(2, 2));
+        assert!(index.doc_range(range(1, 2)).is_invalid());
+        assert_eq!(index.topk_in_range(ValueRange { begin: 0, end: 2 }, 10), vec![1, 0, 2]);
+    }
+}
@@ -0,0 +1,539 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use crate::compact_forward_index::CompactForwardIndex;
+use crate::completion_trie::{SuccinctCompletionTrie, SuccinctCompletionTrieBuilder};
+use crate::fc_dictionary::DEFAULT_BUCKET_SIZE;
+use crate::index::{BlockedInvertedIndex, DEFAULT_BLOCK_FRACTION};
+use crate::integer_fc_dictionary::IntegerFcDictionary;
+use crate::inverted_index::{IntersectionIterator, InvertedIndex, UnionCursor};
+use crate::parameters::Parameters;
+use crate::term_index::{TermCompletions, TermIndex};
+use crate::types::{global, IdType, ScoreType, ValueRange};
+
+/// Index answering top-k completion queries over a static collection.
+///
+/// Queries are split into complete terms and a trailing term prefix, and
+/// results are completion ids, best first.
+pub trait TopKIndex: Send + Sync {
+    /// Return the ids of the `k` best completions whose first terms are the
+    /// complete terms of `query` and whose next term is prefixed by its last
+    /// token
+    fn prefix_topk(&self, query: &str, k: usize) -> Vec<IdType> {
+        self.prefix_topk_filtered(query, k, &|_| true)
+    }
+
+    /// Return the ids of the `k` best completions matching `query` like
+    /// `prefix_topk` whose ids are accepted by `filter`
+    fn prefix_topk_filtered(
+        &self,
+        query: &str,
+        k: usize,
+        filter: &dyn Fn(IdType) -> bool,
+    ) -> Vec<IdType>;
+
+    /// Return the ids of the `k` best completions containing every complete
+    /// term of `query` and a term prefixed by its last token, in any position
+    fn conjunctive_topk(&self, query: &str, k: usize) -> Vec<IdType> {
+        self.conjunctive_topk_filtered(query, k, &|_| true)
+    }
+
+    /// Return the ids of the `k` best completions matching `query` like
+    /// `conjunctive_topk` whose ids are accepted by `filter`
+    fn conjunctive_topk_filtered(
+        &self,
+        query: &str,
+        k: usize,
+        filter: &dyn Fn(IdType) -> bool,
+    ) -> Vec<IdType>;
+
+    /// Get the inclusive range of lexicographic positions of the completions
+    /// matching `query` as a prefix
+    fn locate_prefix(&self, query: &str) -> ValueRange;
+
+    /// Get the size in bytes
+    fn bytes(&self) -> usize;
+}
+
+/// Available `TopKIndex` implementations. Each holds the term dictionary
+/// and range minimum queries over the completions, plus its own structures.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IndexType {
+    /// Succinct completion trie for prefix queries, inverted and forward
+    /// indexes for conjunctive ones
+    Trie,
+    /// Front-coded completions for prefix queries, inverted index for
+    /// conjunctive ones
+    Inverted,
+    /// Front-coded completions for prefix queries, blocked inverted index
+    /// for conjunctive ones
+    #[default]
+    Blocked,
+}
+
+impl IndexType {
+    /// Build an index of this type over `(id, text, score)` triples
+    pub fn build<'a, I>(self, completions: I) -> Result<Arc<dyn TopKIndex>, String>
+    where
+        I: IntoIterator<Item = (IdType, &'a str, ScoreType)>,
+    {
+        Ok(match self {
+            IndexType::Trie => Arc::new(TrieTopKIndex::build(completions)?),
+            IndexType::Inverted => Arc::new(InvertedTopKIndex::build(completions)?),
+            IndexType::Blocked => Arc::new(BlockedTopKIndex::build(completions)?),
+        })
+    }
+}
+
+impl fmt::Display for IndexType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IndexType::Trie => "trie",
+            IndexType::Inverted => "inverted",
+            IndexType::Blocked => "blocked",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for IndexType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trie" => Ok(IndexType::Trie),
+            "inverted" => Ok(IndexType::Inverted),
+            "blocked" => Ok(IndexType::Blocked),
+            _ => Err(format!(
+                "unknown index type {:?}, expected one of trie, inverted, blocked",
+                s
+            )),
+        }
+    }
+}
+
+/// Resolve `query` and collect the ids of the `k` best completions accepted
+/// by `filter` among the doc ids yielded, in increasing order, by `docs` for
+/// the sorted complete terms of the query and the range of its last token
+fn conjunctive_topk<D, F>(
+    terms: &TermIndex,
+    query: &str,
+    k: usize,
+    filter: &dyn Fn(IdType) -> bool,
+    docs: F,
+) -> Vec<IdType>
+where
+    D: Iterator<Item = IdType>,
+    F: FnOnce(&[IdType], ValueRange) -> D,
+{
+    if k == 0 {
+        return Vec::new();
+    }
+    let Some((mut term_ids, range)) = terms.parse(query) else {
+        return Vec::new();
+    };
+    term_ids.sort_unstable();
+    term_ids.dedup();
+    docs(&term_ids, range)
+        .map(|doc_id| terms.id(doc_id))
+        .filter(|&id| filter(id))
+        .take(k)
+        .collect()
+}
+
+/// Get the range of term ids made of `term_id` alone
+fn term_range(term_id: IdType) -> ValueRange {
+    ValueRange {
+        begin: term_id as u64,
+        end: term_id as u64,
+    }
+}
+
+/// Build the front-coded dictionary of the completions in lexicographic
+/// order, locating the range of a prefix
+fn build_lex_completions(completions: &TermCompletions) -> Result<IntegerFcDictionary, String> {
+    IntegerFcDictionary::build(completions.lex_completions(), DEFAULT_BUCKET_SIZE)
+}
+
+/// Locate the completions matching `query` as a prefix, given the range of
+/// their distinct term id sequences found by `locate` from the complete
+/// terms of the query and the range of its last token
+fn locate_prefix<F>(terms: &TermIndex, query: &str, locate: F) -> ValueRange
+where
+    F: FnOnce(&[IdType], ValueRange) -> ValueRange,
+{
+    match terms.parse(query) {
+        Some((prefix, suffix_range)) => terms.doc_range(locate(&prefix, suffix_range)),
+        None => global::INVALID_RANGE,
+    }
+}
+
+/// Index locating the completions matching a prefix with a succinct
+/// completion trie over term ids. Conjunctive queries intersect the posting
+/// lists of their complete terms and check the last token against the
+/// forward index.
+#[derive(Clone, Default)]
+pub struct TrieTopKIndex {
+    terms: TermIndex,
+    trie: SuccinctCompletionTrie,
+    inverted: InvertedIndex,
+    forward: CompactForwardIndex,
+}
+
+impl TrieTopKIndex {
+    /// Build the index over `(id, text, score)` triples
+    pub fn build<'a, I>(completions: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = (IdType, &'a str, ScoreType)>,
+    {
+        let (terms, completions) = TermIndex::build(completions);
+        let params = Parameters::from_completions(completions.lex_completions(), terms.num_terms());
+        let mut builder = SuccinctCompletionTrieBuilder::with_parameters(&params);
+        for completion in completions.lex_completions() {
+            builder.push(completion)?;
+        }
+        let trie = builder.build();
+        let inverted = InvertedIndex::build(&completions.forward, terms.num_terms())?;
+        let forward = CompactForwardIndex::build(&completions.forward, terms.num_terms())?;
+        Ok(Self {
+            terms,
+            trie,
+            inverted,
+            forward,
+        })
+    }
+}
+
+impl TopKIndex for TrieTopKIndex {
+    fn prefix_topk_filtered(
+        &self,
+        query: &str,
+        k: usize,
+        filter: &dyn Fn(IdType) -> bool,
+    ) -> Vec<IdType> {
+        self.terms.topk_in_range_filtered(self.locate_prefix(query), k, filter)
+    }
+
+    fn conjunctive_topk_filtered(
+        &self,
+        query: &str,
+        k: usize,
+        filter: &dyn Fn(IdType) -> bool,
+    ) -> Vec<IdType> {
+        conjunctive_topk(&self.terms, query, k, filter, |term_ids, range| {
+            let docs: Box<dyn Iterator<Item = IdType> + '_> = if term_ids.is_empty() {
+                Box::new(self.inverted.union(range))
+            } else {
+                Box::new(
+                    self.inverted
+                        .intersection(term_ids)
+                        .filter(move |&doc_id| self.forward.contains_term_in_range(doc_id, range)),
+                )
+            };
+            docs
+        })
+    }
+
+    fn locate_prefix(&self, query: &str) -> ValueRange {
+        locate_prefix(&self.terms, query, |prefix, suffix_range| {
+            self.trie.locate_prefix(prefix, suffix_range)
+        })
+    }
+
+    fn bytes(&self) -> usize {
+        self.terms.bytes() + self.trie.bytes() + self.inverted.bytes() + self.forward.bytes()
+    }
+}
+
+/// Index locating the completions matching a prefix in a front-coded
+/// dictionary of their term ids. Conjunctive queries intersect the posting
+/// lists of their complete terms with the union of those of the terms
+/// prefixed by their last token.
+#[derive(Clone, Default)]
+pub struct InvertedTopKIndex {
+    terms: TermIndex,
+    lex_completions: IntegerFcDictionary,
+    inverted: InvertedIndex,
+}
+
+impl InvertedTopKIndex {
+    /// Build the index over `(id, text, score)` triples
+    pub fn build<'a, I>(completions: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = (IdType, &'a str, ScoreType)>,
+    {
+        let (terms, completions) = TermIndex::build(completions);
+        let inverted = InvertedIndex::build(&completions.forward, terms.num_terms())?;
+        Ok(Self {
+            lex_completions: build_lex_completions(&completions)?,
+            terms,
+            inverted,
+        })
+    }
+}
+
+impl TopKIndex for InvertedTopKIndex {
+    fn prefix_topk_filtered(
+        &self,
+        query: &str,
+        k: usize,
+        filter: &dyn Fn(IdType) -> bool,
+    ) -> Vec<IdType> {
+        self.terms.topk_in_range_filtered(self.locate_prefix(query), k, filter)
+    }
+
+    fn conjunctive_topk_filtered(
+        &self,
+        query: &str,
+        k: usize,
+        filter: &dyn Fn(IdType) -> bool,
+    ) -> Vec<IdType> {
+        conjunctive_topk(&self.terms, query, k, filter, |term_ids, range| {
+            let mut cursors: Vec<_> = term_ids
+                .iter()
+                .map(|&term_id| self.inverted.union(term_range(term_id)))
+                .collect();
+            cursors.push(self.inverted.union(range));
+            cursors.sort_by_key(UnionCursor::size);
+            IntersectionIterator::new(cursors)
+        })
+    }
+
+    fn locate_prefix(&self, query: &str) -> ValueRange {
+        locate_prefix(&self.terms, query, |prefix, suffix_range| {
+            self.lex_completions.locate_prefix(prefix, suffix_range)
+        })
+    }
+
+    fn bytes(&self) -> usize {
+        self.terms.bytes() + self.lex_completions.bytes() + self.inverted.bytes()
+    }
+}
+
+/// Index locating the completions matching a prefix in a front-coded
+/// dictionary of their term ids, answering conjunctive queries with a
+/// blocked inverted index only, which groups the posting lists of
+/// consecutive terms
+#[derive(Clone, Default)]
+pub struct BlockedTopKIndex {
+    terms: TermIndex,
+    lex_completions: IntegerFcDictionary,
+    blocked: BlockedInvertedIndex,
+}
+
+impl BlockedTopKIndex {
+    /// Build the index over `(id, text, score)` triples
+    pub fn build<'a, I>(completions: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = (IdType, &'a str, ScoreType)>,
+    {
+        let (terms, completions) = TermIndex::build(completions);
+        let blocked = BlockedInvertedIndex::build(
+            &completions.forward,
+            terms.num_terms(),
+            DEFAULT_BLOCK_FRACTION,
+        )?;
+        Ok(Self {
+            lex_completions: build_lex_completions(&completions)?,
+            terms,
+            blocked,
+        })
+    }
+}
+
+impl TopKIndex for BlockedTopKIndex {
+    fn prefix_topk_filtered(
+        &self,
+        query: &str,
+        k: usize,
+        filter: &dyn Fn(IdType) -> bool,
+    ) -> Vec<IdType> {
+        self.terms.topk_in_range_filtered(self.locate_prefix(query), k, filter)
+    }
+
+    fn conjunctive_topk_filtered(
+        &self,
+        query: &str,
+        k: usize,
+        filter: &dyn Fn(IdType) -> bool,
+    ) -> Vec<IdType> {
+        conjunctive_topk(&self.terms, query, k, filter, |term_ids, range| {
+            // The range of the last token is usually the longest list
+            let mut cursors: Vec<_> = term_ids
+                .iter()
+                .map(|&term_id| self.blocked.docs_in_range(term_range(term_id)))
+                .collect();
+            cursors.push(self.blocked.docs_in_range(range));
+            IntersectionIterator::new(cursors)
+        })
+    }
+
+    fn locate_prefix(&self, query: &str) -> ValueRange {
+        locate_prefix(&self.terms, query, |prefix, suffix_range| {
+            self.lex_completions.locate_prefix(prefix, suffix_range)
+        })
+    }
+
+    fn bytes(&self) -> usize {
+        self.terms.bytes() + self.lex_completions.bytes() + self.blocked.bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::term_index::{conjunctive_matches, prefix_matches};
+
+    const INDEX_TYPES: [IndexType; 3] = [IndexType::Trie, IndexType::Inverted, IndexType::Blocked];
+
+    fn sample(index_type: IndexType) -> Arc<dyn TopKIndex> {
+        index_type
+            .build(vec![
+                (0, "new york times", 0.9),
+                (1, "new york", 1.0),
+                (2, "york new tickets", 0.3),
+                (3, "new jersey", 0.7),
+                (4, "times square", 0.5),
+            ])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_prefix_topk() {
+        for index_type in INDEX_TYPES {
+            let index = sample(index_type);
+            assert_eq!(index.prefix_topk("new y", 10), vec![1, 0], "{}", index_type);
+            assert_eq!(index.prefix_topk("new ", 10), vec![1, 0, 3], "{}", index_type);
+            assert_eq!(index.prefix_topk("n", 2), vec![1, 0], "{}", index_type);
+            assert!(index.prefix_topk("york ti", 10).is_empty());
+            assert_eq!(index.locate_prefix("new ").len(), 3);
+            assert!(index.bytes() > 0);
+        }
+    }
+
+    #[test]
+    fn test_conjunctive_topk() {
+        for index_type in INDEX_TYPES {
+            let index = sample(index_type);
+            assert_eq!(index.conjunctive_topk("york new ti", 10), vec![0, 2], "{}", index_type);
+            assert_eq!(index.conjunctive_topk("new ", 10), vec![1, 0, 3, 2], "{}", index_type);
+            assert_eq!(index.conjunctive_topk("new ", 2), vec![1, 0], "{}", index_type);
+            assert_eq!(index.conjunctive_topk("ti", 10), vec![0, 4, 2], "{}", index_type);
+            assert!(index.conjunctive_topk("boston ti", 10).is_empty());
+            assert!(index.conjunctive_topk("new zz", 10).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_conjunctive_topk_filtered() {
+        for index_type in INDEX_TYPES {
+            let index = sample(index_type);
+            let odd = |id: IdType| id % 2 == 1;
+            let topk = |query, k| index.conjunctive_topk_filtered(query, k, &odd);
+            assert_eq!(topk("new ", 2), vec![1, 3], "{}", index_type);
+            assert_eq!(topk("york new ti", 10), Vec::<IdType>::new(), "{}", index_type);
+            assert_eq!(topk("ti", 10), Vec::<IdType>::new(), "{}", index_type);
+            assert_eq!(topk("ne", 10), vec![1, 3], "{}", index_type);
+        }
+    }
+
+    #[test]
+    fn test_duplicate_term_sequences() {
+        for index_type in INDEX_TYPES {
+            let index = index_type
+                .build(vec![(0, "new  york", 0.5), (1, "new york", 0.8), (2, "newark", 0.1)])
+                .unwrap();
+            let name = index_type.to_string();
+            assert_eq!(index.prefix_topk("new", 10), vec![1, 0, 2], "{}", name);
+            assert_eq!(index.prefix_topk("new ", 1), vec![1], "{}", name);
+            assert_eq!(index.conjunctive_topk("york", 10), vec![1, 0], "{}", name);
+            assert_eq!(index.locate_prefix("new york").len(), 2, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_variants_agree() {
+        let words = ["a", "ab", "abc", "b", "ba", "c", "ca", "cab"];
+        let texts: Vec<String> = (0..400usize)
+            .map(|i| {
+                let n = 1 + i % 4;
+                let terms: Vec<&str> =
+                    (0..n).map(|j| words[(i * 7 + j * 13 + i / 5) % words.len()]).collect();
+                terms.join(" ")
+            })
+            .collect();
+        let indexes: Vec<_> = INDEX_TYPES
+            .iter()
+            .map(|index_type| {
+                let score = |i: usize| (i % 37) as f32;
+                let completions = texts.iter().enumerate();
+                let completions =
+                    completions.map(|(i, text)| (i as IdType, text.as_str(), score(i)));
+                index_type.build(completions).unwrap()
+            })
+            .collect();
+        for query in ["a", "c", "ab ", "a b", "ca ab c", "b a ", "cab c", ""] {
+            for k in [1, 5, 50] {
+                let expected = indexes[0].conjunctive_topk(query, k);
+                for (index_type, index) in INDEX_TYPES.iter().zip(&indexes) {
+                    let topk = index.conjunctive_topk(query, k);
+                    assert_eq!(topk, expected, "{} {:?}", index_type, query);
+                    assert_eq!(index.prefix_topk(query, k), indexes[0].prefix_topk(query, k));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_match_predicates() {
+        let texts = ["a", "ab c", "", "c ab", "ab ab", "b  a c", "  ", "ca b"];
+        let completions = || texts.iter().enumerate().map(|(i, &text)| (i as IdType, text, 1.0));
+        for index_type in INDEX_TYPES {
+            let index = index_type.build(completions()).unwrap();
+            for query in ["a", "ab", "ab ", "c a", "b a ", "", " ", "ca b c", "x"] {
+                let sorted = |mut ids: Vec<IdType>| {
+                    ids.sort_unstable();
+                    ids
+                };
+                let matching = |matches: fn(&str, &str) -> bool| -> Vec<IdType> {
+                    completions()
+                        .filter(|&(_, text, _)| matches(text, query))
+                        .map(|(id, _, _)| id)
+                        .collect()
+                };
+                assert_eq!(sorted(index.prefix_topk(query, 10)), matching(prefix_matches));
+                assert_eq!(
+                    sorted(index.conjunctive_topk(query, 10)),
+                    matching(conjunctive_matches),
+                    "{} {:?}",
+                    index_type,
+                    query
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_blank_completions() {
+        for index_type in INDEX_TYPES {
+            let index = index_type.build(vec![(0, "", 1.0), (1, "new york", 0.5), (2, "  ", 0.9)]);
+            let index = index.unwrap();
+            assert_eq!(index.prefix_topk("", 10), vec![1], "{}", index_type);
+            assert_eq!(index.conjunctive_topk("", 10), vec![1], "{}", index_type);
+            assert_eq!(index.locate_prefix("").len(), 1, "{}", index_type);
+
+            let index = index_type.build(vec![(0, "", 1.0)]).unwrap();
+            assert!(index.prefix_topk("", 10).is_empty(), "{}", index_type);
+            assert!(index.conjunctive_topk("", 10).is_empty(), "{}", index_type);
+        }
+    }
+
+    #[test]
+    fn test_index_type_from_str() {
+        for index_type in INDEX_TYPES {
+            assert_eq!(index_type.to_string().parse::<IndexType>(), Ok(index_type));
+        }
+        assert!("fc".parse::<IndexType>().is_err());
+        assert_eq!(IndexType::default(), IndexType::Blocked);
+    }
+}
@@ -1,117 +1,753 @@
-use std::collections::HashMap;
-use crate::types::IdType;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use crate::constants::FUZZY_EDIT_DISCOUNT;
+use crate::types::{global, IdType};
 
-#[derive(Default, Clone)]
+/// Position of a node in the arena of a `Trie`
+type NodeId = u32;
+
+const ROOT: NodeId = 0;
+
+#[derive(Clone)]
 struct TrieNode {
-    children: HashMap<char, Box<TrieNode>>,
-    id: Option<IdType>,
-    score: f32,
+    /// Parent of the node, the root being its own parent
+    parent: NodeId,
+    /// Start, in the label arena, of the labels on the edge from the parent
+    label_start: u32,
+    /// Number of labels on the edge from the parent, more than one when a
+    /// chain of single-child nodes is compressed into the edge
+    label_len: u32,
+    /// Children sorted by the first label of their edge
+    children: Vec<(char, NodeId)>,
+    /// Ids and scores of the completions ending at this node, sorted by
+    /// decreasing score, then by id
+    entries: Vec<(IdType, f32)>,
+    /// Highest score of any completion in the subtree rooted at this node
+    max_score: f32,
 }
 
 impl TrieNode {
-    fn new() -> Self {
+    fn new(parent: NodeId, label_start: usize, label_len: usize) -> Self {
         Self {
-            children: HashMap::new(),
-            id: None,
-            score: 0.0,
+            parent,
+            label_start: label_start as u32,
+            label_len: label_len as u32,
+            children: Vec::new(),
+            entries: Vec::new(),
+            max_score: f32::NEG_INFINITY,
         }
     }
 
     fn is_terminal(&self) -> bool {
-        self.id.is_some()
+        !self.entries.is_empty()
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.is_terminal() && self.children.is_empty()
+    }
+
+    fn labels(&self) -> std::ops::Range<usize> {
+        let start = self.label_start as usize;
+        start..start + self.label_len as usize
+    }
+}
+
+/// Candidate in the best-first top-k traversal: either the subtree rooted
+/// at a node, with the score of its best completion, or one of the
+/// completions ending at a node once it has been reached. Fuzzy matches are
+/// ranked by their score discounted for their edits.
+#[derive(Clone, Copy)]
+struct Candidate<'a> {
+    trie: &'a Trie,
+    node: NodeId,
+    score: f32,
+    edits: u32,
+    /// Position of the completion among the entries of the node, `None`
+    /// for the subtree
+    entry: Option<usize>,
+}
+
+impl<'a> Candidate<'a> {
+    fn node(trie: &'a Trie, node: NodeId, edits: u32) -> Self {
+        let score = discount(trie.nodes[node as usize].max_score, edits);
+        Self { trie, node, score, edits, entry: None }
+    }
+
+    fn completion(trie: &'a Trie, node: NodeId, entry: usize, edits: u32) -> Self {
+        let score = discount(trie.nodes[node as usize].entries[entry].1, edits);
+        Self { trie, node, score, edits, entry: Some(entry) }
+    }
+
+    /// Get the completion after this one at the same node, if any
+    fn next_entry(&self) -> Option<Self> {
+        let next = self.entry? + 1;
+        (next < self.trie.nodes[self.node as usize].entries.len())
+            .then(|| Self::completion(self.trie, self.node, next, self.edits))
+    }
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
     }
 }
 
+impl Eq for Candidate<'_> {}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Higher scores first, then lexicographically smaller paths. A
+        // subtree's path precedes the paths of all its completions, so ties
+        // are emitted in lexicographic order. Completions ending at the same
+        // node are already sorted.
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| self.trie.cmp_paths(other.node, self.node))
+            .then_with(|| other.entry.cmp(&self.entry))
+    }
+}
+
+/// Path-compressed trie over the characters of completions.
+///
+/// Nodes live in a single arena and refer to each other by position, and the
+/// labels of all edges are stored contiguously in a second arena. Children
+/// are kept sorted by the first label of their edge and searched linearly
+/// up to `global::LINEAR_SCAN_THRESHOLD` children, by binary search beyond.
+/// Chains of non-terminal single-child nodes are compressed into one edge.
 #[derive(Clone)]
 pub struct Trie {
-    root: TrieNode,
+    nodes: Vec<TrieNode>,
+    labels: Vec<char>,
+    /// Arena positions of removed nodes, reused by later insertions
+    free: Vec<NodeId>,
+    /// Number of labels referenced by an edge, the rest of the label arena
+    /// being left over by splits and merges
+    live_labels: usize,
 }
 
 impl Trie {
     pub fn new() -> Self {
         Self {
-            root: TrieNode::new(),
+            nodes: vec![TrieNode::new(ROOT, 0, 0)],
+            labels: Vec::new(),
+            free: Vec::new(),
+            live_labels: 0,
         }
     }
 
+    /// Insert `completion` with the given id, or replace its score if it is
+    /// already in the trie with that id. Completions with different ids are
+    /// kept apart even if their strings are equal.
     pub fn insert(&mut self, completion: &str, id: IdType, score: f32) {
-        let mut current = &mut self.root;
-        let chars: Vec<char> = completion.chars().collect();
-        
-        for &c in &chars {
-            current = current.children
-                .entry(c)
-                .or_insert_with(|| Box::new(TrieNode::new()));
+        let completion: Vec<char> = completion.chars().collect();
+        let mut path = vec![ROOT];
+        let mut node = ROOT;
+        let mut i = 0;
+        while i < completion.len() {
+            match self.child_pos(node, completion[i]) {
+                Ok(pos) => {
+                    let mut child = self.nodes[node as usize].children[pos].1;
+                    let common = lcp(self.edge(child), &completion[i..]);
+                    if common < self.nodes[child as usize].label_len as usize {
+                        child = self.split(child, common);
+                        self.nodes[node as usize].children[pos].1 = child;
+                    }
+                    node = child;
+                    i += common;
+                }
+                Err(pos) => {
+                    let start = self.labels.len();
+                    self.labels.extend_from_slice(&completion[i..]);
+                    let leaf = self.alloc(TrieNode::new(node, start, completion.len() - i));
+                    self.nodes[node as usize].children.insert(pos, (completion[i], leaf));
+                    node = leaf;
+                    i = completion.len();
+                }
+            }
+            path.push(node);
         }
-        
-        current.id = Some(id);
-        current.score = score;
+
+        let entries = &mut self.nodes[node as usize].entries;
+        entries.retain(|&(other, _)| other != id);
+        let pos = entries
+            .partition_point(|&(other, s)| s.total_cmp(&score).then(id.cmp(&other)).is_gt());
+        entries.reserve_exact(1);
+        entries.insert(pos, (id, score));
+        self.update_max_scores(&path);
     }
 
+    /// Remove `completion` under every id, returning whether it was in the
+    /// trie
     pub fn remove(&mut self, completion: &str) -> bool {
-        let mut path = Vec::new();
-        let mut current = &mut self.root;
-        
-        // First pass: find the path to the node
-        for c in completion.chars() {
-            if let Some(next) = current.children.get_mut(&c) {
-                path.push(c);
-                current = next;
-            } else {
+        self.remove_entries(completion, |_| true)
+    }
+
+    /// Remove `completion` with the given id, returning whether it was in
+    /// the trie with that id. Other completions with the same string stay.
+    pub fn remove_id(&mut self, completion: &str, id: IdType) -> bool {
+        self.remove_entries(completion, |other| other == id)
+    }
+
+    /// Remove the entries of `completion` whose ids match, returning whether
+    /// there were any
+    fn remove_entries<F>(&mut self, completion: &str, matches: F) -> bool
+    where
+        F: Fn(IdType) -> bool,
+    {
+        let completion: Vec<char> = completion.chars().collect();
+        let mut path = vec![ROOT];
+        let mut node = ROOT;
+        let mut i = 0;
+        while i < completion.len() {
+            let Some(child) = self.child(node, completion[i]) else {
+                return false; // String not found
+            };
+            let edge = self.edge(child);
+            if !completion[i..].starts_with(edge) {
                 return false; // String not found
             }
+            i += edge.len();
+            node = child;
+            path.push(node);
         }
-        
-        // If the node is not a terminal, the string wasn't in the trie
-        if !current.is_terminal() {
-            return false;
+        let entries = &mut self.nodes[node as usize].entries;
+        let len = entries.len();
+        entries.retain(|&(other, _)| !matches(other));
+        if entries.len() == len {
+            return false; // String not found with a matching id
         }
-        
-        // Remove the terminal marker
-        current.id = None;
-        current.score = 0.0;
-        
-        // Second pass: remove empty nodes
-        let mut current = &mut self.root;
-        for &c in &path[..path.len()-1] {
-            current = current.children.get_mut(&c).unwrap();
-        }
-        
-        // Remove the last node if it's empty
-        if current.children.is_empty() && !current.is_terminal() {
-            current.children.remove(&path[path.len()-1]);
+
+        // Restore path compression around the node once no completion ends
+        // there
+        if node != ROOT && !self.nodes[node as usize].is_terminal() {
+            match self.nodes[node as usize].children.len() {
+                0 => {
+                    path.pop();
+                    let parent = *path.last().expect("the root is on the path");
+                    let label = self.edge(node)[0];
+                    let pos = self
+                        .child_pos(parent, label)
+                        .expect("node is a child of its parent");
+                    self.nodes[parent as usize].children.remove(pos);
+                    self.release(node);
+                    let parent_node = &self.nodes[parent as usize];
+                    if parent != ROOT
+                        && !parent_node.is_terminal()
+                        && parent_node.children.len() == 1
+                    {
+                        self.merge_with_child(parent);
+                    }
+                }
+                1 => self.merge_with_child(node),
+                _ => {}
+            }
         }
-        
+        self.update_max_scores(&path);
+        self.compact_labels_if_sparse();
         true
     }
 
+    /// Return all the completions of `prefix`, in lexicographic order
     pub fn complete(&self, prefix: &str) -> Vec<(IdType, f32)> {
-        let mut current = &self.root;
-        
-        // Navigate to the prefix node
-        for c in prefix.chars() {
-            if let Some(next) = current.children.get(&c) {
-                current = next;
-            } else {
-                return Vec::new(); // Prefix not found
-            }
-        }
-        
+        let chars: Vec<char> = prefix.chars().collect();
+        let Some(current) = self.find(&chars) else {
+            return Vec::new(); // Prefix not found
+        };
+
         // Collect all completions from this node
         let mut results = Vec::new();
         self.collect_completions(current, &mut results);
         results
     }
 
-    fn collect_completions(&self, node: &TrieNode, results: &mut Vec<(IdType, f32)>) {
-        if let Some(id) = node.id {
-            results.push((id, node.score));
+    /// Return the `k` highest-scoring completions of `prefix`, best first,
+    /// breaking ties by lexicographic order.
+    ///
+    /// Subtrees are expanded in order of their maximum score, so a branch is
+    /// never visited once `k` completions scoring at least as high have been
+    /// found.
+    pub fn topk(&self, prefix: &str, k: usize) -> Vec<(IdType, f32)> {
+        self.topk_filtered(prefix, k, |_| true)
+    }
+
+    /// Return the `k` highest-scoring completions of `prefix` whose ids are
+    /// accepted by `filter`, best first, breaking ties by lexicographic
+    /// order. Rejected completions are skipped during the search, so up to
+    /// `k` accepted ones are still found.
+    pub fn topk_filtered<F>(&self, prefix: &str, k: usize, filter: F) -> Vec<(IdType, f32)>
+    where
+        F: Fn(IdType) -> bool,
+    {
+        let prefix: Vec<char> = prefix.chars().collect();
+        let Some(start) = self.find(&prefix) else {
+            return Vec::new(); // Prefix not found
+        };
+        self.best_first(std::iter::once(start), k, filter)
+            .into_iter()
+            .map(|(id, score, _)| (id, score))
+            .collect()
+    }
+
+    /// Get the number of nodes, the root included
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len() - self.free.len()
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.nodes.capacity() * std::mem::size_of::<TrieNode>()
+            + self
+                .nodes
+                .iter()
+                .map(|node| {
+                    node.children.capacity() * std::mem::size_of::<(char, NodeId)>()
+                        + node.entries.capacity() * std::mem::size_of::<(IdType, f32)>()
+                })
+                .sum::<usize>()
+            + self.labels.capacity() * std::mem::size_of::<char>()
+            + self.free.capacity() * std::mem::size_of::<NodeId>()
+    }
+
+    /// Return the `k` best completions starting with a string within
+    /// `max_edits` edits of `prefix`, as `(id, score, edits)`.
+    ///
+    /// Edits are insertions, deletions and substitutions of a character, and
+    /// transpositions of two adjacent characters. Completions are ranked by
+    /// their score times `FUZZY_EDIT_DISCOUNT` per edit (divided by it for
+    /// negative scores), then lexicographically. The trie is walked with the
+    /// rows of the edit distance matrix, and a branch is abandoned as soon as
+    /// none of its extensions can come within `max_edits` of `prefix`, or
+    /// its best completion cannot rank among the `k` best found so far.
+    pub fn fuzzy_topk(&self, prefix: &str, max_edits: u32, k: usize) -> Vec<(IdType, f32, u32)> {
+        self.fuzzy_topk_filtered(prefix, max_edits, k, |_| true)
+    }
+
+    /// Return the `k` best completions starting with a string within
+    /// `max_edits` edits of `prefix` whose ids are accepted by `filter`, as
+    /// `(id, score, edits)`
+    pub fn fuzzy_topk_filtered<F>(
+        &self,
+        prefix: &str,
+        max_edits: u32,
+        k: usize,
+        filter: F,
+    ) -> Vec<(IdType, f32, u32)>
+    where
+        F: Fn(IdType) -> bool,
+    {
+        if k == 0 {
+            return Vec::new();
         }
-        
-        for child in node.children.values() {
+
+        let prefix: Vec<char> = prefix.chars().collect();
+        let mut walk = FuzzyWalk::new(&prefix, max_edits, k);
+        self.fuzzy_visit(ROOT, prefix.len() as u32, &mut walk, &filter);
+        self.drain_best_first(walk.matches, k, &filter)
+    }
+
+    /// Collect the matches below `node`, whose path is on `walk`, given the
+    /// fewest edits between the query and a prefix of that path
+    fn fuzzy_visit<'a, F>(
+        &'a self,
+        node: NodeId,
+        edits: u32,
+        walk: &mut FuzzyWalk<'_, 'a>,
+        filter: &F,
+    ) where
+        F: Fn(IdType) -> bool,
+    {
+        let current = &self.nodes[node as usize];
+        if current.is_terminal() && edits <= walk.max_edits {
+            for (entry, &(id, _)) in current.entries.iter().enumerate() {
+                walk.push_best(Candidate::completion(self, node, entry, edits), filter(id));
+            }
+            walk.matches.push(Candidate::completion(self, node, 0, edits));
+        }
+
+        // Completions below have at least as many edits as the closest
+        // prefix of the query to the path, or as the path itself
+        let fewest_edits = edits.min(walk.closest());
+        for &(_, child) in &current.children {
+            if walk.is_pruned(&Candidate::node(self, child, fewest_edits)) {
+                continue;
+            }
+            let depth = walk.path.len();
+            let mut child_edits = edits;
+            let mut reachable = true;
+            for &label in self.edge(child) {
+                let (last, min) = walk.push(label);
+                child_edits = child_edits.min(last);
+                if min > walk.max_edits {
+                    reachable = false;
+                    break;
+                }
+            }
+
+            if reachable {
+                self.fuzzy_visit(child, child_edits, walk, filter);
+            } else if child_edits <= walk.max_edits {
+                // Every completion below matches with the edits found so far
+                let candidate = Candidate::node(self, child, child_edits);
+                if !walk.is_pruned(&candidate) {
+                    walk.matches.push(candidate);
+                }
+            }
+            walk.truncate(depth);
+        }
+    }
+
+    fn best_first<I, F>(&self, roots: I, k: usize, filter: F) -> Vec<(IdType, f32, u32)>
+    where
+        I: IntoIterator<Item = NodeId>,
+        F: Fn(IdType) -> bool,
+    {
+        let queue: BinaryHeap<_> = roots
+            .into_iter()
+            .filter(|&node| !self.nodes[node as usize].is_empty())
+            .map(|node| Candidate::node(self, node, 0))
+            .collect();
+        self.drain_best_first(queue, k, filter)
+    }
+
+    /// Pop the `k` best completions accepted by `filter` from `queue`, as
+    /// `(id, score, edits)`, expanding subtrees as they come first
+    fn drain_best_first<'a, F>(
+        &'a self,
+        mut queue: BinaryHeap<Candidate<'a>>,
+        k: usize,
+        filter: F,
+    ) -> Vec<(IdType, f32, u32)>
+    where
+        F: Fn(IdType) -> bool,
+    {
+        let mut results = Vec::with_capacity(k);
+        if k == 0 {
+            return results;
+        }
+
+        while let Some(candidate) = queue.pop() {
+            let node = &self.nodes[candidate.node as usize];
+            let edits = candidate.edits;
+            match candidate.entry {
+                Some(entry) => {
+                    // The next completion at the node ranks right below
+                    queue.extend(candidate.next_entry());
+                    let (id, score) = node.entries[entry];
+                    if !filter(id) {
+                        continue;
+                    }
+                    results.push((id, score, edits));
+                    if results.len() == k {
+                        break;
+                    }
+                }
+                None => {
+                    let children = node.children.iter();
+                    queue.extend(children.map(|&(_, child)| Candidate::node(self, child, edits)));
+                    if node.is_terminal() {
+                        queue.push(Candidate::completion(self, candidate.node, 0, edits));
+                    }
+                }
+            }
+        }
+        results
+    }
+
+    /// Get the node whose subtree holds the completions starting with
+    /// `prefix`
+    fn find(&self, prefix: &[char]) -> Option<NodeId> {
+        let mut node = ROOT;
+        let mut i = 0;
+        while i < prefix.len() {
+            let child = self.child(node, prefix[i])?;
+            let edge = self.edge(child);
+            let common = lcp(edge, &prefix[i..]);
+            if common < edge.len() {
+                // The prefix must end inside the edge
+                return (i + common == prefix.len()).then_some(child);
+            }
+            node = child;
+            i += common;
+        }
+        Some(node)
+    }
+
+    fn collect_completions(&self, node: NodeId, results: &mut Vec<(IdType, f32)>) {
+        let node = &self.nodes[node as usize];
+        results.extend_from_slice(&node.entries);
+
+        for &(_, child) in &node.children {
             self.collect_completions(child, results);
         }
     }
+
+    /// Compare the paths from the root to `a` and to `b` lexicographically,
+    /// by walking up to their lowest common ancestor
+    fn cmp_paths(&self, mut a: NodeId, mut b: NodeId) -> Ordering {
+        let (depth_a, depth_b) = (self.depth(a), self.depth(b));
+        for _ in depth_b..depth_a {
+            a = self.nodes[a as usize].parent;
+        }
+        for _ in depth_a..depth_b {
+            b = self.nodes[b as usize].parent;
+        }
+        if a == b {
+            // One path is a prefix of the other
+            return depth_a.cmp(&depth_b);
+        }
+        loop {
+            let parent_a = self.nodes[a as usize].parent;
+            let parent_b = self.nodes[b as usize].parent;
+            if parent_a == parent_b {
+                // Siblings differ in the first label of their edge
+                return self.edge(a)[0].cmp(&self.edge(b)[0]);
+            }
+            a = parent_a;
+            b = parent_b;
+        }
+    }
+
+    /// Get the number of edges between the root and `node`
+    fn depth(&self, mut node: NodeId) -> usize {
+        let mut depth = 0;
+        while node != ROOT {
+            node = self.nodes[node as usize].parent;
+            depth += 1;
+        }
+        depth
+    }
+
+    fn edge(&self, node: NodeId) -> &[char] {
+        &self.labels[self.nodes[node as usize].labels()]
+    }
+
+    fn child(&self, node: NodeId, label: char) -> Option<NodeId> {
+        let pos = self.child_pos(node, label).ok()?;
+        Some(self.nodes[node as usize].children[pos].1)
+    }
+
+    /// Search the children of `node` for the one whose edge starts with
+    /// `label`, returning its position or the one to insert it at
+    fn child_pos(&self, node: NodeId, label: char) -> Result<usize, usize> {
+        let children = &self.nodes[node as usize].children;
+        if children.len() as u64 > global::LINEAR_SCAN_THRESHOLD {
+            return children.binary_search_by(|(l, _)| l.cmp(&label));
+        }
+        for (pos, (l, _)) in children.iter().enumerate() {
+            match l.cmp(&label) {
+                Ordering::Less => continue,
+                Ordering::Equal => return Ok(pos),
+                Ordering::Greater => return Err(pos),
+            }
+        }
+        Err(children.len())
+    }
+
+    /// Split the edge to `node` after its first `len` labels, returning the
+    /// new node in between
+    fn split(&mut self, node: NodeId, len: usize) -> NodeId {
+        let start = self.nodes[node as usize].label_start as usize;
+        let mut mid = TrieNode::new(self.nodes[node as usize].parent, start, len);
+        mid.children.push((self.labels[start + len], node));
+        mid.max_score = self.nodes[node as usize].max_score;
+
+        let lower = &mut self.nodes[node as usize];
+        lower.label_start += len as u32;
+        lower.label_len -= len as u32;
+        self.live_labels -= len;
+        let mid = self.alloc(mid);
+        self.nodes[node as usize].parent = mid;
+        mid
+    }
+
+    /// Merge the only child of `node` into it, appending the child's edge to
+    /// the edge of `node`
+    fn merge_with_child(&mut self, node: NodeId) {
+        let child = self.nodes[node as usize].children[0].1;
+        let start = self.labels.len();
+        self.labels.extend_from_within(self.nodes[node as usize].labels());
+        self.labels.extend_from_within(self.nodes[child as usize].labels());
+        let len = self.labels.len() - start;
+
+        let removed =
+            std::mem::replace(&mut self.nodes[child as usize], TrieNode::new(ROOT, 0, 0));
+        for &(_, grandchild) in &removed.children {
+            self.nodes[grandchild as usize].parent = node;
+        }
+        let merged = &mut self.nodes[node as usize];
+        self.live_labels += len - merged.label_len as usize - removed.label_len as usize;
+        merged.label_start = start as u32;
+        merged.label_len = len as u32;
+        merged.children = removed.children;
+        merged.entries = removed.entries;
+        merged.max_score = removed.max_score;
+        self.free.push(child);
+    }
+
+    fn alloc(&mut self, node: TrieNode) -> NodeId {
+        self.live_labels += node.label_len as usize;
+        match self.free.pop() {
+            Some(id) => {
+                self.nodes[id as usize] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                (self.nodes.len() - 1) as NodeId
+            }
+        }
+    }
+
+    fn release(&mut self, node: NodeId) {
+        self.live_labels -= self.nodes[node as usize].label_len as usize;
+        self.nodes[node as usize] = TrieNode::new(ROOT, 0, 0);
+        self.free.push(node);
+    }
+
+    /// Recompute `max_score` of the nodes of `path`, from the deepest up
+    fn update_max_scores(&mut self, path: &[NodeId]) {
+        for &node in path.iter().rev() {
+            let current = &self.nodes[node as usize];
+            let own = current.entries.first().map_or(f32::NEG_INFINITY, |&(_, score)| score);
+            let max_score = current
+                .children
+                .iter()
+                .map(|&(_, child)| self.nodes[child as usize].max_score)
+                .fold(own, f32::max);
+            self.nodes[node as usize].max_score = max_score;
+        }
+    }
+
+    /// Rewrite the label arena once most of it is no longer referenced
+    fn compact_labels_if_sparse(&mut self) {
+        if self.labels.len() <= 2 * self.live_labels.max(64) {
+            return;
+        }
+        let mut labels = Vec::with_capacity(self.live_labels);
+        for node in &mut self.nodes {
+            let start = labels.len();
+            labels.extend_from_slice(&self.labels[node.labels()]);
+            node.label_start = start as u32;
+        }
+        self.labels = labels;
+    }
+}
+
+/// State of a fuzzy search: the path from the root to the current node and
+/// the matching rows of the edit distance matrix against the query
+struct FuzzyWalk<'q, 't> {
+    query: &'q [char],
+    max_edits: u32,
+    k: usize,
+    path: Vec<char>,
+    /// One row of `query.len() + 1` distances per label of `path`, after the
+    /// row of the empty path
+    rows: Vec<u32>,
+    /// Completions and subtrees matching the query
+    matches: BinaryHeap<Candidate<'t>>,
+    /// The `k` best accepted completions found so far, worst first
+    best: BinaryHeap<Reverse<Candidate<'t>>>,
+}
+
+impl<'q, 't> FuzzyWalk<'q, 't> {
+    fn new(query: &'q [char], max_edits: u32, k: usize) -> Self {
+        Self {
+            query,
+            max_edits,
+            k,
+            path: Vec::new(),
+            rows: (0..=query.len() as u32).collect(),
+            matches: BinaryHeap::new(),
+            best: BinaryHeap::with_capacity(k + 1),
+        }
+    }
+
+    /// Count a matching completion among the best ones found so far if it
+    /// is `accepted` by the filter of the search
+    fn push_best(&mut self, candidate: Candidate<'t>, accepted: bool) {
+        if accepted {
+            self.best.push(Reverse(candidate));
+            if self.best.len() > self.k {
+                self.best.pop();
+            }
+        }
+    }
+
+    /// Check if `candidate` ranks below the `k` best completions found so
+    /// far, so that it cannot be returned
+    fn is_pruned(&self, candidate: &Candidate<'t>) -> bool {
+        self.best.len() == self.k
+            && self.best.peek().is_some_and(|Reverse(worst)| candidate < worst)
+    }
+
+    /// Get the smallest distance between a prefix of the query and the path
+    fn closest(&self) -> u32 {
+        let width = self.query.len() + 1;
+        self.rows[self.rows.len() - width..].iter().copied().min().unwrap_or(0)
+    }
+
+    /// Append `label` to the path, returning the distance between the query
+    /// and the path, and the smallest distance between a prefix of the query
+    /// and the path
+    fn push(&mut self, label: char) -> (u32, u32) {
+        let width = self.query.len() + 1;
+        self.path.push(label);
+        let j = self.path.len();
+        let prev = (j - 1) * width;
+        let curr = j * width;
+
+        let mut min = j as u32;
+        self.rows.push(j as u32);
+        for i in 1..width {
+            let substitution = self.rows[prev + i - 1] + (self.query[i - 1] != label) as u32;
+            let deletion = self.rows[prev + i] + 1;
+            let insertion = self.rows[curr + i - 1] + 1;
+            let mut distance = substitution.min(deletion).min(insertion);
+            let transposed = i > 1
+                && j > 1
+                && self.query[i - 1] == self.path[j - 2]
+                && self.query[i - 2] == label;
+            if transposed {
+                distance = distance.min(self.rows[(j - 2) * width + i - 2] + 1);
+            }
+            min = min.min(distance);
+            self.rows.push(distance);
+        }
+        (self.rows[curr + width - 1], min)
+    }
+
+    /// Go back to the first `depth` labels of the path
+    fn truncate(&mut self, depth: usize) {
+        self.path.truncate(depth);
+        self.rows.truncate((depth + 1) * (self.query.len() + 1));
+    }
+}
+
+impl Default for Trie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Discount `score` for `edits` edits, by `FUZZY_EDIT_DISCOUNT` per edit
+fn discount(score: f32, edits: u32) -> f32 {
+    let factor = FUZZY_EDIT_DISCOUNT.powi(edits as i32);
+    if score < 0.0 {
+        score / factor
+    } else {
+        score * factor
+    }
+}
+
+fn lcp(a: &[char], b: &[char]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
 }
 
 #[cfg(test)]
@@ -124,7 +760,7 @@ mod tests {
         trie.insert("hello", 1, 1.0);
         trie.insert("help", 2, 0.8);
         trie.insert("world", 3, 0.5);
-        
+
         let completions = trie.complete("hel");
         assert_eq!(completions.len(), 2);
         assert!(completions.contains(&(1, 1.0)));
@@ -136,12 +772,316 @@ mod tests {
         let mut trie = Trie::new();
         trie.insert("hello", 1, 1.0);
         trie.insert("help", 2, 0.8);
-        
-        assert!(trie.remove("hello"));
+
+        assert!(!trie.remove_id("hello", 2)); // Not with this id
+        assert!(trie.remove_id("hello", 1));
         assert!(!trie.remove("hello")); // Already removed
         assert!(trie.remove("help"));
-        
+
         let completions = trie.complete("hel");
         assert_eq!(completions.len(), 0);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_trie_topk() {
+        let mut trie = Trie::new();
+        trie.insert("hell", 1, 0.6);
+        trie.insert("hello", 2, 1.0);
+        trie.insert("help", 3, 0.8);
+        trie.insert("helping", 4, 0.9);
+        trie.insert("world", 5, 2.0);
+
+        assert_eq!(trie.topk("hel", 2), vec![(2, 1.0), (4, 0.9)]);
+        assert_eq!(
+            trie.topk("hel", 10),
+            vec![(2, 1.0), (4, 0.9), (3, 0.8), (1, 0.6)]
+        );
+        assert_eq!(trie.topk("", 1), vec![(5, 2.0)]);
+        assert!(trie.topk("hel", 0).is_empty());
+        assert!(trie.topk("xyz", 5).is_empty());
+    }
+
+    #[test]
+    fn test_trie_topk_filtered() {
+        let mut trie = Trie::new();
+        trie.insert("hell", 1, 0.6);
+        trie.insert("hello", 2, 1.0);
+        trie.insert("help", 3, 0.8);
+        trie.insert("helping", 4, 0.9);
+
+        // k accepted completions are found below the rejected best ones
+        assert_eq!(trie.topk_filtered("hel", 2, |id| id % 2 == 1), vec![(3, 0.8), (1, 0.6)]);
+        assert!(trie.topk_filtered("hel", 2, |_| false).is_empty());
+        assert_eq!(
+            trie.fuzzy_topk_filtered("halp", 1, 10, |id| id != 3),
+            vec![(4, 0.9, 1)]
+        );
+    }
+
+    #[test]
+    fn test_trie_topk_after_update_and_remove() {
+        let mut trie = Trie::new();
+        trie.insert("hello", 1, 1.0);
+        trie.insert("help", 2, 0.8);
+
+        // Lowering a score must lower the subtree maximum as well
+        trie.insert("hello", 1, 0.1);
+        assert_eq!(trie.topk("hel", 1), vec![(2, 0.8)]);
+
+        assert!(trie.remove("help"));
+        assert_eq!(trie.topk("hel", 5), vec![(1, 0.1)]);
+        assert!(trie.remove("hello"));
+        assert!(trie.topk("", 5).is_empty());
+    }
+
+    #[test]
+    fn test_trie_topk_breaks_ties_lexicographically() {
+        let mut trie = Trie::new();
+        trie.insert("help", 1, 0.5);
+        trie.insert("hello", 2, 0.5);
+        trie.insert("helicopter", 3, 0.9);
+        trie.insert("hell", 4, 0.5);
+        trie.insert("helm", 5, 0.5);
+
+        assert_eq!(trie.topk("hel", 3), vec![(3, 0.9), (4, 0.5), (2, 0.5)]);
+        assert_eq!(trie.topk("hel", 5), vec![(3, 0.9), (4, 0.5), (2, 0.5), (5, 0.5), (1, 0.5)]);
+        assert_eq!(trie.topk("hell", 2), vec![(4, 0.5), (2, 0.5)]);
+    }
+
+    #[test]
+    fn test_trie_ties_after_splits_and_merges() {
+        let mut trie = Trie::new();
+        let mut reference = std::collections::BTreeMap::new();
+        let mut state = 3u64;
+        for round in 0..2000u32 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let len = 1 + (state >> 60) as usize % 5;
+            let completion: String = (0..len)
+                .map(|i| (b'a' + ((state >> (8 * i + 8)) % 3) as u8) as char)
+                .collect();
+            if round % 3 == 2 {
+                let removed = reference.remove(&completion);
+                let id = removed.map_or(round, |(id, _)| id);
+                assert_eq!(trie.remove_id(&completion, id), removed.is_some());
+            } else {
+                // Few distinct scores, so most results are ranked by their text
+                let score = ((state >> 40) % 3) as f32;
+                let id = reference.get(&completion).map_or(round, |&(id, _)| id);
+                trie.insert(&completion, id, score);
+                reference.insert(completion, (id, score));
+            }
+        }
+
+        for prefix in ["", "a", "ba", "cab"] {
+            let mut expected: Vec<(&String, &(IdType, f32))> =
+                reference.iter().filter(|(c, _)| c.starts_with(prefix)).collect();
+            expected.sort_by(|a, b| b.1 .1.total_cmp(&a.1 .1).then(a.0.cmp(b.0)));
+            let expected: Vec<(IdType, f32)> =
+                expected.into_iter().take(30).map(|(_, &v)| v).collect();
+            assert_eq!(trie.topk(prefix, 30), expected, "{}", prefix);
+        }
+    }
+
+    #[test]
+    fn test_trie_equal_strings_with_different_ids() {
+        let mut trie = Trie::new();
+        trie.insert("cafe", 1, 0.5);
+        trie.insert("cafe", 2, 1.0);
+        trie.insert("cafe", 3, 0.5);
+        trie.insert("cafes", 4, 0.7);
+
+        // Each id is a completion, ties broken by id
+        assert_eq!(trie.topk("caf", 10), vec![(2, 1.0), (4, 0.7), (1, 0.5), (3, 0.5)]);
+        assert_eq!(trie.topk("caf", 2), vec![(2, 1.0), (4, 0.7)]);
+        assert_eq!(trie.topk_filtered("caf", 2, |id| id != 2), vec![(4, 0.7), (1, 0.5)]);
+        assert_eq!(trie.complete("cafe").len(), 4);
+        assert_eq!(
+            trie.fuzzy_topk("cafr", 1, 3),
+            vec![(2, 1.0, 1), (4, 0.7, 1), (1, 0.5, 1)]
+        );
+
+        // Updating the score of one id leaves the others
+        trie.insert("cafe", 3, 2.0);
+        assert_eq!(trie.topk("cafe", 2), vec![(3, 2.0), (2, 1.0)]);
+        assert!(trie.remove_id("cafe", 3));
+        assert!(trie.remove_id("cafe", 2));
+        assert_eq!(trie.topk("cafe", 10), vec![(4, 0.7), (1, 0.5)]);
+        // Removing the string removes it under every remaining id
+        assert!(trie.remove("cafe"));
+        assert_eq!(trie.topk("cafe", 10), vec![(4, 0.7)]);
+    }
+
+    #[test]
+    fn test_trie_path_compression() {
+        let mut trie = Trie::new();
+        trie.insert("hello", 1, 1.0);
+        // The root and a single edge holding the whole string
+        assert_eq!(trie.num_nodes(), 2);
+
+        trie.insert("help", 2, 0.8);
+        trie.insert("hel", 3, 0.5);
+        // Splitting "hello" gives the root, "hel", "lo" and "p"
+        assert_eq!(trie.num_nodes(), 4);
+        assert_eq!(trie.topk("he", 5), vec![(1, 1.0), (2, 0.8), (3, 0.5)]);
+        assert_eq!(trie.topk("hell", 5), vec![(1, 1.0)]);
+
+        // Removing "hel" and "help" merges the chain back into one edge
+        assert!(trie.remove("hel"));
+        assert!(trie.remove("help"));
+        assert_eq!(trie.num_nodes(), 2);
+        assert_eq!(trie.topk("h", 5), vec![(1, 1.0)]);
+        assert!(!trie.remove("hell"));
+        assert!(!trie.remove("hellos"));
+    }
+
+    #[test]
+    fn test_trie_complete_in_lexicographic_order() {
+        let mut trie = Trie::new();
+        for (id, s) in ["help", "hello", "helicopter", "hell", "world"].iter().enumerate() {
+            trie.insert(s, id as IdType, 1.0);
+        }
+        let ids: Vec<IdType> = trie.complete("hel").iter().map(|&(id, _)| id).collect();
+        assert_eq!(ids, vec![2, 3, 1, 0]);
+    }
+
+    #[test]
+    fn test_trie_matches_brute_force() {
+        let mut trie = Trie::new();
+        let mut reference = std::collections::BTreeMap::new();
+        // Many children per node exercise the binary search over labels
+        let mut state = 42u64;
+        for round in 0..3000u32 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let len = 1 + (state >> 60) as usize % 4;
+            let completion: String = (0..len)
+                .map(|i| (b'a' + ((state >> (8 * i + 8)) % 12) as u8) as char)
+                .collect();
+            if round % 3 == 2 {
+                let removed = reference.remove(&completion);
+                let id = removed.map_or(round, |(id, _)| id);
+                assert_eq!(trie.remove_id(&completion, id), removed.is_some());
+            } else {
+                let score = (state >> 40) as f32;
+                let id = reference.get(&completion).map_or(round, |&(id, _)| id);
+                trie.insert(&completion, id, score);
+                reference.insert(completion, (id, score));
+            }
+        }
+
+        for prefix in ["", "d", "dh", "lae"] {
+            let mut expected: Vec<(IdType, f32)> = reference
+                .iter()
+                .filter(|(c, _)| c.starts_with(prefix))
+                .map(|(_, &v)| v)
+                .collect();
+            expected.sort_by(|a, b| b.1.total_cmp(&a.1));
+            expected.truncate(20);
+            assert_eq!(trie.topk(prefix, 20), expected);
+        }
+    }
+
+    #[test]
+    fn test_trie_fuzzy_topk() {
+        let mut trie = Trie::new();
+        trie.insert("hello world", 1, 0.5);
+        trie.insert("help", 2, 0.9);
+        trie.insert("world", 3, 1.0);
+        trie.insert("held", 4, 0.2);
+
+        // "helo wor" misses an "l"
+        assert_eq!(trie.fuzzy_topk("helo wor", 1, 10), vec![(1, 0.5, 1)]);
+        assert!(trie.fuzzy_topk("helo wor", 0, 10).is_empty());
+        assert_eq!(
+            trie.fuzzy_topk("hel", 1, 10),
+            vec![(2, 0.9, 0), (1, 0.5, 0), (4, 0.2, 0)]
+        );
+        // Transposing "pl" gives "help", dropping the "p" gives "hel"
+        assert_eq!(
+            trie.fuzzy_topk("hepl", 1, 10),
+            vec![(2, 0.9, 1), (1, 0.5, 1), (4, 0.2, 1)]
+        );
+        assert_eq!(trie.fuzzy_topk("wrold", 1, 2), vec![(3, 1.0, 1)]);
+        assert_eq!(trie.fuzzy_topk("hekp", 1, 10), vec![(2, 0.9, 1)]);
+        assert_eq!(
+            trie.fuzzy_topk("helx", 1, 10),
+            vec![(2, 0.9, 1), (1, 0.5, 1), (4, 0.2, 1)]
+        );
+        assert_eq!(trie.fuzzy_topk("helx", 1, 2), vec![(2, 0.9, 1), (1, 0.5, 1)]);
+        assert!(trie.fuzzy_topk("hel", 1, 0).is_empty());
+
+        // Each edit halves the score, so a fuzzy match only outranks an
+        // exact one with more than twice its score
+        trie.insert("helm", 5, 2.0);
+        assert_eq!(
+            trie.fuzzy_topk("help", 1, 10),
+            vec![(5, 2.0, 1), (2, 0.9, 0), (1, 0.5, 1), (4, 0.2, 1)]
+        );
+        trie.insert("hell", 6, -1.0);
+        assert_eq!(trie.fuzzy_topk("hall", 1, 10).last(), Some(&(6, -1.0, 1)));
+    }
+
+    /// Edit distance with adjacent transpositions, by dynamic programming
+    fn edit_distance(a: &[u8], b: &[u8]) -> u32 {
+        let mut d = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for (i, row) in d.iter_mut().enumerate() {
+            row[0] = i as u32;
+        }
+        for (j, cell) in d[0].iter_mut().enumerate() {
+            *cell = j as u32;
+        }
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                let cost = (a[i - 1] != b[j - 1]) as u32;
+                d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+                }
+            }
+        }
+        d[a.len()][b.len()]
+    }
+
+    #[test]
+    fn test_trie_fuzzy_matches_brute_force() {
+        let mut trie = Trie::new();
+        let mut strings = Vec::new();
+        let mut state = 7u64;
+        for id in 0..500 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let len = 1 + (state >> 61) as usize;
+            let s: String = (0..len)
+                .map(|i| (b'a' + ((state >> (4 * i + 8)) % 4) as u8) as char)
+                .collect();
+            let score = (state >> 40) as f32;
+            let id = strings.iter().find(|(t, _, _)| *t == s).map_or(id, |&(_, id, _)| id);
+            trie.insert(&s, id, score);
+            strings.retain(|(t, _, _): &(String, IdType, f32)| *t != s);
+            strings.push((s, id, score));
+        }
+
+        for query in ["abc", "dcba", "aa", "bd", "cabd"] {
+            for (max_edits, k) in [(0, 15), (1, 15), (2, 15), (1, 3), (2, 1)] {
+                let mut expected: Vec<(IdType, f32, u32, &str)> = strings
+                    .iter()
+                    .filter_map(|(s, id, score)| {
+                        let edits = (0..=s.len())
+                            .map(|end| edit_distance(query.as_bytes(), &s.as_bytes()[..end]))
+                            .min()
+                            .unwrap();
+                        (edits <= max_edits).then_some((*id, *score, edits, s.as_str()))
+                    })
+                    .collect();
+                expected.sort_by(|a, b| {
+                    discount(b.1, b.2).total_cmp(&discount(a.1, a.2)).then(a.3.cmp(b.3))
+                });
+                let expected: Vec<_> = expected
+                    .into_iter()
+                    .take(k)
+                    .map(|(id, score, edits, _)| (id, score, edits))
+                    .collect();
+                let results = trie.fuzzy_topk(query, max_edits, k);
+                assert_eq!(results, expected, "{} {} {}", query, max_edits, k);
+            }
+        }
+    }
+}
@@ -29,6 +29,20 @@ impl ValueRange {
     pub fn contains(&self, val: u64) -> bool {
         val >= self.begin && val <= self.end
     }
+
+    /// Get the number of values in the range, 0 if it is invalid
+    pub fn len(&self) -> u64 {
+        if self.is_invalid() {
+            0
+        } else {
+            self.end - self.begin + 1
+        }
+    }
+
+    /// Check if the range contains no values
+    pub fn is_empty(&self) -> bool {
+        self.is_invalid()
+    }
 }
 
 /// Represents a scored range
@@ -61,6 +75,10 @@ impl ByteRange {
     pub fn len(&self) -> usize {
         self.end - self.start
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
 }
 
 /// Represents a range of 32-bit integers
@@ -72,7 +90,7 @@ pub struct Uint32Range {
 
 /// Global constants
 pub mod global {
-    use super::IdType;
+    use super::{IdType, ValueRange};
 
     /// Invalid term ID
     pub const INVALID_TERM_ID: IdType = IdType::MAX;
@@ -82,6 +100,12 @@ pub mod global {
     
     /// Not found value
     pub const NOT_FOUND: u64 = u64::MAX;
+
+    /// Invalid range
+    pub const INVALID_RANGE: ValueRange = ValueRange {
+        begin: NOT_FOUND,
+        end: NOT_FOUND,
+    };
     
     /// Linear scan threshold
     pub const LINEAR_SCAN_THRESHOLD: u64 = 8;
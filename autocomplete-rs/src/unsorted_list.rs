@@ -0,0 +1,189 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use crate::index::CompactVector;
+use crate::succinct_rmq::CartesianTree;
+use crate::types::{IdType, ScoredRange, ValueRange};
+
+/// Ranges at most this long are scanned instead of queried with RMQ
+const SCAN_THRESHOLD: usize = 64;
+
+/// List of doc ids in arbitrary order, answering which are the `k` smallest
+/// ones in a range of positions.
+///
+/// As doc ids are score ranks, the smallest doc ids in the range of
+/// lexicographic positions matching a prefix are its best completions. They
+/// are found by repeatedly splitting the range around its minimum, with the
+/// candidate subranges kept in a heap ordered by their minimum.
+#[derive(Debug, Clone)]
+pub struct UnsortedList {
+    rmq: CartesianTree,
+    list: CompactVector,
+}
+
+impl UnsortedList {
+    /// Build the list and its range minimum query structure
+    pub fn build(list: Vec<IdType>) -> Self {
+        Self {
+            rmq: CartesianTree::build(&list, |a, b| a < b),
+            list: CompactVector::build(list.iter().map(|&doc_id| doc_id as u64)),
+        }
+    }
+
+    /// Get the doc id at position `i`
+    pub fn access(&self, i: usize) -> IdType {
+        self.list.access(i) as IdType
+    }
+
+    /// Get the doc id at position `i`, or `None` if out of bounds
+    pub fn get(&self, i: usize) -> Option<IdType> {
+        self.list.get(i).map(|doc_id| doc_id as IdType)
+    }
+
+    /// Return the `k` smallest doc ids at positions in the inclusive
+    /// `range`, in increasing order
+    pub fn topk(&self, range: ValueRange, k: usize) -> Vec<IdType> {
+        self.topk_filtered(range, k, |_| true)
+    }
+
+    /// Return the `k` smallest doc ids at positions in the inclusive
+    /// `range` accepted by `filter`, in increasing order. Rejected doc ids
+    /// are skipped while the range is split, so up to `k` accepted ones are
+    /// still found.
+    pub fn topk_filtered<F>(&self, range: ValueRange, k: usize, mut filter: F) -> Vec<IdType>
+    where
+        F: FnMut(IdType) -> bool,
+    {
+        if range.is_invalid() || k == 0 {
+            return Vec::new();
+        }
+        let (begin, end) = (range.begin as usize, range.end as usize);
+        if end - begin < k {
+            // Report everything in range
+            let mut topk: Vec<IdType> = (begin..=end).map(|i| self.access(i)).collect();
+            topk.sort_unstable();
+            topk.retain(|&doc_id| filter(doc_id));
+            return topk;
+        }
+
+        let mut topk = Vec::with_capacity(k);
+        let mut queue = BinaryHeap::new();
+        queue.push(MinFirst(self.scored_range(begin, end)));
+        while let Some(MinFirst(min)) = queue.pop() {
+            if filter(min.min_val) {
+                topk.push(min.min_val);
+                if topk.len() == k {
+                    break;
+                }
+            }
+            let min_pos = min.min_pos as usize;
+            if min_pos > min.range.begin as usize {
+                queue.push(MinFirst(self.scored_range(min.range.begin as usize, min_pos - 1)));
+            }
+            if min_pos < min.range.end as usize {
+                queue.push(MinFirst(self.scored_range(min_pos + 1, min.range.end as usize)));
+            }
+        }
+        topk
+    }
+
+    /// Get the number of doc ids
+    pub fn size(&self) -> usize {
+        self.list.size()
+    }
+
+    /// Get the size in bytes
+    pub fn bytes(&self) -> usize {
+        self.rmq.bytes() + self.list.bytes()
+    }
+
+    /// Locate the minimum of the inclusive range `[begin, end]`
+    fn scored_range(&self, begin: usize, end: usize) -> ScoredRange {
+        let min_pos = if end - begin <= SCAN_THRESHOLD {
+            (begin..=end).min_by_key(|&i| self.access(i)).unwrap()
+        } else {
+            self.rmq.rmq(begin, end)
+        };
+        ScoredRange {
+            range: ValueRange {
+                begin: begin as u64,
+                end: end as u64,
+            },
+            min_pos: min_pos as u32,
+            min_val: self.access(min_pos),
+        }
+    }
+}
+
+impl Default for UnsortedList {
+    fn default() -> Self {
+        Self::build(Vec::new())
+    }
+}
+
+/// Heap entry popping the scored range with the smallest minimum first
+struct MinFirst(ScoredRange);
+
+impl PartialEq for MinFirst {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.min_val == other.0.min_val
+    }
+}
+
+impl Eq for MinFirst {}
+
+impl PartialOrd for MinFirst {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MinFirst {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if ScoredRange::greater(&self.0, &other.0) {
+            Ordering::Less
+        } else if ScoredRange::greater(&other.0, &self.0) {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(begin: u64, end: u64) -> ValueRange {
+        ValueRange { begin, end }
+    }
+
+    #[test]
+    fn test_topk() {
+        let list: Vec<IdType> = (0..2000u32).map(|i| (i * 7919) % 2003).collect();
+        let unsorted = UnsortedList::build(list.clone());
+        let cases = [(0, 1999, 10), (100, 1500, 25), (5, 9, 10), (300, 400, 101), (7, 7, 1)];
+        for (begin, end, k) in cases {
+            let mut expected = list[begin..=end].to_vec();
+            expected.sort_unstable();
+            expected.truncate(k);
+            assert_eq!(unsorted.topk(range(begin as u64, end as u64), k), expected);
+        }
+        assert!(unsorted.topk(range(0, 10), 0).is_empty());
+        assert!(unsorted.topk(crate::types::global::INVALID_RANGE, 5).is_empty());
+    }
+
+    #[test]
+    fn test_topk_filtered() {
+        let list: Vec<IdType> = (0..2000u32).map(|i| (i * 7919) % 2003).collect();
+        let unsorted = UnsortedList::build(list.clone());
+        for (begin, end, k) in [(0, 1999, 10), (100, 1500, 25), (5, 9, 10)] {
+            let mut expected: Vec<IdType> =
+                list[begin..=end].iter().copied().filter(|doc_id| doc_id % 3 == 0).collect();
+            expected.sort_unstable();
+            expected.truncate(k);
+            let range = range(begin as u64, end as u64);
+            assert_eq!(unsorted.topk_filtered(range, k, |doc_id| doc_id % 3 == 0), expected);
+        }
+        assert!(unsorted.topk_filtered(range(0, 1999), 5, |_| false).is_empty());
+    }
+}
@@ -0,0 +1,703 @@
+use autocomplete_rs::autocomplete::{
+    Autocomplete, CompletionEntry, CompletionMode, IndexConfig, ResultOrder,
+};
+use autocomplete_rs::constants::COMPACTION_THRESHOLD;
+use autocomplete_rs::topk_index::IndexType;
+use autocomplete_rs::normalizer::NormalizerChain;
+use autocomplete_rs::dictionary::Payload;
+use autocomplete_rs::tag_index::TagFilter;
+
+/// Create an autocomplete answering queries in every mode
+fn all_modes() -> Autocomplete {
+    Autocomplete::with_config(IndexConfig {
+        trie: true,
+        infix: true,
+        ..IndexConfig::default()
+    })
+}
+
+fn sample() -> Autocomplete {
+    let mut autocomplete = all_modes();
+    let strings = vec![
+        ("hello".to_string(), 1.0),
+        ("help".to_string(), 0.8),
+        ("hell".to_string(), 0.6),
+        ("helicopter".to_string(), 0.9),
+        ("world".to_string(), 0.5),
+    ];
+    autocomplete.init(&strings).unwrap();
+    autocomplete
+}
+
+#[test]
+fn test_complete_topk_descending() {
+    let autocomplete = sample();
+    let completions = autocomplete.complete("hel", 3);
+    assert_eq!(
+        completions,
+        vec![
+            ("hello".to_string(), 1.0),
+            ("helicopter".to_string(), 0.9),
+            ("help".to_string(), 0.8),
+        ]
+    );
+}
+
+#[test]
+fn test_complete_k_larger_than_matches() {
+    let autocomplete = sample();
+    assert_eq!(autocomplete.complete("wor", 10), vec![("world".to_string(), 0.5)]);
+    assert!(autocomplete.complete("xyz", 10).is_empty());
+    assert!(autocomplete.complete("hel", 0).is_empty());
+}
+
+#[test]
+fn test_prefix_complete_with_and_without_trie() {
+    let strings: Vec<(String, f32)> = [("new york", 1.0), ("new  jersey", 0.5), ("newark", 0.7)]
+        .iter()
+        .map(|&(text, score)| (text.to_string(), score))
+        .collect();
+    let completions = |trie: bool, prefix: &str| {
+        let mut autocomplete =
+            Autocomplete::with_config(IndexConfig { trie, ..IndexConfig::default() });
+        autocomplete.init(&strings).unwrap();
+        let completions = autocomplete.complete(prefix, 10);
+        completions.into_iter().map(|(text, _)| text).collect::<Vec<_>>()
+    };
+
+    // The character trie matches characters, the term-level index terms
+    for trie in [true, false] {
+        assert_eq!(completions(trie, "new"), ["new york", "newark", "new  jersey"]);
+        assert_eq!(completions(trie, "new york"), ["new york"]);
+    }
+    assert!(completions(true, "new j").is_empty());
+    assert_eq!(completions(false, "new j"), ["new  jersey"]);
+    assert_eq!(completions(true, "new "), ["new york", "new  jersey"]);
+    assert_eq!(completions(false, "new "), ["new york", "new  jersey"]);
+}
+
+#[test]
+fn test_conjunctive_complete() {
+    let mut autocomplete = Autocomplete::new();
+    let strings = vec![
+        ("new york times".to_string(), 0.9),
+        ("new york".to_string(), 1.0),
+        ("york new tickets".to_string(), 0.3),
+        ("times square".to_string(), 0.5),
+    ];
+    autocomplete.init(&strings).unwrap();
+
+    assert_eq!(
+        autocomplete.conjunctive_complete("york new ti", 10),
+        vec![
+            ("new york times".to_string(), 0.9),
+            ("york new tickets".to_string(), 0.3),
+        ]
+    );
+    assert_eq!(
+        autocomplete.complete_with_mode("ti", 2, CompletionMode::Conjunctive).unwrap(),
+        vec![
+            ("new york times".to_string(), 0.9),
+            ("times square".to_string(), 0.5),
+        ]
+    );
+    let prefix = autocomplete.complete_with_mode("york ti", 10, CompletionMode::Prefix);
+    assert!(prefix.unwrap().is_empty());
+
+    // Appending strings makes them visible to conjunctive queries too
+    autocomplete.init(&[("tiny york".to_string(), 2.0)]).unwrap();
+    assert_eq!(
+        autocomplete.conjunctive_complete("york ti", 1),
+        vec![("tiny york".to_string(), 2.0)]
+    );
+}
+
+#[test]
+fn test_locate_prefix() {
+    let mut autocomplete = Autocomplete::new();
+    let strings = vec![
+        ("new york times".to_string(), 0.9),
+        ("new york".to_string(), 1.0),
+        ("new jersey".to_string(), 0.7),
+        ("newark airport".to_string(), 0.4),
+        ("york".to_string(), 0.3),
+    ];
+    autocomplete.init(&strings).unwrap();
+
+    let range = autocomplete.locate_prefix("new");
+    assert_eq!(range.len(), 4);
+    assert_eq!(autocomplete.locate_prefix("new y").len(), 2);
+    assert_eq!(autocomplete.locate_prefix("").len(), 5);
+    assert!(autocomplete.locate_prefix("boston").is_invalid());
+    assert_eq!(autocomplete.locate_prefix("boston").len(), 0);
+}
+
+#[test]
+fn test_index_types_agree() {
+    let strings = vec![
+        ("new york times".to_string(), 0.9),
+        ("new york".to_string(), 1.0),
+        ("york new tickets".to_string(), 0.3),
+        ("times square".to_string(), 0.5),
+    ];
+    for index_type in [IndexType::Trie, IndexType::Inverted, IndexType::Blocked] {
+        let mut autocomplete = Autocomplete::with_index_type(index_type);
+        autocomplete.init(&strings).unwrap();
+        assert_eq!(autocomplete.index_type(), index_type);
+        assert_eq!(
+            autocomplete.conjunctive_complete("ti", 10),
+            vec![
+                ("new york times".to_string(), 0.9),
+                ("times square".to_string(), 0.5),
+                ("york new tickets".to_string(), 0.3),
+            ]
+        );
+        assert_eq!(autocomplete.locate_prefix("new y").len(), 2);
+        assert!(autocomplete.bytes() > 0);
+    }
+}
+
+#[test]
+fn test_ties_are_broken_lexicographically() {
+    let mut autocomplete = Autocomplete::new();
+    let strings = vec![
+        ("new york".to_string(), 0.5),
+        ("new jersey".to_string(), 0.5),
+        ("newark".to_string(), 0.9),
+        ("new delhi".to_string(), 0.5),
+    ];
+    autocomplete.init(&strings).unwrap();
+
+    let expected = vec![
+        ("newark".to_string(), 0.9),
+        ("new delhi".to_string(), 0.5),
+        ("new jersey".to_string(), 0.5),
+    ];
+    for _ in 0..3 {
+        assert_eq!(autocomplete.complete("new", 3), expected);
+    }
+    assert_eq!(
+        autocomplete.complete_with_mode("new", 3, CompletionMode::Conjunctive).unwrap(),
+        expected
+    );
+    let order = ResultOrder::Lexicographic;
+    assert_eq!(
+        autocomplete.complete_with_order("new", 3, CompletionMode::Prefix, order).unwrap(),
+        vec![
+            ("new delhi".to_string(), 0.5),
+            ("new jersey".to_string(), 0.5),
+            ("newark".to_string(), 0.9),
+        ]
+    );
+}
+
+#[test]
+fn test_fuzzy_complete() {
+    let mut autocomplete = all_modes();
+    let strings = vec![
+        ("hello world".to_string(), 0.7),
+        ("help wanted".to_string(), 0.9),
+        ("world".to_string(), 1.0),
+    ];
+    autocomplete.init(&strings).unwrap();
+
+    assert!(autocomplete.complete("helo wor", 10).is_empty());
+    assert_eq!(
+        autocomplete.fuzzy_complete("helo wor", 1, 10).unwrap(),
+        vec![("hello world".to_string(), 0.7)]
+    );
+    // An edit halves the score, so the exact prefix match ranks first
+    let fuzzy = CompletionMode::Fuzzy { max_edits: 1 };
+    assert_eq!(
+        autocomplete.complete_with_mode("hell", 10, fuzzy).unwrap(),
+        vec![("hello world".to_string(), 0.7), ("help wanted".to_string(), 0.9)]
+    );
+    // Unless the fuzzy match scores more than twice as high
+    autocomplete.update_score("help wanted", 1.5).unwrap();
+    assert_eq!(
+        autocomplete.complete_with_mode("hell", 10, fuzzy).unwrap(),
+        vec![("help wanted".to_string(), 1.5), ("hello world".to_string(), 0.7)]
+    );
+
+    // Fuzzy queries need the character trie
+    let mut without_trie = Autocomplete::with_config(IndexConfig {
+        trie: false,
+        ..IndexConfig::default()
+    });
+    without_trie.init(&strings).unwrap();
+    assert!(without_trie.fuzzy_complete("helo", 1, 10).is_err());
+    assert!(without_trie.complete_with_mode("hell", 10, fuzzy).is_err());
+    assert!(without_trie.bytes() < autocomplete.bytes());
+}
+
+#[test]
+fn test_normalized_completions() {
+    let mut autocomplete = all_modes().with_normalizer(NormalizerChain::standard());
+    let strings = vec![
+        ("Héllo, World!".to_string(), 1.0),
+        ("New-York".to_string(), 0.8),
+        ("new zealand".to_string(), 0.6),
+    ];
+    autocomplete.init(&strings).unwrap();
+
+    // Completions are matched by their normalized form but returned as given
+    assert_eq!(
+        autocomplete.complete("HELLO  w", 10),
+        vec![("Héllo, World!".to_string(), 1.0)]
+    );
+    assert_eq!(
+        autocomplete.complete("NEW ", 10),
+        vec![("New-York".to_string(), 0.8), ("new zealand".to_string(), 0.6)]
+    );
+    // A trailing separator still completes the last term
+    assert_eq!(
+        autocomplete.conjunctive_complete("york NEW ", 10),
+        vec![("New-York".to_string(), 0.8)]
+    );
+    assert!(autocomplete.conjunctive_complete("york NEWS", 10).is_empty());
+    assert_eq!(autocomplete.locate_prefix("Néw").len(), 2);
+    assert_eq!(
+        autocomplete.fuzzy_complete("hallo", 1, 10).unwrap(),
+        vec![("Héllo, World!".to_string(), 1.0)]
+    );
+
+    // Without a normalizer, the text is matched as given
+    let mut exact = Autocomplete::new();
+    exact.init(&strings).unwrap();
+    assert!(exact.complete("hello", 10).is_empty());
+}
+
+#[test]
+fn test_infix_complete() {
+    let mut autocomplete = all_modes().with_normalizer(NormalizerChain::standard());
+    let strings = vec![
+        ("iPhone 15 Pro Max".to_string(), 0.9),
+        ("iPad Pro".to_string(), 0.7),
+        ("MacBook Pro".to_string(), 1.0),
+        ("ProMax cable".to_string(), 0.5),
+    ];
+    autocomplete.init(&strings).unwrap();
+
+    assert!(autocomplete.complete("pro max", 10).is_empty());
+    assert_eq!(
+        autocomplete.infix_complete("PRO MAX", 10).unwrap(),
+        vec![("iPhone 15 Pro Max".to_string(), 0.9)]
+    );
+    assert_eq!(
+        autocomplete.complete_with_mode("pro", 2, CompletionMode::Infix).unwrap(),
+        vec![("MacBook Pro".to_string(), 1.0), ("iPhone 15 Pro Max".to_string(), 0.9)]
+    );
+    assert!(autocomplete.infix_complete("book", 10).unwrap().is_empty());
+    assert_eq!(
+        autocomplete.complete_with_mode("book", 10, CompletionMode::Substring).unwrap(),
+        vec![("MacBook Pro".to_string(), 1.0)]
+    );
+    assert_eq!(
+        autocomplete.substring_complete("omax", 10).unwrap(),
+        vec![("ProMax cable".to_string(), 0.5)]
+    );
+
+    // Infix and substring queries need the suffix arrays
+    let mut without_infix = Autocomplete::new();
+    without_infix.init(&strings).unwrap();
+    assert!(without_infix.infix_complete("pro", 10).is_err());
+    assert!(without_infix.complete_with_mode("pro", 10, CompletionMode::Substring).is_err());
+    assert!(without_infix.bytes() < autocomplete.bytes());
+}
+
+#[test]
+fn test_payloads() {
+    let mut autocomplete = Autocomplete::new();
+    let payload = Payload {
+        data: vec![1, 2, 3],
+        attributes: [("url".to_string(), "/p/42".to_string())].into_iter().collect(),
+    };
+    let entries = vec![
+        CompletionEntry {
+            text: "iphone".to_string(),
+            score: 1.0,
+            payload: payload.clone(),
+            ..Default::default()
+        },
+        CompletionEntry { text: "ipad".to_string(), score: 0.5, ..Default::default() },
+    ];
+    autocomplete.init_entries(&entries).unwrap();
+
+    let completions = autocomplete.complete("ip", 10);
+    assert_eq!(completions[0].0, "iphone");
+    assert_eq!(autocomplete.payload(&completions[0].0), Some(&payload));
+    assert!(autocomplete.payload("ipad").unwrap().is_empty());
+    assert_eq!(autocomplete.payload("ipod"), None);
+
+    // Initializing without payloads keeps the existing ones
+    autocomplete.init(&[("iphone".to_string(), 2.0)]).unwrap();
+    assert_eq!(autocomplete.payload("iphone"), Some(&payload));
+}
+
+#[test]
+fn test_tag_filters() {
+    let mut autocomplete = all_modes().with_normalizer(NormalizerChain::standard());
+    let entry = |text: &str, score, tags: &[&str]| CompletionEntry {
+        text: text.to_string(),
+        score,
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        ..Default::default()
+    };
+    autocomplete
+        .init_entries(&[
+            entry("Harry Potter", 1.0, &["books"]),
+            entry("Harry Styles", 0.9, &["people", "music"]),
+            entry("Harry Potter Soundtrack", 0.8, &["music"]),
+            entry("Harrison Ford", 0.7, &["people"]),
+            entry("Harry Potter Box Set", 0.6, &["books"]),
+        ])
+        .unwrap();
+    assert_eq!(autocomplete.tags("Harry Styles"), vec!["music", "people"]);
+
+    let complete = |query: &str, k, mode, filter: &TagFilter| -> Vec<String> {
+        autocomplete
+            .complete_with_filter(query, k, mode, ResultOrder::Score, filter)
+            .unwrap()
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect()
+    };
+    // k filtered results are returned even though better ones are rejected
+    let people = TagFilter::new().include("people");
+    assert_eq!(
+        complete("harr", 2, CompletionMode::Prefix, &people),
+        vec!["Harry Styles", "Harrison Ford"]
+    );
+    let books = TagFilter::new().include("books");
+    for mode in [CompletionMode::Conjunctive, CompletionMode::Infix, CompletionMode::Substring] {
+        assert_eq!(complete("potter", 1, mode, &books), vec!["Harry Potter"], "{:?}", mode);
+    }
+    let neither = TagFilter::new().exclude("books").exclude("people");
+    for mode in [
+        CompletionMode::Prefix,
+        CompletionMode::Conjunctive,
+        CompletionMode::Fuzzy { max_edits: 1 },
+        CompletionMode::Infix,
+        CompletionMode::Substring,
+    ] {
+        assert_eq!(complete("harry", 10, mode, &neither), vec!["Harry Potter Soundtrack"]);
+    }
+    assert_eq!(
+        complete("potter", 10, CompletionMode::Infix, &books),
+        vec!["Harry Potter", "Harry Potter Box Set"]
+    );
+    assert_eq!(
+        complete("harry", 10, CompletionMode::Prefix, &TagFilter::new()),
+        complete("harry", 10, CompletionMode::Prefix, &TagFilter::new().exclude("unknown"))
+    );
+}
+
+#[test]
+fn test_incremental_changes() {
+    let mut autocomplete = sample();
+    let entry = |text: &str, score| CompletionEntry {
+        text: text.to_string(),
+        score,
+        ..Default::default()
+    };
+
+    autocomplete.add(entry("helium", 2.0)).unwrap();
+    assert!(autocomplete.add(entry("helium", 0.1)).is_err());
+    assert_eq!(autocomplete.num_terms(), 6);
+    assert_eq!(autocomplete.complete("hel", 1), vec![("helium".to_string(), 2.0)]);
+
+    autocomplete.update_score("help", 3.0).unwrap();
+    assert!(autocomplete.update_score("helm", 3.0).is_err());
+    assert_eq!(autocomplete.complete("hel", 1), vec![("help".to_string(), 3.0)]);
+    assert_eq!(autocomplete.conjunctive_complete("hel", 1), vec![("help".to_string(), 3.0)]);
+
+    autocomplete.remove("help").unwrap();
+    assert!(autocomplete.remove("help").is_err());
+    assert!(!autocomplete.contains("help"));
+    assert_eq!(autocomplete.num_terms(), 5);
+    for mode in [
+        CompletionMode::Prefix,
+        CompletionMode::Conjunctive,
+        CompletionMode::Fuzzy { max_edits: 1 },
+        CompletionMode::Infix,
+        CompletionMode::Substring,
+    ] {
+        let completions = autocomplete.complete_with_mode("hel", 10, mode).unwrap();
+        assert_eq!(completions.len(), 4, "{:?}", mode);
+        assert!(completions.iter().all(|(text, _)| text != "help"), "{:?}", mode);
+    }
+
+    // A removed completion can be added again
+    autocomplete
+        .upsert(&[entry("help", 0.2), entry("hello", 0.1)])
+        .unwrap();
+    assert_eq!(autocomplete.num_terms(), 6);
+    assert_eq!(
+        autocomplete.complete("help", 10),
+        vec![("help".to_string(), 0.2)]
+    );
+    assert_eq!(autocomplete.complete("hello", 10), vec![("hello".to_string(), 0.1)]);
+}
+
+#[test]
+fn test_changes_are_pending_until_compaction() {
+    let mut autocomplete = sample();
+    let indexed = autocomplete.indexed_generation();
+    let modes = [
+        CompletionMode::Prefix,
+        CompletionMode::Conjunctive,
+        CompletionMode::Fuzzy { max_edits: 1 },
+        CompletionMode::Infix,
+        CompletionMode::Substring,
+    ];
+
+    // Changes are visible at once, without rebuilding the indexes
+    autocomplete
+        .add(CompletionEntry {
+            text: "helium".to_string(),
+            score: 0.7,
+            ..Default::default()
+        })
+        .unwrap();
+    autocomplete.update_score("hell", 5.0).unwrap();
+    autocomplete.remove("hello").unwrap();
+    assert_eq!(autocomplete.indexed_generation(), indexed);
+    assert_eq!(autocomplete.generation(), indexed + 3);
+    assert_eq!(autocomplete.num_pending(), 3);
+    let expected = [("hell", 5.0), ("helicopter", 0.9), ("help", 0.8), ("helium", 0.7)];
+    let expected: Vec<(String, f32)> =
+        expected.iter().map(|&(text, score)| (text.to_string(), score)).collect();
+    for mode in modes {
+        let completions = autocomplete.complete_with_mode("hel", 10, mode).unwrap();
+        assert_eq!(completions, expected, "{:?}", mode);
+        let completions = autocomplete.complete_with_mode("hel", 2, mode).unwrap();
+        assert_eq!(completions, expected[..2], "{:?}", mode);
+    }
+
+    // Changes made while a compaction is built stay pending after it
+    let compaction = autocomplete.compaction();
+    let stale = autocomplete.compaction().run().unwrap();
+    autocomplete.update_score("help", 9.0).unwrap();
+    assert!(autocomplete.finish_compaction(compaction.run().unwrap()));
+    assert!(!autocomplete.finish_compaction(stale));
+    assert_eq!(autocomplete.indexed_generation(), indexed + 3);
+    assert_eq!(autocomplete.num_pending(), 1);
+    for mode in modes {
+        let completions = autocomplete.complete_with_mode("hel", 1, mode).unwrap();
+        assert_eq!(completions, vec![("help".to_string(), 9.0)], "{:?}", mode);
+    }
+
+    autocomplete.compact().unwrap();
+    assert_eq!(autocomplete.num_pending(), 0);
+    assert_eq!(autocomplete.complete("hel", 10).len(), 4);
+    assert_eq!(autocomplete.locate_prefix("hel").len(), 4);
+}
+
+#[test]
+fn test_failed_compaction_blocks_additions_until_a_removal() {
+    let mut autocomplete = sample();
+    for _ in 0..COMPACTION_THRESHOLD {
+        autocomplete.update_score("help", 2.0).unwrap();
+    }
+    assert!(autocomplete.needs_compaction());
+    let compaction = autocomplete.compaction();
+    assert!(!autocomplete.needs_compaction());
+
+    // A failure stops compactions and changes adding completions or scores
+    assert!(autocomplete.fail_compaction(compaction.generation(), "too large".to_string()));
+    assert_eq!(autocomplete.compaction_error(), Some("too large"));
+    assert!(!autocomplete.needs_compaction());
+    let entry = CompletionEntry { text: "helium".to_string(), score: 0.7, ..Default::default() };
+    let error = autocomplete.add(entry.clone()).unwrap_err();
+    assert!(error.contains("too large"), "{}", error);
+    assert!(autocomplete.update_score("hell", 5.0).is_err());
+    assert!(autocomplete.upsert(std::slice::from_ref(&entry)).is_err());
+    assert_eq!(autocomplete.complete("hel", 10).len(), 4);
+
+    // A removal clears it, and the pending changes are compacted again
+    autocomplete.remove("world").unwrap();
+    assert_eq!(autocomplete.compaction_error(), None);
+    assert!(autocomplete.needs_compaction());
+    autocomplete.add(entry).unwrap();
+
+    // A stale failure is ignored once the indexes were rebuilt
+    let stale = autocomplete.compaction();
+    autocomplete.compact().unwrap();
+    assert!(!autocomplete.fail_compaction(stale.generation(), "stale".to_string()));
+    assert_eq!(autocomplete.compaction_error(), None);
+}
+
+#[test]
+fn test_shared_normalized_form_in_every_mode() {
+    let modes = [
+        ("cafe a", CompletionMode::Prefix),
+        ("lait caf", CompletionMode::Conjunctive),
+        ("cafe au", CompletionMode::Fuzzy { max_edits: 0 }),
+        ("au", CompletionMode::Infix),
+        ("e au", CompletionMode::Substring),
+    ];
+    for index_type in [IndexType::Trie, IndexType::Inverted, IndexType::Blocked] {
+        let config = IndexConfig { index_type, trie: true, infix: true };
+        let mut autocomplete =
+            Autocomplete::with_config(config).with_normalizer(NormalizerChain::standard());
+        let strings = [("Café au lait", 1.0), ("cafe au lait", 0.5), ("CAFE AU LAIT", 0.5)];
+        let strings: Vec<(String, f32)> =
+            strings.iter().map(|&(text, score)| (text.to_string(), score)).collect();
+        autocomplete.init(&strings).unwrap();
+        autocomplete.init(&[("cafe noir".to_string(), 0.7)]).unwrap();
+
+        // Every completion is returned, ties broken by id
+        let unfiltered = TagFilter::new();
+        let filtered = TagFilter::new().exclude("decaf");
+        for (query, mode) in modes {
+            for filter in [&unfiltered, &filtered] {
+                let completions = autocomplete
+                    .complete_with_filter(query, 10, mode, ResultOrder::Score, filter)
+                    .unwrap();
+                assert_eq!(completions, strings, "{} {:?}", index_type, mode);
+            }
+        }
+        assert_eq!(autocomplete.locate_prefix("cafe au").len(), 3);
+
+        // Removing one of them leaves the others
+        autocomplete.remove("cafe au lait").unwrap();
+        for (query, mode) in modes {
+            let completions = autocomplete.complete_with_mode(query, 10, mode).unwrap();
+            let expected = [strings[0].clone(), strings[2].clone()];
+            assert_eq!(completions, expected, "{} {:?}", index_type, mode);
+        }
+        autocomplete.remove("Café au lait").unwrap();
+        autocomplete.remove("CAFE AU LAIT").unwrap();
+        assert_eq!(autocomplete.complete("cafe", 10), vec![("cafe noir".to_string(), 0.7)]);
+        assert_eq!(autocomplete.num_terms(), 1);
+    }
+}
+
+#[test]
+fn test_blank_completions_and_queries_in_every_mode() {
+    let modes = [
+        CompletionMode::Prefix,
+        CompletionMode::Conjunctive,
+        CompletionMode::Fuzzy { max_edits: 1 },
+        CompletionMode::Infix,
+        CompletionMode::Substring,
+    ];
+    for index_type in [IndexType::Trie, IndexType::Inverted, IndexType::Blocked] {
+        for trie in [true, false] {
+            let config = IndexConfig { index_type, trie, infix: true };
+            let mut autocomplete =
+                Autocomplete::with_config(config).with_normalizer(NormalizerChain::standard());
+            autocomplete.init(&[("New York".to_string(), 1.0)]).unwrap();
+
+            // Completions without terms once normalized are rejected, along
+            // with the rest of their batch
+            for blank in ["", "  ", "!!!"] {
+                let strings = [("boston".to_string(), 0.5), (blank.to_string(), 0.5)];
+                assert!(autocomplete.init(&strings).is_err(), "{:?}", blank);
+                let entry = CompletionEntry { text: blank.to_string(), ..Default::default() };
+                assert!(autocomplete.add(entry.clone()).is_err(), "{:?}", blank);
+                assert!(autocomplete.upsert(&[entry]).is_err(), "{:?}", blank);
+            }
+            assert_eq!(autocomplete.num_terms(), 1);
+
+            // A query the normalizer empties matches nothing, while the empty
+            // query matches everything in the modes accepting it
+            for mode in modes {
+                if let (CompletionMode::Fuzzy { .. }, false) = (mode, trie) {
+                    // Disabled modes are still rejected
+                    assert!(autocomplete.complete_with_mode("+", 10, mode).is_err());
+                    continue;
+                }
+                for query in ["+", " ", "?!"] {
+                    let completions = autocomplete.complete_with_mode(query, 10, mode).unwrap();
+                    assert!(completions.is_empty(), "{} {:?} {:?}", index_type, mode, query);
+                }
+            }
+            assert_eq!(autocomplete.complete("", 10), vec![("New York".to_string(), 1.0)]);
+        }
+    }
+}
+
+#[test]
+fn test_non_finite_scores_are_rejected() {
+    let mut autocomplete = sample();
+    for score in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+        let strings = [("helium".to_string(), 0.5), ("hello nan".to_string(), score)];
+        assert!(autocomplete.init(&strings).is_err());
+        let entry = CompletionEntry { text: "helium".to_string(), score, ..Default::default() };
+        assert!(autocomplete.add(entry.clone()).is_err());
+        assert!(autocomplete.upsert(&[entry]).is_err());
+        assert!(autocomplete.update_score("hello", score).is_err());
+    }
+    assert!(!autocomplete.contains("helium"));
+    assert_eq!(autocomplete.complete("hel", 1), vec![("hello".to_string(), 1.0)]);
+}
+
+#[test]
+fn test_replace() {
+    let mut autocomplete = sample().with_normalizer(NormalizerChain::standard());
+    let entries = [CompletionEntry {
+        text: "Helsinki".to_string(),
+        score: 5.0,
+        ..Default::default()
+    }];
+
+    let generation = autocomplete.generation();
+    let mut replacement = autocomplete.begin_replace();
+    replacement.fresh.init_entries(&entries).unwrap();
+    autocomplete.replace(replacement).unwrap();
+    assert_eq!(autocomplete.num_terms(), 1);
+    assert_eq!(autocomplete.complete("hel", 10), vec![("Helsinki".to_string(), 5.0)]);
+    // The configuration is kept and generations keep increasing
+    assert_eq!(autocomplete.normalizer().len(), NormalizerChain::standard().len());
+    assert!(autocomplete.config().trie && autocomplete.config().infix);
+    assert_eq!(autocomplete.indexed_generation(), generation + 1);
+
+    // Compactions begun before a replacement are ignored after it
+    let compaction = autocomplete.compaction();
+    autocomplete.remove("Helsinki").unwrap();
+    let replacement = autocomplete.begin_replace();
+    autocomplete.replace(replacement).unwrap();
+    assert!(!autocomplete.finish_compaction(compaction.run().unwrap()));
+    assert_eq!(autocomplete.num_terms(), 0);
+    assert!(autocomplete.complete("hel", 10).is_empty());
+}
+
+#[test]
+fn test_changes_during_replace_are_replayed() {
+    let mut autocomplete = sample();
+    let entry = |text: &str, score| CompletionEntry {
+        text: text.to_string(),
+        score,
+        ..Default::default()
+    };
+    let mut replacement = autocomplete.begin_replace();
+    replacement.fresh.init_entries(&[entry("help", 1.0), entry("helium", 2.0)]).unwrap();
+
+    // Changes made meanwhile are applied on top of the replacement, and
+    // dropped for completions it lacks
+    let compaction = autocomplete.compaction();
+    autocomplete.add(entry("helix", 3.0)).unwrap();
+    autocomplete.update_score("help", 4.0).unwrap();
+    autocomplete.update_score("hello", 9.0).unwrap();
+    autocomplete.remove("world").unwrap();
+    autocomplete.upsert(&[entry("helium", 0.5)]).unwrap();
+    let generation = autocomplete.generation();
+    autocomplete.replace(replacement).unwrap();
+    let expected = [("help", 4.0), ("helix", 3.0), ("helium", 0.5)];
+    let expected: Vec<(String, f32)> =
+        expected.iter().map(|&(text, score)| (text.to_string(), score)).collect();
+    assert_eq!(autocomplete.complete("hel", 10), expected);
+    assert!(autocomplete.generation() > generation);
+    assert!(!autocomplete.finish_compaction(compaction.run().unwrap()));
+    autocomplete.compact().unwrap();
+    assert_eq!(autocomplete.complete("hel", 10), expected);
+
+    // A replacement is superseded by a later one
+    let first = autocomplete.begin_replace();
+    let second = autocomplete.begin_replace();
+    autocomplete.remove("help").unwrap();
+    assert!(autocomplete.replace(first).is_err());
+    autocomplete.replace(second).unwrap();
+    assert_eq!(autocomplete.num_terms(), 0);
+}
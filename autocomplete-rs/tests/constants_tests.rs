@@ -1,3 +1,5 @@
+#![allow(clippy::assertions_on_constants)]
+
 use autocomplete_rs::constants::*;
 
 #[test]
@@ -0,0 +1,55 @@
+use autocomplete_rs::constants::{
+    DEFAULT_K, DEFAULT_MAX_EDITS, MAX_EDITS, MAX_K, MAX_RESULTS_CEILING,
+};
+use autocomplete_rs::limits::{check_score, resolve_max_edits, ResultLimit};
+
+#[test]
+fn test_default_limit() {
+    let limit = ResultLimit::default();
+    assert_eq!(limit.max_k(), MAX_K);
+    assert_eq!(limit.default_k(), DEFAULT_K.min(MAX_K));
+}
+
+#[test]
+fn test_resolve_defaults_and_clamps() {
+    let limit = ResultLimit::new(20).unwrap();
+    assert_eq!(limit.resolve(None).unwrap(), DEFAULT_K as usize);
+    assert_eq!(limit.resolve(Some(0)).unwrap(), DEFAULT_K as usize);
+    assert_eq!(limit.resolve(Some(3)).unwrap(), 3);
+    assert_eq!(limit.resolve(Some(20)).unwrap(), 20);
+    assert_eq!(limit.resolve(Some(500)).unwrap(), 20);
+}
+
+#[test]
+fn test_resolve_rejects_invalid_values() {
+    let limit = ResultLimit::default();
+    assert!(limit.resolve(Some(-1)).is_err());
+    assert!(limit.resolve(Some(MAX_RESULTS_CEILING as i32 + 1)).is_err());
+    assert!(limit.resolve(Some(i32::MAX)).is_err());
+}
+
+#[test]
+fn test_new_validates_ceiling() {
+    assert!(ResultLimit::new(0).is_err());
+    assert!(ResultLimit::new(MAX_RESULTS_CEILING + 1).is_err());
+    assert_eq!(ResultLimit::new(5).unwrap().default_k(), 5);
+}
+
+#[test]
+fn test_resolve_max_edits() {
+    assert_eq!(resolve_max_edits(None), Ok(DEFAULT_MAX_EDITS));
+    assert_eq!(resolve_max_edits(Some(0)), Ok(0));
+    assert_eq!(resolve_max_edits(Some(2)), Ok(2));
+    assert_eq!(resolve_max_edits(Some(MAX_EDITS as i32)), Ok(MAX_EDITS));
+    assert!(resolve_max_edits(Some(MAX_EDITS as i32 + 1)).is_err());
+    assert!(resolve_max_edits(Some(-1)).is_err());
+}
+
+#[test]
+fn test_check_score() {
+    assert_eq!(check_score(0.5), Ok(0.5));
+    assert_eq!(check_score(-1.0), Ok(-1.0));
+    for score in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+        assert!(check_score(score).is_err());
+    }
+}
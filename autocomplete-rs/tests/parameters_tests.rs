@@ -1,12 +1,20 @@
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
-use tempfile::NamedTempFile;
+use tempfile::TempDir;
 use autocomplete_rs::parameters::Parameters;
-use autocomplete_rs::constants::{MAX_NUM_CHARS_PER_QUERY, MAX_NUM_TERMS_PER_QUERY};
+use autocomplete_rs::constants::MAX_NUM_CHARS_PER_QUERY;
 
-fn create_test_stats_file() -> NamedTempFile {
-    let mut file = NamedTempFile::new().unwrap();
+/// Creates `<basename>.mapped.stats` in a temporary directory and returns the
+/// directory, the open stats file and the collection basename
+fn create_stats_file() -> (TempDir, File, String) {
+    let dir = TempDir::new().unwrap();
+    let basename = dir.path().join("collection").to_str().unwrap().to_string();
+    let file = File::create(format!("{}.mapped.stats", basename)).unwrap();
+    (dir, file, basename)
+}
+
+fn create_test_stats_file() -> (TempDir, String) {
+    let (dir, mut file, basename) = create_stats_file();
     writeln!(file, "1000").unwrap();  // num_terms
     writeln!(file, "50").unwrap();    // max_string_length
     writeln!(file, "500").unwrap();   // num_completions
@@ -15,16 +23,15 @@ fn create_test_stats_file() -> NamedTempFile {
     writeln!(file, "100").unwrap();   // nodes_per_level[0]
     writeln!(file, "200").unwrap();   // nodes_per_level[1]
     writeln!(file, "300").unwrap();   // nodes_per_level[2]
-    file
+    (dir, basename)
 }
 
 #[test]
 fn test_parameters_load_valid() {
-    let test_file = create_test_stats_file();
+    let (_dir, basename) = create_test_stats_file();
     let mut params = Parameters::new();
-    let path = test_file.path().to_str().unwrap().to_string();
-    println!("Test file path: {}", path);
-    params.collection_basename = path;
+    println!("Collection basename: {}", basename);
+    params.collection_basename = basename;
     
     match params.load() {
         Ok(_) => println!("Load succeeded"),
@@ -49,7 +56,7 @@ fn test_parameters_load_invalid_file() {
 
 #[test]
 fn test_parameters_load_invalid_data() {
-    let mut file = NamedTempFile::new().unwrap();
+    let (_dir, mut file, basename) = create_stats_file();
     writeln!(file, "0").unwrap();  // invalid num_terms
     writeln!(file, "50").unwrap();
     writeln!(file, "500").unwrap();
@@ -60,13 +67,13 @@ fn test_parameters_load_invalid_data() {
     writeln!(file, "300").unwrap();
 
     let mut params = Parameters::new();
-    params.collection_basename = file.path().to_str().unwrap().to_string();
+    params.collection_basename = basename;
     assert!(params.load().is_err());
 }
 
 #[test]
 fn test_parameters_load_invalid_constants() {
-    let mut file = NamedTempFile::new().unwrap();
+    let (_dir, mut file, basename) = create_stats_file();
     writeln!(file, "1000").unwrap();
     writeln!(file, "{}", MAX_NUM_CHARS_PER_QUERY + 1).unwrap();  // exceeds MAX_NUM_CHARS_PER_QUERY
     writeln!(file, "500").unwrap();
@@ -77,13 +84,13 @@ fn test_parameters_load_invalid_constants() {
     writeln!(file, "300").unwrap();
 
     let mut params = Parameters::new();
-    params.collection_basename = file.path().to_str().unwrap().to_string();
+    params.collection_basename = basename;
     assert!(params.load().is_err());
 }
 
 #[test]
 fn test_parameters_load_truncated() {
-    let mut file = NamedTempFile::new().unwrap();
+    let (_dir, mut file, basename) = create_stats_file();
     writeln!(file, "1000").unwrap();
     writeln!(file, "50").unwrap();
     writeln!(file, "500").unwrap();
@@ -93,6 +100,17 @@ fn test_parameters_load_truncated() {
     // Missing nodes_per_level entries
 
     let mut params = Parameters::new();
-    params.collection_basename = file.path().to_str().unwrap().to_string();
+    params.collection_basename = basename;
     assert!(params.load().is_err());
-} 
\ No newline at end of file
+}
+
+#[test]
+fn test_parameters_from_completions() {
+    let completions = [vec![1, 2], vec![1, 2, 3], vec![1, 4], vec![2], vec![2, 1, 1]];
+    let params = Parameters::from_completions(&completions, 4);
+    assert_eq!(params.num_terms, 4);
+    assert_eq!(params.num_completions, 5);
+    assert_eq!(params.universe, 5);
+    assert_eq!(params.num_levels, 3);
+    assert_eq!(params.nodes_per_level, vec![2, 3, 2]);
+}
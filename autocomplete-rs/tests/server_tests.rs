@@ -0,0 +1,781 @@
+use std::sync::Arc;
+use async_graphql::value;
+use tokio::sync::Mutex;
+use tonic::{Code, Request};
+use autocomplete_rs::autocomplete::{Autocomplete, IndexConfig};
+use autocomplete_rs::graphql::create_schema;
+use autocomplete_rs::limits::ResultLimit;
+use autocomplete_rs::server::autocomplete_proto::autocomplete_service_server::AutocompleteService;
+use autocomplete_rs::server::autocomplete_proto::{
+    AddRequest, CompleteRequest, CompleteResponse, CompletionMode, InitMode, InitRequest, Payload,
+    RemoveRequest, ResultOrder, StatsRequest, StringScore, TagFilter, UpdateScoreRequest,
+    UpsertRequest,
+};
+use autocomplete_rs::server::AutocompleteServiceImpl;
+
+fn sample() -> Arc<Mutex<Autocomplete>> {
+    let mut autocomplete = Autocomplete::new();
+    let strings: Vec<(String, f32)> = (0..30)
+        .map(|i| (format!("item {:02}", i), i as f32))
+        .collect();
+    autocomplete.init(&strings).unwrap();
+    Arc::new(Mutex::new(autocomplete))
+}
+
+fn complete_request(prefix: &str, max_results: i32) -> Request<CompleteRequest> {
+    Request::new(CompleteRequest {
+        prefix: prefix.to_string(),
+        max_results,
+        mode: CompletionMode::Prefix as i32,
+        order: ResultOrder::Score as i32,
+        max_edits: None,
+        filter: None,
+    })
+}
+
+#[tokio::test]
+async fn test_grpc_complete_honors_max_results() {
+    let service = AutocompleteServiceImpl::new(sample(), ResultLimit::new(12).unwrap());
+
+    let response = service.complete(complete_request("item", 3)).await.unwrap();
+    let texts: Vec<String> = response.into_inner().completions
+        .into_iter()
+        .map(|c| c.text)
+        .collect();
+    assert_eq!(texts, vec!["item 29", "item 28", "item 27"]);
+
+    let response = service.complete(complete_request("item", 0)).await.unwrap();
+    assert_eq!(response.into_inner().completions.len(), 10);
+
+    let response = service.complete(complete_request("item", 100)).await.unwrap();
+    assert_eq!(response.into_inner().completions.len(), 12);
+}
+
+#[tokio::test]
+async fn test_grpc_complete_rejects_invalid_max_results() {
+    let service = AutocompleteServiceImpl::new(sample(), ResultLimit::default());
+
+    let status = service.complete(complete_request("item", -1)).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let status = service.complete(complete_request("item", i32::MAX)).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_graphql_complete_honors_max_results() {
+    let schema = create_schema(sample(), ResultLimit::new(12).unwrap());
+
+    let response = schema
+        .execute(r#"{ complete(prefix: "item", maxResults: 2) { completions { text } } }"#)
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        value!({ "complete": { "completions": [{ "text": "item 29" }, { "text": "item 28" }] } })
+    );
+
+    let response = schema
+        .execute(r#"{ complete(prefix: "item") { completions { text } } }"#)
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    let expected: Vec<_> = (20..30)
+        .rev()
+        .map(|i| value!({ "text": format!("item {:02}", i) }))
+        .collect();
+    assert_eq!(
+        response.data,
+        value!({ "complete": { "completions": expected } })
+    );
+}
+
+#[tokio::test]
+async fn test_graphql_complete_rejects_invalid_max_results() {
+    let schema = create_schema(sample(), ResultLimit::default());
+
+    let response = schema
+        .execute(r#"{ complete(prefix: "item", maxResults: -5) { completions { text } } }"#)
+        .await;
+    assert_eq!(response.errors.len(), 1);
+}
+
+fn conjunctive_sample() -> Arc<Mutex<Autocomplete>> {
+    let mut autocomplete = Autocomplete::with_config(IndexConfig {
+        trie: true,
+        infix: true,
+        ..IndexConfig::default()
+    });
+    let strings = vec![
+        ("new york times".to_string(), 0.9),
+        ("new york".to_string(), 1.0),
+        ("york new tickets".to_string(), 0.3),
+        ("times square".to_string(), 0.5),
+    ];
+    autocomplete.init(&strings).unwrap();
+    Arc::new(Mutex::new(autocomplete))
+}
+
+#[tokio::test]
+async fn test_grpc_conjunctive_mode() {
+    let service = AutocompleteServiceImpl::new(conjunctive_sample(), ResultLimit::default());
+
+    let request = Request::new(CompleteRequest {
+        prefix: "york new ti".to_string(),
+        max_results: 10,
+        mode: CompletionMode::Conjunctive as i32,
+        order: ResultOrder::Score as i32,
+        max_edits: None,
+        filter: None,
+    });
+    let response = service.complete(request).await.unwrap();
+    let texts: Vec<String> = response.into_inner().completions
+        .into_iter()
+        .map(|c| c.text)
+        .collect();
+    assert_eq!(texts, vec!["new york times", "york new tickets"]);
+
+    let request = Request::new(CompleteRequest {
+        prefix: "york".to_string(),
+        max_results: 10,
+        mode: 42,
+        order: ResultOrder::Score as i32,
+        max_edits: None,
+        filter: None,
+    });
+    let status = service.complete(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_graphql_conjunctive_mode() {
+    let schema = create_schema(conjunctive_sample(), ResultLimit::default());
+
+    let response = schema
+        .execute(r#"{ complete(prefix: "york ti", mode: CONJUNCTIVE) { completions { text } } }"#)
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        value!({ "complete": { "completions": [
+            { "text": "new york times" },
+            { "text": "york new tickets" },
+        ] } })
+    );
+}
+
+#[tokio::test]
+async fn test_grpc_lexicographic_order() {
+    let service = AutocompleteServiceImpl::new(conjunctive_sample(), ResultLimit::default());
+
+    let request = Request::new(CompleteRequest {
+        prefix: "ti".to_string(),
+        max_results: 2,
+        mode: CompletionMode::Conjunctive as i32,
+        order: ResultOrder::Lexicographic as i32,
+        max_edits: None,
+        filter: None,
+    });
+    let response = service.complete(request).await.unwrap();
+    let texts: Vec<String> = response.into_inner().completions
+        .into_iter()
+        .map(|c| c.text)
+        .collect();
+    assert_eq!(texts, vec!["new york times", "times square"]);
+
+    let request = Request::new(CompleteRequest {
+        prefix: "new".to_string(),
+        max_results: 10,
+        mode: CompletionMode::Prefix as i32,
+        order: 7,
+        max_edits: None,
+        filter: None,
+    });
+    let status = service.complete(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_graphql_lexicographic_order() {
+    let schema = create_schema(sample(), ResultLimit::default());
+
+    let response = schema
+        .execute(r#"{ complete(prefix: "item 1", maxResults: 3, order: LEXICOGRAPHIC) { completions { text } } }"#)
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        value!({ "complete": { "completions": [
+            { "text": "item 17" },
+            { "text": "item 18" },
+            { "text": "item 19" },
+        ] } })
+    );
+}
+
+#[tokio::test]
+async fn test_grpc_fuzzy_mode() {
+    let service = AutocompleteServiceImpl::new(conjunctive_sample(), ResultLimit::default());
+
+    let fuzzy_request = |prefix: &str, max_edits: Option<i32>| {
+        Request::new(CompleteRequest {
+            prefix: prefix.to_string(),
+            max_results: 10,
+            mode: CompletionMode::Fuzzy as i32,
+            order: ResultOrder::Score as i32,
+            max_edits,
+            filter: None,
+        })
+    };
+    let response = service.complete(fuzzy_request("nwe yrok", Some(2))).await.unwrap();
+    let texts: Vec<String> = response.into_inner().completions
+        .into_iter()
+        .map(|c| c.text)
+        .collect();
+    assert_eq!(texts, vec!["new york", "new york times"]);
+
+    // The default allows a single edit
+    let response = service.complete(fuzzy_request("tmies", None)).await.unwrap();
+    assert_eq!(response.into_inner().completions.len(), 1);
+    let response = service.complete(fuzzy_request("nwe yrok", None)).await.unwrap();
+    assert!(response.into_inner().completions.is_empty());
+
+    // Zero edits asks for exact matches
+    let response = service.complete(fuzzy_request("tmies", Some(0))).await.unwrap();
+    assert!(response.into_inner().completions.is_empty());
+    let response = service.complete(fuzzy_request("times", Some(0))).await.unwrap();
+    assert_eq!(response.into_inner().completions.len(), 1);
+
+    let status = service.complete(fuzzy_request("new", Some(9))).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // Fuzzy queries are rejected without the character trie
+    let without_trie = Autocomplete::with_config(IndexConfig {
+        trie: false,
+        ..IndexConfig::default()
+    });
+    let without_trie = Arc::new(Mutex::new(without_trie));
+    let service = AutocompleteServiceImpl::new(without_trie, ResultLimit::default());
+    let status = service.complete(fuzzy_request("item", Some(1))).await.unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn test_graphql_fuzzy_mode() {
+    let schema = create_schema(conjunctive_sample(), ResultLimit::default());
+
+    let response = schema
+        .execute(r#"{ complete(prefix: "york nwe", mode: FUZZY) { completions { text } } }"#)
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        value!({ "complete": { "completions": [{ "text": "york new tickets" }] } })
+    );
+
+    // An explicit zero allows no edits, unlike the proto default
+    let response = schema
+        .execute(r#"{ complete(prefix: "york nwe", mode: FUZZY, maxEdits: 0) { completions { text } } }"#)
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(response.data, value!({ "complete": { "completions": [] } }));
+
+    let response = schema
+        .execute(r#"{ complete(prefix: "york", mode: FUZZY, maxEdits: -1) { completions { text } } }"#)
+        .await;
+    assert_eq!(response.errors.len(), 1);
+}
+
+#[tokio::test]
+async fn test_grpc_infix_and_substring_modes() {
+    let service = AutocompleteServiceImpl::new(conjunctive_sample(), ResultLimit::default());
+
+    let request = |prefix: &str, mode: CompletionMode| {
+        Request::new(CompleteRequest {
+            prefix: prefix.to_string(),
+            max_results: 10,
+            mode: mode as i32,
+            order: ResultOrder::Score as i32,
+            max_edits: None,
+            filter: None,
+        })
+    };
+    let response = service.complete(request("york", CompletionMode::Infix)).await.unwrap();
+    let texts: Vec<String> = response.into_inner().completions
+        .into_iter()
+        .map(|c| c.text)
+        .collect();
+    assert_eq!(texts, vec!["new york", "new york times", "york new tickets"]);
+
+    let response = service.complete(request("ork", CompletionMode::Infix)).await.unwrap();
+    assert!(response.into_inner().completions.is_empty());
+    let response = service.complete(request("ork t", CompletionMode::Substring)).await.unwrap();
+    let texts: Vec<String> = response.into_inner().completions
+        .into_iter()
+        .map(|c| c.text)
+        .collect();
+    assert_eq!(texts, vec!["new york times"]);
+
+    // Infix and substring queries are rejected unless enabled
+    let service = AutocompleteServiceImpl::new(sample(), ResultLimit::default());
+    let status = service.complete(request("item", CompletionMode::Infix)).await.unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn test_graphql_infix_mode() {
+    let schema = create_schema(conjunctive_sample(), ResultLimit::default());
+
+    let response = schema
+        .execute(r#"{ complete(prefix: "times", mode: INFIX) { completions { text } } }"#)
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        value!({ "complete": { "completions": [
+            { "text": "new york times" },
+            { "text": "times square" },
+        ] } })
+    );
+}
+
+#[tokio::test]
+async fn test_grpc_payloads() {
+    let service = AutocompleteServiceImpl::new(
+        Arc::new(Mutex::new(Autocomplete::new())),
+        ResultLimit::default(),
+    );
+    let payload = Payload {
+        data: b"sku-42".to_vec(),
+        attributes: [("category".to_string(), "phones".to_string())].into_iter().collect(),
+    };
+    let request = Request::new(InitRequest {
+        strings: vec![
+            StringScore {
+                text: "iphone".to_string(),
+                score: 1.0,
+                payload: Some(payload.clone()),
+                tags: vec![],
+            },
+            StringScore { text: "ipad".to_string(), score: 0.5, payload: None, tags: vec![] },
+        ],
+        mode: InitMode::Append as i32,
+    });
+    assert!(service.init(request).await.unwrap().into_inner().success);
+
+    let response = service.complete(complete_request("ip", 10)).await.unwrap();
+    let completions = response.into_inner().completions;
+    assert_eq!(completions[0].payload, Some(payload));
+    assert_eq!(completions[1].payload, None);
+}
+
+#[tokio::test]
+async fn test_graphql_payloads() {
+    let schema = create_schema(Arc::new(Mutex::new(Autocomplete::new())), ResultLimit::default());
+
+    let response = schema
+        .execute(
+            r#"mutation { init(strings: [
+                { text: "iphone", score: 1.0,
+                  payload: { data: "c2t1LTQy", attributes: { category: "phones" } } },
+                { text: "ipad", score: 0.5 },
+                { text: "imac", score: 0.1, payload: { data: "bWFj" } }
+            ]) { success error } }"#,
+        )
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(response.data, value!({ "init": { "success": true, "error": null } }));
+
+    let response = schema
+        .execute(r#"{ complete(prefix: "i") { completions { text payload { data attributes } } } }"#)
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        value!({ "complete": { "completions": [
+            { "text": "iphone", "payload": { "data": "c2t1LTQy", "attributes": { "category": "phones" } } },
+            { "text": "ipad", "payload": null },
+            { "text": "imac", "payload": { "data": "bWFj", "attributes": {} } },
+        ] } })
+    );
+
+    let response = schema
+        .execute(r#"mutation { init(strings: [{ text: "x", score: 1.0, payload: { data: "%%" } }]) { success } }"#)
+        .await;
+    assert_eq!(response.data, value!({ "init": { "success": false } }));
+}
+
+#[tokio::test]
+async fn test_grpc_rejects_non_finite_scores() {
+    let service = AutocompleteServiceImpl::new(sample(), ResultLimit::default());
+    for score in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+        let string = StringScore { text: "item x".to_string(), score, payload: None, tags: vec![] };
+        let request = InitRequest { strings: vec![string.clone()], mode: InitMode::Append as i32 };
+        let status = service.init(Request::new(request)).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        let request = AddRequest { string: Some(string.clone()) };
+        let status = service.add(Request::new(request)).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        let request = UpsertRequest { strings: vec![string] };
+        let status = service.upsert(Request::new(request)).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        let request = UpdateScoreRequest { text: "item 00".to_string(), score };
+        let status = service.update_score(Request::new(request)).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    // The best completion is still ranked by its finite score
+    let response = service.complete(complete_request("item", 1)).await.unwrap();
+    assert_eq!(response.into_inner().completions[0].text, "item 29");
+}
+
+#[tokio::test]
+async fn test_graphql_rejects_non_finite_scores() {
+    let schema = create_schema(sample(), ResultLimit::default());
+
+    // 1e39 is out of the range of f32 and reads as infinity
+    for (mutation, expected) in [
+        (
+            r#"mutation { init(strings: [{ text: "item x", score: 1e39 }]) { success } }"#,
+            value!({ "init": { "success": false } }),
+        ),
+        (
+            r#"mutation { add(string: { text: "item x", score: 1e39 }) { success } }"#,
+            value!({ "add": { "success": false } }),
+        ),
+        (
+            r#"mutation { upsert(strings: [{ text: "item x", score: -1e39 }]) { success } }"#,
+            value!({ "upsert": { "success": false } }),
+        ),
+        (
+            r#"mutation { updateScore(text: "item 00", score: 1e39) { success } }"#,
+            value!({ "updateScore": { "success": false } }),
+        ),
+    ] {
+        let response = schema.execute(mutation).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data, expected, "{}", mutation);
+    }
+
+    let response = schema
+        .execute(r#"{ complete(prefix: "item", maxResults: 1) { completions { text } } }"#)
+        .await;
+    assert_eq!(
+        response.data,
+        value!({ "complete": { "completions": [{ "text": "item 29" }] } })
+    );
+}
+
+#[tokio::test]
+async fn test_grpc_tag_filter() {
+    let service = AutocompleteServiceImpl::new(
+        Arc::new(Mutex::new(Autocomplete::new())),
+        ResultLimit::default(),
+    );
+    let string = |text: &str, score, tags: &[&str]| StringScore {
+        text: text.to_string(),
+        score,
+        payload: None,
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+    };
+    let request = Request::new(InitRequest {
+        strings: vec![
+            string("harry potter", 1.0, &["books"]),
+            string("harry styles", 0.9, &["people", "music"]),
+            string("harrison ford", 0.7, &["people"]),
+        ],
+        mode: InitMode::Append as i32,
+    });
+    assert!(service.init(request).await.unwrap().into_inner().success);
+
+    let complete = |include: &[&str], exclude: &[&str]| {
+        let mut request = complete_request("harr", 10);
+        request.get_mut().filter = Some(TagFilter {
+            include: include.iter().map(|tag| tag.to_string()).collect(),
+            exclude: exclude.iter().map(|tag| tag.to_string()).collect(),
+        });
+        let service = service.clone();
+        async move {
+            let response = service.complete(request).await.unwrap();
+            let completions = response.into_inner().completions;
+            completions.into_iter().map(|c| c.text).collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(complete(&["people"], &[]).await, ["harry styles", "harrison ford"]);
+    assert_eq!(complete(&[], &["people"]).await, ["harry potter"]);
+    assert_eq!(complete(&["people"], &["music"]).await, ["harrison ford"]);
+    assert_eq!(complete(&[], &[]).await.len(), 3);
+}
+
+#[tokio::test]
+async fn test_graphql_tag_filter() {
+    let schema = create_schema(Arc::new(Mutex::new(Autocomplete::new())), ResultLimit::default());
+
+    let response = schema
+        .execute(
+            r#"mutation { init(strings: [
+                { text: "harry potter", score: 1.0, tags: ["books"] },
+                { text: "harry styles", score: 0.9, tags: ["people", "music"] },
+                { text: "harrison ford", score: 0.7, tags: ["people"] }
+            ]) { success } }"#,
+        )
+        .await;
+    assert_eq!(response.data, value!({ "init": { "success": true } }));
+
+    let response = schema
+        .execute(
+            r#"{ complete(prefix: "harr", maxResults: 1,
+                 filter: { include: ["people"], exclude: ["music"] }) { completions { text } } }"#,
+        )
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        value!({ "complete": { "completions": [{ "text": "harrison ford" }] } })
+    );
+}
+
+#[tokio::test]
+async fn test_grpc_incremental_changes() {
+    let service = AutocompleteServiceImpl::new(sample(), ResultLimit::default());
+    let string = |text: &str, score| StringScore {
+        text: text.to_string(),
+        score,
+        payload: None,
+        tags: vec![],
+    };
+
+    let response = service
+        .add(Request::new(AddRequest { string: Some(string("item new", 100.0)) }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.success, "{}", response.error);
+    assert_eq!(response.num_terms, 31);
+    let response = service
+        .add(Request::new(AddRequest { string: Some(string("item new", 1.0)) }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(!response.success);
+    assert!(!response.error.is_empty());
+
+    let request = UpdateScoreRequest { text: "item 00".to_string(), score: 200.0 };
+    assert!(service.update_score(Request::new(request)).await.unwrap().into_inner().success);
+    let response = service.complete(complete_request("item", 2)).await.unwrap();
+    let texts: Vec<String> = response.into_inner().completions
+        .into_iter()
+        .map(|c| c.text)
+        .collect();
+    assert_eq!(texts, vec!["item 00", "item new"]);
+
+    let request = RemoveRequest { text: "item 00".to_string() };
+    let response = service.remove(Request::new(request)).await.unwrap().into_inner();
+    assert!(response.success);
+    assert_eq!(response.num_terms, 30);
+    let request = RemoveRequest { text: "item 00".to_string() };
+    assert!(!service.remove(Request::new(request)).await.unwrap().into_inner().success);
+
+    let request = UpsertRequest { strings: vec![string("item 29", 0.5), string("item zz", 0.1)] };
+    let response = service.upsert(Request::new(request)).await.unwrap().into_inner();
+    assert!(response.success);
+    assert_eq!(response.num_terms, 31);
+    let stats = service.get_stats(Request::new(StatsRequest {})).await.unwrap().into_inner();
+    assert_eq!(stats.num_terms, 31);
+}
+
+#[tokio::test]
+async fn test_graphql_incremental_changes() {
+    let schema = create_schema(conjunctive_sample(), ResultLimit::default());
+
+    let response = schema
+        .execute(
+            r#"mutation {
+                add(string: { text: "new york pizza", score: 2.0 }) { success numTerms }
+                updateScore(text: "times square", score: 3.0) { success }
+                remove(text: "new york") { success numTerms }
+                upsert(strings: [{ text: "new york", score: 0.1 }]) { success numTerms }
+            }"#,
+        )
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        value!({
+            "add": { "success": true, "numTerms": 5 },
+            "updateScore": { "success": true },
+            "remove": { "success": true, "numTerms": 4 },
+            "upsert": { "success": true, "numTerms": 5 },
+        })
+    );
+
+    let response = schema
+        .execute(r#"{ complete(prefix: "new york") { completions { text } } }"#)
+        .await;
+    assert_eq!(
+        response.data,
+        value!({ "complete": { "completions": [
+            { "text": "new york pizza" },
+            { "text": "new york times" },
+            { "text": "new york" },
+        ] } })
+    );
+
+    let response = schema
+        .execute(r#"mutation { remove(text: "boston") { success error } }"#)
+        .await;
+    assert_eq!(
+        response.data,
+        value!({ "remove": { "success": false, "error": "completion \"boston\" not found" } })
+    );
+}
+
+fn init_request(prefix: &str, count: usize, mode: InitMode) -> Request<InitRequest> {
+    Request::new(InitRequest {
+        strings: (0..count)
+            .map(|i| StringScore {
+                text: format!("{} {:04}", prefix, i),
+                score: i as f32,
+                payload: None,
+                tags: vec![],
+            })
+            .collect(),
+        mode: mode as i32,
+    })
+}
+
+#[tokio::test]
+async fn test_grpc_init_modes() {
+    let service = AutocompleteServiceImpl::new(sample(), ResultLimit::default());
+
+    let response = service.init(init_request("extra", 5, InitMode::Append)).await.unwrap();
+    assert!(response.into_inner().success);
+    let stats = service.get_stats(Request::new(StatsRequest {})).await.unwrap().into_inner();
+    assert_eq!(stats.num_terms, 35);
+
+    let response = service.init(init_request("fresh", 3, InitMode::Replace)).await.unwrap();
+    assert!(response.into_inner().success);
+    let stats = service.get_stats(Request::new(StatsRequest {})).await.unwrap().into_inner();
+    assert_eq!(stats.num_terms, 3);
+    let response = service.complete(complete_request("item", 10)).await.unwrap();
+    assert!(response.into_inner().completions.is_empty());
+
+    let mut request = init_request("x", 1, InitMode::Replace);
+    request.get_mut().mode = 7;
+    let status = service.init(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_grpc_changes_are_compacted_in_background() {
+    let autocomplete = sample();
+    let service = AutocompleteServiceImpl::new(autocomplete.clone(), ResultLimit::default());
+    let indexed = autocomplete.lock().await.indexed_generation();
+
+    // A single change stays pending
+    let request = UpdateScoreRequest { text: "item 00".to_string(), score: 200.0 };
+    assert!(service.update_score(Request::new(request)).await.unwrap().into_inner().success);
+    assert_eq!(autocomplete.lock().await.num_pending(), 1);
+
+    // Appended strings are found at once, and indexed in the background
+    let response = service.init(init_request("extra", 5, InitMode::Append)).await.unwrap();
+    assert!(response.into_inner().success);
+    let response = service.complete(complete_request("extra", 10)).await.unwrap();
+    assert_eq!(response.into_inner().completions.len(), 5);
+    service.wait_for_compactions().await;
+    let autocomplete = autocomplete.lock().await;
+    assert_eq!(autocomplete.num_pending(), 0);
+    assert_eq!(autocomplete.indexed_generation(), indexed + 6);
+    assert_eq!(autocomplete.locate_prefix("extra").len(), 5);
+}
+
+#[tokio::test]
+async fn test_grpc_reports_failed_compaction() {
+    let autocomplete = sample();
+    let service = AutocompleteServiceImpl::new(autocomplete.clone(), ResultLimit::default());
+    let string = |text: &str, score| StringScore {
+        text: text.to_string(),
+        score,
+        payload: None,
+        tags: vec![],
+    };
+    {
+        let mut autocomplete = autocomplete.lock().await;
+        autocomplete.update_score("item 00", 200.0).unwrap();
+        let generation = autocomplete.compaction().generation();
+        assert!(autocomplete.fail_compaction(generation, "out of memory".to_string()));
+    }
+
+    let request = AddRequest { string: Some(string("item new", 1.0)) };
+    let response = service.add(Request::new(request)).await.unwrap().into_inner();
+    assert!(!response.success);
+    assert!(response.error.contains("out of memory"), "{}", response.error);
+
+    // Removing a string clears the failure
+    let request = RemoveRequest { text: "item 01".to_string() };
+    assert!(service.remove(Request::new(request)).await.unwrap().into_inner().success);
+    let request = AddRequest { string: Some(string("item new", 1.0)) };
+    assert!(service.add(Request::new(request)).await.unwrap().into_inner().success);
+    assert_eq!(autocomplete.lock().await.compaction_error(), None);
+}
+
+#[tokio::test]
+async fn test_grpc_replace_keeps_concurrent_changes() {
+    let autocomplete = sample();
+    let service = AutocompleteServiceImpl::new(autocomplete.clone(), ResultLimit::default());
+    let texts = |response: tonic::Response<CompleteResponse>| -> Vec<String> {
+        response.into_inner().completions.into_iter().map(|c| c.text).collect()
+    };
+
+    // Build a replacement aside while strings change through the service
+    let mut replacement = autocomplete.lock().await.begin_replace();
+    let strings = vec![("fresh".to_string(), 3.0), ("item 05".to_string(), 2.0)];
+    replacement.fresh.init(&strings).unwrap();
+    let request = UpdateScoreRequest { text: "item 05".to_string(), score: 50.0 };
+    assert!(service.update_score(Request::new(request)).await.unwrap().into_inner().success);
+    let request = RemoveRequest { text: "item 29".to_string() };
+    assert!(service.remove(Request::new(request)).await.unwrap().into_inner().success);
+    let response = service.init(init_request("new", 2, InitMode::Append)).await.unwrap();
+    assert!(response.into_inner().success);
+
+    // Queries see the old strings until the replacement is swapped in
+    let response = service.complete(complete_request("", 2)).await.unwrap();
+    assert_eq!(texts(response), vec!["item 05", "item 28"]);
+    autocomplete.lock().await.replace(replacement).unwrap();
+    service.wait_for_compactions().await;
+    let response = service.complete(complete_request("", 10)).await.unwrap();
+    assert_eq!(texts(response), vec!["item 05", "fresh", "new 0001", "new 0000"]);
+}
+
+#[tokio::test]
+async fn test_graphql_init_replace() {
+    let schema = create_schema(conjunctive_sample(), ResultLimit::default());
+
+    let response = schema
+        .execute(
+            r#"mutation {
+                init(mode: REPLACE, strings: [{ text: "boston", score: 1.0 }]) { success }
+            }"#,
+        )
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(response.data, value!({ "init": { "success": true } }));
+
+    let response = schema.execute(r#"{ stats { numTerms } }"#).await;
+    assert_eq!(response.data, value!({ "stats": { "numTerms": 1 } }));
+}
+
+#[test]
+fn test_schema_file_matches_sdl() {
+    let schema = create_schema(Arc::new(Mutex::new(Autocomplete::new())), ResultLimit::default());
+    // The SDL leaves blank lines in place of the types it does not print
+    let mut sdl = String::new();
+    for line in schema.sdl().trim_matches('\n').lines() {
+        if !(line.is_empty() && sdl.ends_with("\n\n")) {
+            sdl.push_str(line);
+            sdl.push('\n');
+        }
+    }
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/schema/schema.graphql");
+    let file = std::fs::read_to_string(path).unwrap();
+    assert_eq!(file, sdl, "schema/schema.graphql differs from the SDL of create_schema");
+}